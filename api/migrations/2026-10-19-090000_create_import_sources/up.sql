@@ -0,0 +1,21 @@
+CREATE TABLE import_sources (
+    id SERIAL PRIMARY KEY NOT NULL,
+    token INTEGER NOT NULL REFERENCES tokens(id) ON DELETE CASCADE,
+    namespace VARCHAR NOT NULL,
+    uri VARCHAR NOT NULL,
+    pattern VARCHAR,
+    template VARCHAR,
+    schedule VARCHAR NOT NULL,
+    clear_before BOOLEAN NOT NULL,
+    creation_timestamp TIMESTAMP NOT NULL,
+    next_run TIMESTAMP
+);
+
+CREATE TABLE import_runs (
+    id SERIAL PRIMARY KEY NOT NULL,
+    source INTEGER NOT NULL REFERENCES import_sources(id) ON DELETE CASCADE,
+    start_timestamp TIMESTAMP NOT NULL,
+    end_timestamp TIMESTAMP,
+    success BOOLEAN,
+    message VARCHAR
+);
@@ -0,0 +1,6 @@
+CREATE TABLE translation_usage (
+    token INTEGER NOT NULL REFERENCES tokens(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (token, day)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE saved_queries (
+    id SERIAL PRIMARY KEY NOT NULL,
+    token INTEGER NOT NULL REFERENCES tokens(id) ON DELETE CASCADE,
+    namespace VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    patterns TEXT[] NOT NULL,
+    templates TEXT[] NOT NULL,
+    parameters TEXT[] NOT NULL,
+    creation_timestamp TIMESTAMP NOT NULL,
+    UNIQUE (namespace, name)
+);
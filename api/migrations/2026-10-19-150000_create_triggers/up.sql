@@ -0,0 +1,14 @@
+CREATE TABLE triggers (
+    id SERIAL PRIMARY KEY NOT NULL,
+    token INTEGER NOT NULL REFERENCES tokens(id) ON DELETE CASCADE,
+    namespace VARCHAR NOT NULL,
+    target_namespace VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    patterns TEXT[] NOT NULL,
+    templates TEXT[] NOT NULL,
+    creation_timestamp TIMESTAMP NOT NULL,
+    last_run_timestamp TIMESTAMP,
+    last_error VARCHAR
+);
+
+CREATE INDEX triggers_namespace ON triggers (namespace);
@@ -0,0 +1,10 @@
+CREATE TABLE webhooks (
+    id SERIAL PRIMARY KEY NOT NULL,
+    token INTEGER NOT NULL REFERENCES tokens(id) ON DELETE CASCADE,
+    namespace VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    creation_timestamp TIMESTAMP NOT NULL,
+    last_delivery_timestamp TIMESTAMP,
+    last_status VARCHAR
+);
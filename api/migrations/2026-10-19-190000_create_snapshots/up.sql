@@ -0,0 +1,11 @@
+CREATE TABLE snapshots (
+    id SERIAL PRIMARY KEY NOT NULL,
+    token INTEGER REFERENCES tokens(id) ON DELETE SET NULL,
+    namespace VARCHAR NOT NULL,
+    description VARCHAR NOT NULL,
+    atoms INTEGER NOT NULL,
+    bytes BIGINT NOT NULL,
+    creation_timestamp TIMESTAMP NOT NULL
+);
+
+CREATE INDEX snapshots_namespace ON snapshots (namespace);
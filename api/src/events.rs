@@ -0,0 +1,128 @@
+use chrono::{NaiveDateTime, Utc};
+use rocket::fairing::AdHoc;
+use rocket::tokio::sync::broadcast;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+/// Number of events a slow subscriber may fall behind before it misses some
+const CAPACITY: usize = 1024;
+
+/// Mutating operations on a namespace
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Upload,
+    Import,
+    Transform,
+    Clear,
+}
+
+impl Operation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Upload => "upload",
+            Operation::Import => "import",
+            Operation::Transform => "transform",
+            Operation::Clear => "clear",
+        }
+    }
+}
+
+/// A mutating operation that was attempted on a namespace
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NamespaceEvent {
+    pub operation: Operation,
+    /// In the `/space/subspace/` form
+    pub namespace: String,
+    /// Id of the token the operation ran on behalf of
+    pub token: i32,
+    pub success: bool,
+    /// Number of triggers that led to this operation, 0 for requests
+    pub depth: usize,
+    /// Bytes of data written for uploads and imports; `None` for transforms and clears, as Mork
+    /// does not report what they write
+    pub size: Option<u64>,
+    pub timestamp: NaiveDateTime,
+}
+
+impl NamespaceEvent {
+    pub fn new(operation: Operation, namespace: String, token: i32, success: bool) -> Self {
+        NamespaceEvent {
+            operation,
+            namespace,
+            token,
+            success,
+            depth: 0,
+            size: None,
+            timestamp: Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+/// Broadcasts [`NamespaceEvent`]s within this process, to triggers, webhooks and subscriptions
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<NamespaceEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        EventBus { sender }
+    }
+}
+
+impl EventBus {
+    /// Sends `event` to the current subscribers, if there are any
+    pub fn publish(&self, event: NamespaceEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NamespaceEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Fairing that runs `handle` in the background for every event published after liftoff
+pub fn subscriber<F, Fut>(name: &'static str, handle: F) -> AdHoc
+where
+    F: Fn(EventBus, NamespaceEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    AdHoc::on_liftoff(name, move |rocket| {
+        let events = rocket.state::<EventBus>().cloned();
+
+        Box::pin(async move {
+            let Some(events) = events else {
+                eprintln!("No event bus, {name} will not run");
+                return;
+            };
+            let mut receiver = events.subscribe();
+
+            rocket::tokio::spawn(async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => {
+                            rocket::tokio::spawn(handle(events.clone(), event));
+                        }
+                        Err(RecvError::Lagged(missed)) => {
+                            eprintln!("{name} fell behind, missed {missed} events");
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+        })
+    })
+}
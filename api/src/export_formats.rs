@@ -0,0 +1,622 @@
+//! Translations from MeTTa back to the formats that `/translations/*` imports, the inverse of
+//! the conventions described in the translations README, and to graph formats for visualization.
+
+use rocket::form::FromFormField;
+use rocket::serde::json::serde_json::{self, Map, Value};
+use std::collections::HashMap;
+
+use crate::metta::{parse_all, unescape, Expr};
+
+const STRING_DATATYPE: &str = "http://www.w3.org/2001/XMLSchema#string";
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+/// Format of the result of `/spaces/export`
+#[derive(FromFormField, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The atoms as exported by MORK
+    #[default]
+    Metta,
+    /// Triples in the shape of the N-Triples, Turtle and RDF/XML translations
+    NTriples,
+    /// Same triples as for `NTriples`, `Namespace` atoms become the `@context`
+    JsonLd,
+    /// Atoms in the shape of one of the CSV translations
+    Csv,
+    /// Graphviz graph of triples or binary relations, see [`graph`]
+    Dot,
+    /// Same graph as for `Dot`
+    GraphMl,
+    /// Same graph as for `Dot`, as Cytoscape.js `elements`
+    Cytoscape,
+}
+
+/// Converts the `metta` exported by MORK to `format`. `labels` are the node-label predicates
+/// of the graph formats and ignored by the others.
+pub fn convert(metta: &str, format: OutputFormat, labels: &[String]) -> Result<String, String> {
+    if format == OutputFormat::Metta {
+        return Ok(metta.to_string());
+    }
+
+    let atoms = parse_all(metta).map_err(|e| format!("invalid export: {e}"))?;
+
+    match format {
+        OutputFormat::Metta => unreachable!(),
+        OutputFormat::NTriples => to_ntriples(&atoms),
+        OutputFormat::JsonLd => to_jsonld(&atoms).map(|v| v.to_string()),
+        OutputFormat::Csv => to_csv(&atoms),
+        OutputFormat::Dot => graph(&atoms, labels).map(|g| g.to_dot()),
+        OutputFormat::GraphMl => graph(&atoms, labels).map(|g| g.to_graphml()),
+        OutputFormat::Cytoscape => graph(&atoms, labels).map(|g| g.to_cytoscape().to_string()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Iri(String),
+    Blank(String),
+    Literal {
+        value: String,
+        datatype: String,
+        language: Option<String>,
+    },
+}
+
+/// Text of a symbol or string literal
+fn text(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Symbol(s) => Some(s.clone()),
+        Expr::Str(s) => Some(unescape(s)),
+        _ => None,
+    }
+}
+
+/// `(uriref <iri>)`, `(bnode <label>)` or `((literal (<datatype> [<language>])) "<value>")`
+fn term(expr: &Expr) -> Option<Term> {
+    match expr.children()? {
+        [Expr::Symbol(kind), value] if kind == "uriref" => text(value).map(Term::Iri),
+        [Expr::Symbol(kind), label] if kind == "bnode" => text(label).map(Term::Blank),
+        [literal, value] => {
+            let [Expr::Symbol(kind), datatype] = literal.children()? else {
+                return None;
+            };
+            if kind != "literal" {
+                return None;
+            }
+            let (datatype, language) = match datatype.children()? {
+                [datatype] => (text(datatype)?, None),
+                [datatype, language] => (text(datatype)?, Some(text(language)?)),
+                _ => return None,
+            };
+            Some(Term::Literal {
+                value: text(value)?,
+                datatype,
+                language,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `(Namespace ("<prefix>" "<iri>"))`
+fn namespace(expr: &Expr) -> Option<(String, String)> {
+    match expr.children()? {
+        [Expr::Symbol(kind), binding] if kind == "Namespace" => match binding.children()? {
+            [prefix, iri] => Some((text(prefix)?, text(iri)?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+type Triple = (Term, Term, Term);
+/// `(prefix, iri)` pairs
+type Namespaces = Vec<(String, String)>;
+
+/// Splits `atoms` in triples and namespace bindings, failing on the first atom that is neither
+fn triples(atoms: &[Expr]) -> Result<(Vec<Triple>, Namespaces), String> {
+    let mut triples = vec![];
+    let mut namespaces = vec![];
+
+    for atom in atoms {
+        if let Some(binding) = namespace(atom) {
+            namespaces.push(binding);
+            continue;
+        }
+        let triple = match atom.children() {
+            Some([s, p, o]) => term(s).zip(term(p)).zip(term(o)),
+            _ => None,
+        };
+        match triple {
+            Some(((s, p), o)) => triples.push((s, p, o)),
+            None => return Err(format!("not a triple: {atom}")),
+        }
+    }
+
+    Ok((triples, namespaces))
+}
+
+fn escape_ntriples(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn ntriples_term(term: &Term) -> String {
+    match term {
+        Term::Iri(iri) => format!("<{iri}>"),
+        Term::Blank(label) => format!("_:{label}"),
+        Term::Literal {
+            value,
+            language: Some(language),
+            ..
+        } => format!("\"{}\"@{language}", escape_ntriples(value)),
+        Term::Literal {
+            value, datatype, ..
+        } if datatype == STRING_DATATYPE => format!("\"{}\"", escape_ntriples(value)),
+        Term::Literal {
+            value, datatype, ..
+        } => format!("\"{}\"^^<{datatype}>", escape_ntriples(value)),
+    }
+}
+
+/// One line per triple, `Namespace` atoms are dropped as N-Triples has no prefixes
+pub fn to_ntriples(atoms: &[Expr]) -> Result<String, String> {
+    let (triples, _) = triples(atoms)?;
+
+    Ok(triples
+        .iter()
+        .map(|(s, p, o)| {
+            format!(
+                "{} {} {} .\n",
+                ntriples_term(s),
+                ntriples_term(p),
+                ntriples_term(o)
+            )
+        })
+        .collect())
+}
+
+/// `<prefix>:<local>` if one of the namespaces is a prefix of `iri`
+fn compact(iri: &str, namespaces: &[(String, String)]) -> String {
+    namespaces
+        .iter()
+        .filter(|(_, ns)| !ns.is_empty() && iri.starts_with(ns.as_str()))
+        .max_by_key(|(_, ns)| ns.len())
+        .map(|(prefix, ns)| format!("{prefix}:{}", &iri[ns.len()..]))
+        .unwrap_or_else(|| iri.to_string())
+}
+
+fn node_id(term: &Term, namespaces: &[(String, String)]) -> Option<String> {
+    match term {
+        Term::Iri(iri) => Some(compact(iri, namespaces)),
+        Term::Blank(label) => Some(format!("_:{label}")),
+        Term::Literal { .. } => None,
+    }
+}
+
+fn jsonld_value(term: &Term, namespaces: &[(String, String)]) -> Value {
+    match term {
+        Term::Literal {
+            value,
+            language: Some(language),
+            ..
+        } => serde_json::json!({"@value": value, "@language": language}),
+        Term::Literal {
+            value, datatype, ..
+        } if datatype == STRING_DATATYPE => serde_json::json!({ "@value": value }),
+        Term::Literal {
+            value, datatype, ..
+        } => serde_json::json!({"@value": value, "@type": compact(datatype, namespaces)}),
+        node => serde_json::json!({ "@id": node_id(node, namespaces) }),
+    }
+}
+
+/// Flattened JSON-LD: a `@graph` with one node object per subject, in order of appearance.
+/// IRIs are compacted with the `Namespace` atoms, which make up the `@context`.
+pub fn to_jsonld(atoms: &[Expr]) -> Result<Value, String> {
+    let (triples, namespaces) = triples(atoms)?;
+
+    let mut nodes: Vec<Map<String, Value>> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for (s, p, o) in &triples {
+        let (Some(id), Some(property)) = (node_id(s, &namespaces), node_id(p, &namespaces)) else {
+            return Err(format!(
+                "literal used as subject or predicate: {}",
+                ntriples_term(if matches!(s, Term::Literal { .. }) {
+                    s
+                } else {
+                    p
+                })
+            ));
+        };
+        // `@id` would overwrite the node id, other keywords would be read as something else
+        if property.starts_with('@') {
+            return Err(format!(
+                "predicate {} is the JSON-LD keyword {property}",
+                ntriples_term(p)
+            ));
+        }
+        let i = *index.entry(id.clone()).or_insert_with(|| {
+            nodes.push(Map::from_iter([("@id".to_string(), Value::String(id))]));
+            nodes.len() - 1
+        });
+
+        let (key, value) = match (p, node_id(o, &namespaces)) {
+            (Term::Iri(iri), Some(class)) if iri == RDF_TYPE => ("@type".to_string(), class.into()),
+            _ => (property, jsonld_value(o, &namespaces)),
+        };
+        match nodes[i].entry(key).or_insert_with(|| Value::Array(vec![])) {
+            Value::Array(values) => values.push(value),
+            other => {
+                return Err(format!(
+                    "conflicting values for {}: {other}",
+                    ntriples_term(s)
+                ))
+            }
+        }
+    }
+
+    let mut document = Map::new();
+    if !namespaces.is_empty() {
+        let context = namespaces
+            .into_iter()
+            .filter(|(_, iri)| !iri.is_empty())
+            .map(|(prefix, iri)| (prefix, Value::String(iri)))
+            .collect();
+        document.insert("@context".to_string(), Value::Object(context));
+    }
+    document.insert(
+        "@graph".to_string(),
+        Value::Array(nodes.into_iter().map(Value::Object).collect()),
+    );
+
+    Ok(Value::Object(document))
+}
+
+/// A row or column of a table: numbered, or labeled in the first column or the header
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Index(u64),
+    Label(String),
+}
+
+fn key(expr: &Expr) -> Option<Key> {
+    match expr {
+        Expr::Symbol(s) => Some(s.parse().map(Key::Index).unwrap_or(Key::Label(s.clone()))),
+        Expr::Str(s) => Some(Key::Label(unescape(s))),
+        _ => None,
+    }
+}
+
+fn cell(expr: &Expr) -> String {
+    text(expr).unwrap_or_else(|| expr.to_string())
+}
+
+#[derive(Default)]
+struct Table {
+    header: Option<Vec<String>>,
+    rows: Vec<Key>,
+    columns: Vec<Key>,
+    cells: HashMap<(Key, Key), String>,
+}
+
+impl Table {
+    fn insert(&mut self, row: Key, column: Key, value: String) {
+        if !self.rows.contains(&row) {
+            self.rows.push(row.clone());
+        }
+        if !self.columns.contains(&column) {
+            self.columns.push(column.clone());
+        }
+        self.cells.insert((row, column), value);
+    }
+
+    /// Reads one atom in any of the shapes of the CSV translations
+    fn add(&mut self, atom: &Expr, ordinal: usize) -> Option<()> {
+        let children = atom.children()?;
+
+        match children {
+            // (header (<label>..))
+            [Expr::Symbol(h), labels] if h.eq_ignore_ascii_case("header") => {
+                self.header = Some(labels.children()?.iter().map(cell).collect());
+            }
+            // (= (value (<row> <column>)) <value>), or (<column> <row>) for the functional shape
+            [Expr::Symbol(eq), function, value] if eq == "=" => {
+                let [Expr::Symbol(name), arguments] = function.children()? else {
+                    return None;
+                };
+                let [first, second] = arguments.children()? else {
+                    return None;
+                };
+                if name != "value" {
+                    return None;
+                }
+                match (key(first)?, key(second)?) {
+                    (Key::Label(column), Key::Index(row)) => {
+                        self.insert(Key::Index(row), Key::Label(column), cell(value))
+                    }
+                    (row, column) => self.insert(row, column, cell(value)),
+                }
+            }
+            // (<row> (<value>..)), or (<label> (<value>..)) for columns with a header
+            [first, values] if values.children().is_some() && first.children().is_none() => {
+                let values = values.children()?;
+                match key(first)? {
+                    Key::Index(row) => values.iter().enumerate().for_each(|(j, v)| {
+                        self.insert(Key::Index(row), Key::Index(j as u64), cell(v))
+                    }),
+                    Key::Label(column) => values.iter().enumerate().for_each(|(i, v)| {
+                        self.insert(Key::Index(i as u64), Key::Label(column.clone()), cell(v))
+                    }),
+                }
+            }
+            // (<row> <label> <value>)
+            [row, label, value] if row.children().is_none() && label.children().is_none() => {
+                let Key::Index(row) = key(row)? else {
+                    return None;
+                };
+                self.insert(Key::Index(row), Key::Label(text(label)?), cell(value));
+            }
+            // (("<label>" <value>)..)
+            fields => {
+                for field in fields {
+                    let [Expr::Str(label), value] = field.children()? else {
+                        return None;
+                    };
+                    self.insert(
+                        Key::Index(ordinal as u64),
+                        Key::Label(unescape(label)),
+                        cell(value),
+                    );
+                }
+            }
+        }
+
+        Some(())
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Rebuilds the table from atoms in any of the row or cell based shapes of the CSV
+/// translations. Numbered rows and columns are sorted, labeled ones stay in order of appearance
+/// and become the header row and first column.
+pub fn to_csv(atoms: &[Expr]) -> Result<String, String> {
+    let mut table = Table::default();
+
+    for (ordinal, atom) in atoms.iter().enumerate() {
+        if table.add(atom, ordinal).is_none() {
+            return Err(format!("not a table row or cell: {atom}"));
+        }
+    }
+
+    let sort = |keys: &mut Vec<Key>| {
+        if keys.iter().all(|k| matches!(k, Key::Index(_))) {
+            keys.sort_by_key(|k| match k {
+                Key::Index(i) => *i,
+                Key::Label(_) => 0,
+            });
+        }
+    };
+    sort(&mut table.rows);
+    sort(&mut table.columns);
+
+    let label = |k: &Key| match k {
+        Key::Index(i) => i.to_string(),
+        Key::Label(l) => l.clone(),
+    };
+    let labeled_rows = table.rows.iter().any(|k| matches!(k, Key::Label(_)));
+    let header = table.header.clone().or_else(|| {
+        table
+            .columns
+            .iter()
+            .any(|k| matches!(k, Key::Label(_)))
+            .then(|| table.columns.iter().map(label).collect())
+    });
+
+    let mut lines = vec![];
+    if let Some(header) = header {
+        let corner = labeled_rows.then(String::new);
+        lines.push(corner.into_iter().chain(header).collect::<Vec<_>>());
+    }
+    for row in &table.rows {
+        let first = labeled_rows.then(|| label(row));
+        let cells = table.columns.iter().map(|column| {
+            table
+                .cells
+                .get(&(row.clone(), column.clone()))
+                .cloned()
+                .unwrap_or_default()
+        });
+        lines.push(first.into_iter().chain(cells).collect());
+    }
+
+    Ok(lines
+        .iter()
+        .map(|line| {
+            let fields: Vec<String> = line.iter().map(|f| escape_csv(f)).collect();
+            fields.join(",") + "\r\n"
+        })
+        .collect())
+}
+
+/// Nodes and labeled edges, in order of appearance
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Graph {
+    /// `(id, label)`
+    pub nodes: Vec<(String, String)>,
+    /// `(source, target, label)`
+    pub edges: Vec<(String, String, String)>,
+}
+
+impl Graph {
+    fn node(&mut self, id: String, label: String) -> String {
+        if !self.nodes.iter().any(|(n, _)| *n == id) {
+            self.nodes.push((id.clone(), label));
+        }
+        id
+    }
+
+    fn label(&mut self, id: String, label: String) {
+        match self.nodes.iter_mut().find(|(n, _)| *n == id) {
+            Some(node) => node.1 = label,
+            None => self.nodes.push((id, label)),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = String::from("digraph {\n");
+        for (id, label) in &self.nodes {
+            dot += &format!("  {} [label={}];\n", quote(id), quote(label));
+        }
+        for (source, target, label) in &self.edges {
+            dot += &format!(
+                "  {} -> {} [label={}];\n",
+                quote(source),
+                quote(target),
+                quote(label)
+            );
+        }
+        dot + "}\n"
+    }
+
+    /// Node and edge ids are generated, as GraphML wants XML names, the node ids of the graph
+    /// are kept in the `id` data key
+    pub fn to_graphml(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"id\" for=\"node\" attr.name=\"id\" attr.type=\"string\"/>\n",
+            "  <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <graph edgedefault=\"directed\">\n",
+        ));
+        let mut index = HashMap::new();
+        for (i, (id, label)) in self.nodes.iter().enumerate() {
+            index.insert(id, i);
+            xml += &format!(
+                "    <node id=\"n{i}\"><data key=\"id\">{}</data><data key=\"label\">{}</data></node>\n",
+                escape(id),
+                escape(label)
+            );
+        }
+        for (i, (source, target, label)) in self.edges.iter().enumerate() {
+            xml += &format!(
+                "    <edge id=\"e{i}\" source=\"n{}\" target=\"n{}\"><data key=\"label\">{}</data></edge>\n",
+                index[source],
+                index[target],
+                escape(label)
+            );
+        }
+        xml + "  </graph>\n</graphml>\n"
+    }
+
+    /// `{"elements": {"nodes": [..], "edges": [..]}}`, which `cytoscape({elements})` accepts
+    pub fn to_cytoscape(&self) -> Value {
+        let nodes: Vec<Value> = self
+            .nodes
+            .iter()
+            .map(|(id, label)| serde_json::json!({"data": {"id": id, "label": label}}))
+            .collect();
+        let edges: Vec<Value> = self
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(i, (source, target, label))| {
+                serde_json::json!({"data": {
+                    "id": format!("e{i}"),
+                    "source": source,
+                    "target": target,
+                    "label": label,
+                }})
+            })
+            .collect();
+
+        serde_json::json!({"elements": {"nodes": nodes, "edges": edges}})
+    }
+}
+
+/// Node id and label of an RDF term, literals are identified by their N-Triples form
+fn graph_node(term: &Term, namespaces: &[(String, String)]) -> (String, String) {
+    match term {
+        Term::Literal { value, .. } => (ntriples_term(term), value.clone()),
+        node => {
+            let id = node_id(node, namespaces).unwrap_or_default();
+            (id.clone(), id)
+        }
+    }
+}
+
+/// Reads edges from triples `(<subject> <predicate> <object>)` in the shape of the RDF
+/// translations, or from binary relations `(<predicate> <source> <target>)`. Edges with one of
+/// the `labels` predicates are not drawn but name their source instead. IRIs are compacted with
+/// the `Namespace` atoms, `labels` may use either form.
+pub fn graph(atoms: &[Expr], labels: &[String]) -> Result<Graph, String> {
+    let namespaces: Namespaces = atoms.iter().filter_map(namespace).collect();
+    let mut graph = Graph::default();
+
+    for atom in atoms {
+        if namespace(atom).is_some() {
+            continue;
+        }
+        let (source, predicate, target, is_label) = match atom.children() {
+            Some([s, p, o]) => match (term(s), term(p), term(o)) {
+                (Some(s), Some(p), Some(o)) => {
+                    let predicate = graph_node(&p, &namespaces).0;
+                    let is_label = labels
+                        .iter()
+                        .any(|l| *l == predicate || p == Term::Iri(l.clone()));
+                    (
+                        graph_node(&s, &namespaces),
+                        predicate,
+                        graph_node(&o, &namespaces),
+                        is_label,
+                    )
+                }
+                _ if s.children().is_none() => {
+                    let (source, target, predicate) = (cell(p), cell(o), cell(s));
+                    let is_label = labels.contains(&predicate);
+                    (
+                        (source.clone(), source),
+                        predicate,
+                        (target.clone(), target),
+                        is_label,
+                    )
+                }
+                _ => return Err(format!("not an edge: {atom}")),
+            },
+            _ => return Err(format!("not an edge: {atom}")),
+        };
+
+        if is_label {
+            graph.label(source.0, target.1);
+            continue;
+        }
+        let source = graph.node(source.0, source.1);
+        let target = graph.node(target.0, target.1);
+        graph.edges.push((source, target, predicate));
+    }
+
+    Ok(graph)
+}
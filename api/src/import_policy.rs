@@ -0,0 +1,330 @@
+use reqwest::{redirect, Client};
+use rocket::http::Status;
+use serde::Serialize;
+use std::env;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use url::Url;
+
+const DEFAULT_MAX_FETCH_BYTES: u64 = 100 * 1024 * 1024;
+const DEFAULT_MAX_FETCH_SECS: u64 = 20;
+
+/// Reasons for refusing to fetch a uri on behalf of a client.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum ImportPolicyError {
+    InvalidUri,
+    SchemeNotAllowed(String),
+    HostNotAllowed(String),
+    UnresolvableHost(String),
+    PrivateAddress(IpAddr),
+    TooLarge(u64),
+    /// The remote redirects elsewhere, which would bypass the checks
+    Redirect(String),
+    /// The remote answered with an error status, or not at all
+    Unreachable(String),
+    /// The remote took longer than the time limit
+    TimedOut,
+    /// The resource is not UTF-8 text
+    NotText,
+}
+
+impl ImportPolicyError {
+    /// Stable, machine readable code, sent as the prefix of the error body
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImportPolicyError::InvalidUri => "invalid_uri",
+            ImportPolicyError::SchemeNotAllowed(_) => "scheme_not_allowed",
+            ImportPolicyError::HostNotAllowed(_) => "host_not_allowed",
+            ImportPolicyError::UnresolvableHost(_) => "unresolvable_host",
+            ImportPolicyError::PrivateAddress(_) => "private_address",
+            ImportPolicyError::TooLarge(_) => "too_large",
+            ImportPolicyError::Redirect(_) => "redirect_not_allowed",
+            ImportPolicyError::Unreachable(_) => "unreachable",
+            ImportPolicyError::TimedOut => "timed_out",
+            ImportPolicyError::NotText => "not_text",
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ImportPolicyError::InvalidUri | ImportPolicyError::UnresolvableHost(_) => {
+                Status::BadRequest
+            }
+            ImportPolicyError::TooLarge(_) => Status::PayloadTooLarge,
+            ImportPolicyError::Unreachable(_) => Status::BadGateway,
+            ImportPolicyError::TimedOut => Status::GatewayTimeout,
+            ImportPolicyError::NotText => Status::UnprocessableEntity,
+            _ => Status::Forbidden,
+        }
+    }
+}
+
+impl fmt::Display for ImportPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportPolicyError::InvalidUri => write!(f, "uri could not be parsed"),
+            ImportPolicyError::SchemeNotAllowed(scheme) => {
+                write!(f, "scheme '{scheme}' is not allowed")
+            }
+            ImportPolicyError::HostNotAllowed(host) => write!(f, "host '{host}' is not allowed"),
+            ImportPolicyError::UnresolvableHost(host) => {
+                write!(f, "host '{host}' could not be resolved")
+            }
+            ImportPolicyError::PrivateAddress(ip) => {
+                write!(f, "uri resolves to non-public address {ip}")
+            }
+            ImportPolicyError::TooLarge(size) => {
+                write!(f, "remote resource exceeds the limit after {size} bytes")
+            }
+            ImportPolicyError::Redirect(location) => {
+                write!(f, "remote redirects to '{location}'")
+            }
+            ImportPolicyError::Unreachable(reason) => {
+                write!(f, "remote could not be fetched: {reason}")
+            }
+            ImportPolicyError::TimedOut => {
+                write!(f, "remote did not answer within the time limit")
+            }
+            ImportPolicyError::NotText => write!(f, "remote resource is not UTF-8 text"),
+        }
+    }
+}
+
+/// Restrictions on the uris that `/spaces/import` and other routes may fetch data from.
+///
+/// Configured through `METTA_KG_IMPORT_*` environment variables, see [`ImportPolicy::from_env`].
+/// Host patterns match exactly, or match any subdomain when written as `*.example.com`.
+#[derive(Clone, Debug)]
+pub struct ImportPolicy {
+    pub allowed_schemes: Vec<String>,
+    pub denied_schemes: Vec<String>,
+    /// Empty means every host is allowed, unless denied
+    pub allowed_hosts: Vec<String>,
+    pub denied_hosts: Vec<String>,
+    pub allow_private_addresses: bool,
+    pub max_fetch_bytes: u64,
+    pub max_fetch_time: Duration,
+}
+
+impl Default for ImportPolicy {
+    fn default() -> Self {
+        ImportPolicy {
+            allowed_schemes: vec![String::from("http"), String::from("https")],
+            denied_schemes: vec![],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            allow_private_addresses: false,
+            max_fetch_bytes: DEFAULT_MAX_FETCH_BYTES,
+            max_fetch_time: Duration::from_secs(DEFAULT_MAX_FETCH_SECS),
+        }
+    }
+}
+
+fn env_list(key: &str) -> Option<Vec<String>> {
+    env::var(key).ok().map(|value| {
+        value
+            .split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect()
+    })
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host.ends_with(&format!(".{suffix}")),
+        None => pattern == host,
+    }
+}
+
+/// Whether `ip` points into loopback, private, link-local or otherwise non-public ranges
+pub fn is_non_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_non_public_ipv4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_non_public_ipv4(mapped);
+            }
+            is_non_public_ipv6(ip)
+        }
+    }
+}
+
+fn is_non_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // 0.0.0.0/8 "this network"
+        || a == 0
+        // 100.64.0.0/10 carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // 198.18.0.0/15 benchmarking
+        || (a == 198 && (b == 18 || b == 19))
+        // 240.0.0.0/4 reserved
+        || a >= 240
+}
+
+fn is_non_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // fc00::/7 unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 link-local
+        || (first & 0xffc0) == 0xfe80
+        // 2001:db8::/32 documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+        // 64:ff9b::/96 and 64:ff9b:1::/48 NAT64, which reach IPv4 addresses through a gateway
+        || (first == 0x0064 && ip.segments()[1] == 0xff9b)
+        // 2002::/16 6to4, likewise
+        || first == 0x2002
+}
+
+impl ImportPolicy {
+    /// Reads the policy from the environment, falling back to [`ImportPolicy::default`]:
+    ///
+    /// - `METTA_KG_IMPORT_ALLOWED_SCHEMES`, `METTA_KG_IMPORT_DENIED_SCHEMES`: comma separated
+    /// - `METTA_KG_IMPORT_ALLOWED_HOSTS`, `METTA_KG_IMPORT_DENIED_HOSTS`: comma separated
+    /// - `METTA_KG_IMPORT_ALLOW_PRIVATE`: `true` to allow non-public addresses
+    /// - `METTA_KG_IMPORT_MAX_BYTES`, `METTA_KG_IMPORT_MAX_SECONDS`
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        ImportPolicy {
+            allowed_schemes: env_list("METTA_KG_IMPORT_ALLOWED_SCHEMES")
+                .unwrap_or(default.allowed_schemes),
+            denied_schemes: env_list("METTA_KG_IMPORT_DENIED_SCHEMES")
+                .unwrap_or(default.denied_schemes),
+            allowed_hosts: env_list("METTA_KG_IMPORT_ALLOWED_HOSTS")
+                .unwrap_or(default.allowed_hosts),
+            denied_hosts: env_list("METTA_KG_IMPORT_DENIED_HOSTS").unwrap_or(default.denied_hosts),
+            allow_private_addresses: env::var("METTA_KG_IMPORT_ALLOW_PRIVATE")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(default.allow_private_addresses),
+            max_fetch_bytes: env::var("METTA_KG_IMPORT_MAX_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_fetch_bytes),
+            max_fetch_time: env::var("METTA_KG_IMPORT_MAX_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_fetch_time),
+        }
+    }
+
+    /// Checks scheme and host against the allow- and denylists, without any network access
+    pub fn check_url(&self, url: &Url) -> Result<(), ImportPolicyError> {
+        let scheme = url.scheme().to_lowercase();
+
+        if self.denied_schemes.contains(&scheme) || !self.allowed_schemes.contains(&scheme) {
+            return Err(ImportPolicyError::SchemeNotAllowed(scheme));
+        }
+
+        let host = match url.host_str() {
+            Some(host) => host.trim_matches(|c| c == '[' || c == ']').to_lowercase(),
+            None => return Err(ImportPolicyError::InvalidUri),
+        };
+
+        if self.denied_hosts.iter().any(|p| host_matches(p, &host)) {
+            return Err(ImportPolicyError::HostNotAllowed(host));
+        }
+
+        if !self.allowed_hosts.is_empty()
+            && !self.allowed_hosts.iter().any(|p| host_matches(p, &host))
+        {
+            return Err(ImportPolicyError::HostNotAllowed(host));
+        }
+
+        Ok(())
+    }
+
+    /// Applies [`ImportPolicy::check_url`], resolves the host and rejects non-public addresses.
+    /// Also used for uris the API sends data to, such as webhooks.
+    pub async fn check_host(&self, url: &Url) -> Result<(), ImportPolicyError> {
+        self.check_url(url)?;
+
+        let host = url.host_str().ok_or(ImportPolicyError::InvalidUri)?;
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let addresses: Vec<IpAddr> = match url.host() {
+            Some(url::Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+            Some(url::Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+            _ => rocket::tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|_| ImportPolicyError::UnresolvableHost(host.to_string()))?
+                .map(|addr| addr.ip())
+                .collect(),
+        };
+
+        if addresses.is_empty() {
+            return Err(ImportPolicyError::UnresolvableHost(host.to_string()));
+        }
+
+        if !self.allow_private_addresses {
+            if let Some(ip) = addresses.into_iter().find(|ip| is_non_public_ip(*ip)) {
+                return Err(ImportPolicyError::PrivateAddress(ip));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Downloads `uri` after [`ImportPolicy::check_host`]. Redirects are not followed, the
+    /// download is cut off once it exceeds `max_fetch_bytes` and must finish within
+    /// `max_fetch_time`, so the limits hold for the data actually received rather than for
+    /// what the remote announces.
+    ///
+    /// The host is resolved again for the download, so this does not protect against DNS
+    /// rebinding; deployments that need that should also restrict the API's egress.
+    pub async fn fetch(&self, uri: &str) -> Result<String, ImportPolicyError> {
+        let url = Url::parse(uri).map_err(|_| ImportPolicyError::InvalidUri)?;
+        self.check_host(&url).await?;
+
+        let failed = |e: reqwest::Error| {
+            if e.is_timeout() {
+                ImportPolicyError::TimedOut
+            } else {
+                ImportPolicyError::Unreachable(e.to_string())
+            }
+        };
+
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(self.max_fetch_time)
+            .build()
+            .map_err(failed)?;
+        let mut resp = client.get(url.as_str()).send().await.map_err(failed)?;
+
+        if resp.status().is_redirection() {
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            return Err(ImportPolicyError::Redirect(location.to_string()));
+        }
+        if !resp.status().is_success() {
+            return Err(ImportPolicyError::Unreachable(resp.status().to_string()));
+        }
+
+        let mut body = vec![];
+        while let Some(chunk) = resp.chunk().await.map_err(failed)? {
+            body.extend_from_slice(&chunk);
+            if body.len() as u64 > self.max_fetch_bytes {
+                return Err(ImportPolicyError::TooLarge(body.len() as u64));
+            }
+        }
+
+        String::from_utf8(body).map_err(|_| ImportPolicyError::NotText)
+    }
+}
@@ -0,0 +1,198 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::routes::translations::env_limit;
+
+/// State of a background job
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    /// Every step went through
+    Succeeded,
+    /// A further iteration would not change anything
+    Converged,
+    /// Stopped by the iteration or time limit before converging
+    LimitReached,
+    Cancelled,
+    Failed,
+}
+
+/// Statistics of one iteration of a rule set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IterationStats {
+    /// Starts at 1
+    pub iteration: usize,
+    /// Number of atoms in the namespace after the iteration
+    pub atoms: usize,
+    /// Number of atoms the iteration added
+    pub added: usize,
+    pub duration_ms: u128,
+}
+
+/// State of a pipeline step
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    /// Not run, because a step it depends on did not succeed or the job was cancelled
+    Skipped,
+}
+
+/// Result of one step of a pipeline
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StepResult {
+    pub name: String,
+    pub op: String,
+    pub status: StepStatus,
+    /// Why the step failed or was skipped
+    pub message: Option<String>,
+    /// What the step produced, such as the exported MeTTa
+    pub output: Option<String>,
+    pub duration_ms: Option<u128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub namespace: String,
+    pub status: JobStatus,
+    /// Why the job failed or stopped
+    pub message: Option<String>,
+    /// Number of atoms in the namespace before the first iteration
+    pub initial_atoms: Option<usize>,
+    pub iterations: Vec<IterationStats>,
+    /// Steps of a pipeline, in the order they run
+    pub steps: Vec<StepResult>,
+    pub start_timestamp: NaiveDateTime,
+    pub end_timestamp: Option<NaiveDateTime>,
+}
+
+struct Entry {
+    job: Job,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Background jobs of this process. Finished jobs are kept for
+/// `METTA_KG_JOBS_RETENTION_SECONDS` and lost on restart.
+#[derive(Default, Clone)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+/// Held by the task running a job to report its progress
+pub struct JobHandle {
+    id: String,
+    jobs: Arc<Mutex<HashMap<String, Entry>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobRegistry {
+    /// Registers a running job of `kind` on `namespace`
+    pub fn start(&self, kind: &str, namespace: String, steps: Vec<StepResult>) -> (Job, JobHandle) {
+        let job = Job {
+            id: Uuid::new_v4().simple().to_string(),
+            kind: kind.to_string(),
+            namespace,
+            status: JobStatus::Running,
+            message: None,
+            initial_atoms: None,
+            iterations: vec![],
+            steps,
+            start_timestamp: Utc::now().naive_utc(),
+            end_timestamp: None,
+        };
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let mut jobs = self.jobs.lock().unwrap();
+        let expired = Utc::now().naive_utc()
+            - chrono::Duration::seconds(env_limit("METTA_KG_JOBS_RETENTION_SECONDS", 3600) as i64);
+        jobs.retain(|_, entry| entry.job.end_timestamp.is_none_or(|end| end > expired));
+        jobs.insert(
+            job.id.clone(),
+            Entry {
+                job: job.clone(),
+                cancelled: cancelled.clone(),
+            },
+        );
+
+        let handle = JobHandle {
+            id: job.id.clone(),
+            jobs: self.jobs.clone(),
+            cancelled,
+        };
+        (job, handle)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().unwrap().get(id).map(|e| e.job.clone())
+    }
+
+    /// All jobs, oldest first
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|e| e.job.clone())
+            .collect();
+        jobs.sort_by_key(|job| job.start_timestamp);
+        jobs
+    }
+
+    /// Asks a running job to stop after its current step. Returns false if it is not running.
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(id) {
+            Some(entry) if entry.job.status == JobStatus::Running => {
+                entry.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl JobHandle {
+    fn update(&self, f: impl FnOnce(&mut Job)) {
+        if let Some(entry) = self.jobs.lock().unwrap().get_mut(&self.id) {
+            f(&mut entry.job);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_initial_atoms(&self, atoms: usize) {
+        self.update(|job| job.initial_atoms = Some(atoms));
+    }
+
+    pub fn record(&self, stats: IterationStats) {
+        self.update(|job| job.iterations.push(stats));
+    }
+
+    pub fn update_step(&self, index: usize, f: impl FnOnce(&mut StepResult)) {
+        self.update(|job| {
+            if let Some(step) = job.steps.get_mut(index) {
+                f(step);
+            }
+        });
+    }
+
+    pub fn finish(&self, status: JobStatus, message: Option<String>) {
+        self.update(|job| {
+            job.status = status;
+            job.message = message;
+            job.end_timestamp = Some(Utc::now().naive_utc());
+        });
+    }
+}
@@ -1,14 +1,25 @@
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use rocket::fairing::AdHoc;
 use rocket::http::Method;
 use rocket::routes;
 use rocket::{Build, Rocket};
 use rocket_cors::AllowedOrigins;
 
 pub mod db;
+pub mod events;
+pub mod export_formats;
+pub mod import_policy;
+pub mod jobs;
+pub mod metta;
 pub mod model;
 pub mod mork_api;
 pub mod routes;
+pub mod schedule;
+pub mod scheduler;
 pub mod schema;
+pub mod translation_runner;
+pub mod trigger_runner;
+pub mod webhook_runner;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
@@ -45,6 +56,10 @@ pub fn rocket() -> Rocket<Build> {
                 routes::translations::create_from_nt,
                 routes::translations::create_from_jsonld,
                 routes::translations::create_from_n3,
+                routes::translations::create_from_json,
+                routes::translations::create_from_turtle,
+                routes::translations::create_from_rdfxml,
+                routes::translations::create_from_sparql,
                 routes::tokens::get_all,
                 routes::tokens::get,
                 routes::tokens::create,
@@ -53,13 +68,62 @@ pub fn rocket() -> Rocket<Build> {
                 routes::tokens::delete_batch,
                 routes::spaces::read,
                 routes::spaces::import,
+                routes::spaces::import_batch,
                 routes::spaces::transform,
+                routes::spaces::transform_preview,
+                routes::spaces::cross_transform,
                 routes::spaces::upload,
                 routes::spaces::explore,
                 routes::spaces::export,
+                routes::spaces::query,
                 routes::spaces::clear,
+                routes::spaces::sparql,
+                routes::spaces::subscribe,
+                routes::namespaces::copy,
+                routes::namespaces::move_to,
+                routes::namespaces::rename,
+                routes::snapshots::create,
+                routes::snapshots::get_all,
+                routes::snapshots::restore,
+                routes::snapshots::delete,
+                routes::diff::diff,
+                routes::sources::create,
+                routes::sources::get_all,
+                routes::sources::delete,
+                routes::sources::runs,
+                routes::sources::run,
+                routes::queries::create,
+                routes::queries::get_all,
+                routes::queries::delete,
+                routes::queries::run,
+                routes::rules::run,
+                routes::pipelines::run,
+                routes::triggers::create,
+                routes::triggers::get_all,
+                routes::triggers::delete,
+                routes::webhooks::create,
+                routes::webhooks::get_all,
+                routes::webhooks::delete,
+                routes::jobs::get_all,
+                routes::jobs::get,
+                routes::jobs::cancel,
             ],
         )
         .attach(cors.clone())
+        .attach(scheduler::fairing())
+        .attach(trigger_runner::fairing())
+        .attach(webhook_runner::fairing())
+        .attach(AdHoc::on_liftoff("Temp dir", |rocket| {
+            Box::pin(async move {
+                // uploaded files are buffered here, see `temp_dir` in Rocket.toml
+                let temp_dir = rocket.config().temp_dir.relative();
+                if let Err(e) = std::fs::create_dir_all(&temp_dir) {
+                    eprintln!("Failed to create {}: {e}", temp_dir.display());
+                }
+            })
+        }))
         .manage(cors)
+        .manage(routes::translations::TranslationLimiter::default())
+        .manage(jobs::JobRegistry::default())
+        .manage(events::EventBus::default())
 }
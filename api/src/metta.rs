@@ -0,0 +1,273 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A MeTTa atom as far as the API needs to understand it: symbols, `$variables`,
+/// string literals and expressions. Numbers and other grounded values are kept as symbols.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Expr {
+    Symbol(String),
+    Variable(String),
+    /// String literal, stored without the surrounding quotes but with escapes intact
+    Str(String),
+    Expression(Vec<Expr>),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// Byte offset into the input
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl fmt::Display for Expr {
+    /// Canonical form: single spaces between children, no comments
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Symbol(s) => write!(f, "{s}"),
+            Expr::Variable(v) => write!(f, "${v}"),
+            Expr::Str(s) => write!(f, "\"{s}\""),
+            Expr::Expression(children) => {
+                write!(f, "(")?;
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{child}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Expr {
+    /// Names of the variables in this atom, in order of first occurrence
+    pub fn variables(&self) -> Vec<String> {
+        let mut result = vec![];
+        self.collect_variables(&mut result);
+        result
+    }
+
+    fn collect_variables(&self, result: &mut Vec<String>) {
+        match self {
+            Expr::Variable(v) if !result.contains(v) => result.push(v.clone()),
+            Expr::Expression(children) => {
+                children.iter().for_each(|c| c.collect_variables(result));
+            }
+            _ => (),
+        }
+    }
+
+    /// This atom with the variables in `bindings` replaced by their values
+    pub fn substitute(&self, bindings: &HashMap<String, Expr>) -> Expr {
+        match self {
+            Expr::Variable(v) => bindings.get(v).cloned().unwrap_or_else(|| self.clone()),
+            Expr::Expression(children) => {
+                Expr::Expression(children.iter().map(|c| c.substitute(bindings)).collect())
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Children of an expression, or `None` for any other atom
+    pub fn children(&self) -> Option<&[Expr]> {
+        match self {
+            Expr::Expression(children) => Some(children),
+            _ => None,
+        }
+    }
+}
+
+/// Value of a string literal, [`Expr::Str`] keeps the escapes as written
+pub fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+/// Deepest nesting of expressions the parser accepts, so that hostile input cannot exhaust the
+/// stack of the recursive descent
+pub const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+        Err(ParseError {
+            message: message.to_string(),
+            position: self.position,
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == ';' {
+                while let Some(c) = self.bump() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            None => self.error("unexpected end of input"),
+            Some(')') => self.error("unexpected ')'"),
+            Some('(') => {
+                if self.depth == MAX_DEPTH {
+                    return self.error("expressions nested too deeply");
+                }
+                self.bump();
+                self.depth += 1;
+                let mut children = vec![];
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        None => return self.error("unclosed '('"),
+                        Some(')') => {
+                            self.bump();
+                            self.depth -= 1;
+                            return Ok(Expr::Expression(children));
+                        }
+                        Some(_) => children.push(self.parse_expr()?),
+                    }
+                }
+            }
+            Some('"') => {
+                self.bump();
+                let start = self.position;
+                loop {
+                    match self.bump() {
+                        None => return self.error("unterminated string literal"),
+                        Some('\\') => {
+                            self.bump();
+                        }
+                        Some('"') => {
+                            let value = self.input[start..self.position - 1].to_string();
+                            return Ok(Expr::Str(value));
+                        }
+                        Some(_) => (),
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.position;
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' || c == ';' {
+                        break;
+                    }
+                    self.bump();
+                }
+                let token = &self.input[start..self.position];
+                match token.strip_prefix('$') {
+                    Some("") => self.error("empty variable name"),
+                    Some(name) => Ok(Expr::Variable(name.to_string())),
+                    None => Ok(Expr::Symbol(token.to_string())),
+                }
+            }
+        }
+    }
+}
+
+/// Parses every top-level atom in `input`
+pub fn parse_all(input: &str) -> Result<Vec<Expr>, ParseError> {
+    let mut parser = Parser {
+        input,
+        position: 0,
+        depth: 0,
+    };
+    let mut result = vec![];
+
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            return Ok(result);
+        }
+        result.push(parser.parse_expr()?);
+    }
+}
+
+/// Parses `input`, which must contain exactly one atom
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut atoms = parse_all(input)?;
+
+    match atoms.len() {
+        1 => Ok(atoms.remove(0)),
+        0 => Err(ParseError {
+            message: "expected an atom".to_string(),
+            position: 0,
+        }),
+        _ => Err(ParseError {
+            message: "expected a single atom".to_string(),
+            position: 0,
+        }),
+    }
+}
+
+/// Checks that `pattern` and `template` are single atoms and that the template only uses
+/// variables bound by the pattern.
+pub fn validate_mm2(pattern: &str, template: &str) -> Result<(), String> {
+    let pattern = parse(pattern).map_err(|e| format!("invalid pattern: {e}"))?;
+    let template = parse(template).map_err(|e| format!("invalid template: {e}"))?;
+
+    let bound = pattern.variables();
+    let unbound: Vec<String> = template
+        .variables()
+        .into_iter()
+        .filter(|v| !bound.contains(v))
+        .map(|v| format!("${v}"))
+        .collect();
+
+    if !unbound.is_empty() {
+        return Err(format!(
+            "template uses variables not bound by the pattern: {}",
+            unbound.join(", ")
+        ));
+    }
+
+    Ok(())
+}
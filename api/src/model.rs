@@ -1,4 +1,6 @@
-use crate::schema::tokens;
+use crate::schema::{
+    import_runs, import_sources, saved_queries, snapshots, tokens, triggers, webhooks,
+};
 use chrono::NaiveDateTime;
 use diesel::{Insertable, Queryable, QueryableByName, Selectable};
 use rocket::serde::{Deserialize, Serialize};
@@ -33,3 +35,158 @@ pub struct Token {
     pub permission_share_write: bool,
     pub parent: Option<i32>,
 }
+
+#[derive(Serialize, Deserialize, Insertable, Clone)]
+#[diesel(table_name = import_sources)]
+pub struct ImportSourceInsert {
+    pub token: i32,
+    pub namespace: String,
+    pub uri: String,
+    pub pattern: Option<String>,
+    pub template: Option<String>,
+    pub schedule: String,
+    pub clear_before: bool,
+    pub creation_timestamp: NaiveDateTime,
+    pub next_run: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Selectable, Clone)]
+#[diesel(table_name = import_sources)]
+pub struct ImportSource {
+    pub id: i32,
+    pub token: i32,
+    pub namespace: String,
+    pub uri: String,
+    pub pattern: Option<String>,
+    pub template: Option<String>,
+    pub schedule: String,
+    pub clear_before: bool,
+    pub creation_timestamp: NaiveDateTime,
+    pub next_run: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone)]
+#[diesel(table_name = import_runs)]
+pub struct ImportRunInsert {
+    pub source: i32,
+    pub start_timestamp: NaiveDateTime,
+    pub end_timestamp: Option<NaiveDateTime>,
+    pub success: Option<bool>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Selectable, Clone)]
+#[diesel(table_name = import_runs)]
+pub struct ImportRun {
+    pub id: i32,
+    pub source: i32,
+    pub start_timestamp: NaiveDateTime,
+    pub end_timestamp: Option<NaiveDateTime>,
+    pub success: Option<bool>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone)]
+#[diesel(table_name = saved_queries)]
+pub struct SavedQueryInsert {
+    pub token: i32,
+    pub namespace: String,
+    pub name: String,
+    pub description: String,
+    pub kind: String,
+    pub patterns: Vec<String>,
+    pub templates: Vec<String>,
+    pub parameters: Vec<String>,
+    pub creation_timestamp: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Selectable, Clone)]
+#[diesel(table_name = saved_queries)]
+pub struct SavedQuery {
+    pub id: i32,
+    pub token: i32,
+    pub namespace: String,
+    pub name: String,
+    pub description: String,
+    pub kind: String,
+    pub patterns: Vec<String>,
+    pub templates: Vec<String>,
+    pub parameters: Vec<String>,
+    pub creation_timestamp: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone)]
+#[diesel(table_name = triggers)]
+pub struct TriggerInsert {
+    pub token: i32,
+    pub namespace: String,
+    pub target_namespace: String,
+    pub description: String,
+    pub patterns: Vec<String>,
+    pub templates: Vec<String>,
+    pub creation_timestamp: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Selectable, Clone)]
+#[diesel(table_name = triggers)]
+pub struct Trigger {
+    pub id: i32,
+    pub token: i32,
+    pub namespace: String,
+    pub target_namespace: String,
+    pub description: String,
+    pub patterns: Vec<String>,
+    pub templates: Vec<String>,
+    pub creation_timestamp: NaiveDateTime,
+    pub last_run_timestamp: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone)]
+#[diesel(table_name = webhooks)]
+pub struct WebhookInsert {
+    pub token: i32,
+    pub namespace: String,
+    pub url: String,
+    pub description: String,
+    pub creation_timestamp: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Selectable, Clone)]
+#[diesel(table_name = webhooks)]
+pub struct Webhook {
+    pub id: i32,
+    pub token: i32,
+    pub namespace: String,
+    pub url: String,
+    pub description: String,
+    pub creation_timestamp: NaiveDateTime,
+    pub last_delivery_timestamp: Option<NaiveDateTime>,
+    /// HTTP status of the last delivery attempt, or why it could not be made
+    pub last_status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone)]
+#[diesel(table_name = snapshots)]
+pub struct SnapshotInsert {
+    pub token: Option<i32>,
+    pub namespace: String,
+    pub description: String,
+    pub atoms: i32,
+    pub bytes: i64,
+    pub creation_timestamp: NaiveDateTime,
+}
+
+/// A snapshot, whose content is stored in a file of its own, see `routes::snapshots`
+#[derive(Serialize, Deserialize, Queryable, Selectable, Clone)]
+#[diesel(table_name = snapshots)]
+pub struct Snapshot {
+    pub id: i32,
+    /// Token that took the snapshot, if it still exists
+    pub token: Option<i32>,
+    pub namespace: String,
+    pub description: String,
+    pub atoms: i32,
+    pub bytes: i64,
+    pub creation_timestamp: NaiveDateTime,
+}
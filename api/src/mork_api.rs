@@ -5,6 +5,8 @@ use std::any::Any;
 use std::env;
 use std::path::PathBuf;
 
+use crate::metta::Expr;
+
 #[derive(Serialize, Deserialize, Clone)]
 #[allow(dead_code)]
 pub enum ExportFormat {
@@ -30,6 +32,9 @@ impl Default for TransformDetails {
     }
 }
 
+/// Appended to the name of a namespace to tag the atoms stored in it
+const DATA_TAG_SUFFIX: &str = "a727d4f9-836a-4e4c-9480";
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Namespace {
     path: Vec<String>,
@@ -56,8 +61,21 @@ impl Namespace {
             .unwrap_or_else(|| "root".to_string())
     }
 
-    fn data_tag(&self) -> String {
-        format!("{}a727d4f9-836a-4e4c-9480", self.current_name())
+    /// Symbol that tags the atoms stored in this namespace itself
+    pub fn data_tag(&self) -> String {
+        format!("{}{DATA_TAG_SUFFIX}", self.current_name())
+    }
+
+    /// `value` at the position of this namespace, without the data tag, so that it stands for
+    /// the atoms of this namespace and of the namespaces below it alike
+    pub fn with_subtree(&self, value: &str) -> String {
+        let mut result = value.to_string();
+
+        for name in self.path.iter().rev() {
+            result = format!("({name} {result})");
+        }
+
+        result
     }
 
     pub fn with_namespace(&self, value: &str) -> String {
@@ -71,6 +89,21 @@ impl Namespace {
 
         result
     }
+
+    /// The value inside a [`Namespace::with_namespace`] wrapper of any namespace, or `expr`
+    /// itself if it is not wrapped. Atoms of nested namespaces come back wrapped when a parent
+    /// namespace is queried.
+    pub fn unwrap(expr: &Expr) -> &Expr {
+        fn inner(expr: &Expr) -> Option<&Expr> {
+            match expr.children()? {
+                [Expr::Symbol(name), value] if name.ends_with(DATA_TAG_SUFFIX) => Some(value),
+                [Expr::Symbol(_), wrapped] => inner(wrapped),
+                _ => None,
+            }
+        }
+
+        inner(expr).unwrap_or(expr)
+    }
 }
 
 impl From<PathBuf> for Namespace {
@@ -179,7 +212,15 @@ pub trait Request {
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct TransformRequest {
     namespace: Namespace,
+    /// Where the templates write to, `namespace` when not set
+    target_namespace: Option<Namespace>,
+    /// Per pattern, by index, the namespace it reads from instead of `namespace`
+    pattern_namespaces: Vec<Namespace>,
+    /// Per template, by index, the namespace it writes to instead of the target namespace
+    template_namespaces: Vec<Namespace>,
     transform_input: TransformDetails,
+    /// Whether patterns and templates are placed with [`Namespace::with_subtree`]
+    subtree: bool,
 }
 
 impl TransformRequest {
@@ -192,30 +233,72 @@ impl TransformRequest {
         self
     }
 
+    pub fn target_namespace(mut self, ns: PathBuf) -> Self {
+        self.target_namespace = Some(Namespace::from(ns));
+        self
+    }
+
+    /// Namespaces of the patterns, in order, for transforms reading from several namespaces
+    pub fn pattern_namespaces(mut self, namespaces: Vec<PathBuf>) -> Self {
+        self.pattern_namespaces = namespaces.into_iter().map(Namespace::from).collect();
+        self
+    }
+
+    /// Namespaces of the templates, in order, for transforms writing to several namespaces
+    pub fn template_namespaces(mut self, namespaces: Vec<PathBuf>) -> Self {
+        self.template_namespaces = namespaces.into_iter().map(Namespace::from).collect();
+        self
+    }
+
     pub fn transform_input(mut self, inp: TransformDetails) -> Self {
         self.transform_input = inp;
         self
     }
 
+    /// Matches and writes whole subtrees rather than the atoms of single namespaces
+    pub fn subtree(mut self, subtree: bool) -> Self {
+        self.subtree = subtree;
+        self
+    }
+
+    fn place(&self, namespace: &Namespace, value: &str) -> String {
+        if self.subtree {
+            namespace.with_subtree(value)
+        } else {
+            namespace.with_namespace(value)
+        }
+    }
+
     fn multi_patterns(&self) -> String {
         format!(
             "(, {})",
             self.transform_input
                 .patterns
                 .iter()
-                .map(|pattern| { self.namespace.with_namespace(pattern) })
+                .enumerate()
+                .map(|(i, pattern)| {
+                    self.place(
+                        self.pattern_namespaces.get(i).unwrap_or(&self.namespace),
+                        pattern,
+                    )
+                })
                 .collect::<Vec<String>>()
                 .join(" ")
         )
     }
 
     fn multi_templates(&self) -> String {
+        let target = self.target_namespace.as_ref().unwrap_or(&self.namespace);
+
         format!(
             "(, {})",
             self.transform_input
                 .templates
                 .iter()
-                .map(|pattern| { self.namespace.with_namespace(pattern) })
+                .enumerate()
+                .map(|(i, template)| {
+                    self.place(self.template_namespaces.get(i).unwrap_or(target), template)
+                })
                 .collect::<Vec<String>>()
                 .join(" ")
         )
@@ -251,6 +334,7 @@ pub struct ImportRequest {
     namespace: Namespace,
     transform_input: TransformDetails,
     uri: String,
+    fetch_timeout: Option<std::time::Duration>,
 }
 
 impl ImportRequest {
@@ -267,6 +351,18 @@ impl ImportRequest {
         self.uri = uri;
         self
     }
+
+    /// Only the first pattern and template are used
+    pub fn transform_input(mut self, inp: TransformDetails) -> Self {
+        self.transform_input = inp;
+        self
+    }
+
+    /// How long to wait for Mork to fetch and load the uri
+    pub fn fetch_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.fetch_timeout = Some(timeout);
+        self
+    }
 }
 
 impl Request for ImportRequest {
@@ -279,7 +375,12 @@ impl Request for ImportRequest {
     fn path(&self) -> String {
         format!(
             "/import/{}/{}/?uri={}",
-            urlencoding::encode("$x"),
+            urlencoding::encode(
+                self.transform_input
+                    .patterns
+                    .first()
+                    .unwrap_or(&"$x".to_string())
+            ),
             urlencoding::encode(
                 &self.namespace.with_namespace(
                     self.transform_input
@@ -288,13 +389,18 @@ impl Request for ImportRequest {
                         .unwrap_or(&"$x".to_string())
                 )
             ),
-            self.uri
+            urlencoding::encode(&self.uri)
         )
     }
 
     fn body(&self) -> Option<Self::Body> {
         None
     }
+
+    fn timeout(&self) -> std::time::Duration {
+        self.fetch_timeout
+            .unwrap_or_else(|| std::time::Duration::from_secs(20))
+    }
 }
 
 #[derive(Default)]
@@ -396,6 +502,8 @@ pub struct UploadRequest {
     pattern: String,
     template: String,
     data: String,
+    /// Whether `template` is placed with [`Namespace::with_subtree`]
+    subtree: bool,
 }
 
 impl UploadRequest {
@@ -422,6 +530,13 @@ impl UploadRequest {
         self.data = data;
         self
     }
+
+    /// Writes below the namespace rather than among its own atoms, for data that carries the
+    /// wrappers of the namespaces below it, such as a subtree export
+    pub fn subtree(mut self, subtree: bool) -> Self {
+        self.subtree = subtree;
+        self
+    }
 }
 
 impl Request for UploadRequest {
@@ -432,10 +547,16 @@ impl Request for UploadRequest {
     }
 
     fn path(&self) -> String {
+        let template = if self.subtree {
+            self.namespace.with_subtree(&self.template)
+        } else {
+            self.namespace.with_namespace(&self.template)
+        };
+
         format!(
             "/upload/{}/{}",
             urlencoding::encode(&self.pattern),
-            urlencoding::encode(&self.namespace.with_namespace(&self.template))
+            urlencoding::encode(&template)
         )
     }
     fn body(&self) -> Option<Self::Body> {
@@ -450,6 +571,8 @@ pub struct ExportRequest {
     template: String,
     format: Option<ExportFormat>,
     max_write: Option<usize>,
+    /// Whether `pattern` is placed with [`Namespace::with_subtree`]
+    subtree: bool,
 }
 
 impl ExportRequest {
@@ -476,6 +599,19 @@ impl ExportRequest {
         self.format = Some(format);
         self
     }
+
+    /// Stops the export after this many results
+    pub fn max_write(mut self, max_write: usize) -> Self {
+        self.max_write = Some(max_write);
+        self
+    }
+
+    /// Matches anywhere below the namespace rather than among its own atoms. With a `$x`
+    /// pattern and template, atoms come back with the wrappers below the namespace.
+    pub fn subtree(mut self, subtree: bool) -> Self {
+        self.subtree = subtree;
+        self
+    }
 }
 
 impl Request for ExportRequest {
@@ -486,9 +622,14 @@ impl Request for ExportRequest {
     }
 
     fn path(&self) -> String {
+        let pattern = if self.subtree {
+            self.namespace.with_subtree(&self.pattern)
+        } else {
+            self.namespace.with_namespace(&self.pattern)
+        };
         let mut path = format!(
             "/export/{}/{}",
-            urlencoding::encode(&self.namespace.with_namespace(&self.pattern)),
+            urlencoding::encode(&pattern),
             urlencoding::encode(&self.template)
         );
 
@@ -525,6 +666,8 @@ impl Request for ExportRequest {
 pub struct ClearRequest {
     namespace: Namespace,
     expr: String,
+    /// Whether `expr` is placed with [`Namespace::with_subtree`]
+    subtree: bool,
 }
 
 impl ClearRequest {
@@ -541,6 +684,12 @@ impl ClearRequest {
         self.expr = expr;
         self
     }
+
+    /// Clears matches anywhere below the namespace rather than among its own atoms
+    pub fn subtree(mut self, subtree: bool) -> Self {
+        self.subtree = subtree;
+        self
+    }
 }
 
 impl Request for ClearRequest {
@@ -551,7 +700,11 @@ impl Request for ClearRequest {
     }
 
     fn path(&self) -> String {
-        let expr_to_use = self.namespace.with_namespace(&self.expr);
+        let expr_to_use = if self.subtree {
+            self.namespace.with_subtree(&self.expr)
+        } else {
+            self.namespace.with_namespace(&self.expr)
+        };
 
         format!("/clear/{}", urlencoding::encode(&expr_to_use))
     }
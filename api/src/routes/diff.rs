@@ -0,0 +1,130 @@
+use rocket::http::Status;
+use rocket::post;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::metta::{parse_all, Expr};
+use crate::model::Token;
+use crate::mork_api::{ExportFormat, ExportRequest, MorkApiClient, Namespace};
+use crate::routes::snapshots;
+use crate::routes::spaces::authorize_namespace;
+
+/// One side of a diff, written as `{"namespace": "/space/"}` or `{"snapshot": 1}`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffSide {
+    /// Current contents of a namespace and the namespaces below it
+    Namespace(String),
+    /// Contents of a namespace when the snapshot with this id was taken
+    Snapshot(i32),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DiffInput {
+    pub a: DiffSide,
+    pub b: DiffSide,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DiffCounts {
+    pub a: usize,
+    pub b: usize,
+    pub only_in_a: usize,
+    pub only_in_b: usize,
+    pub common: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DiffResult {
+    /// Atoms in canonical form, sorted
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    pub counts: DiffCounts,
+}
+
+/// `atom` of a subtree export of `namespace`, relative to it: its own atoms lose their tag,
+/// which differs between namespaces, while atoms of the namespaces below keep their wrappers
+fn relative(namespace: &Namespace, atom: Expr) -> Expr {
+    match atom.children() {
+        Some([Expr::Symbol(tag), value]) if *tag == namespace.data_tag() => value.clone(),
+        _ => atom,
+    }
+}
+
+/// Distinct atoms on `side`, including the namespaces below it, provided the token may read it
+async fn load(token: &Token, side: &DiffSide) -> Result<BTreeSet<Expr>, Custom<String>> {
+    let (label, namespace, content) = match side {
+        DiffSide::Namespace(namespace) => {
+            let path = authorize_namespace(token, namespace, false)?;
+            let request = ExportRequest::new()
+                .namespace(path)
+                .pattern("$x".to_string())
+                .template("$x".to_string())
+                .format(ExportFormat::Metta)
+                .subtree(true);
+
+            let content = MorkApiClient::new().dispatch(request).await.map_err(|e| {
+                Custom(
+                    Status::InternalServerError,
+                    format!("Failed to contact backend: {e}"),
+                )
+            })?;
+            (namespace.clone(), namespace.clone(), content)
+        }
+        DiffSide::Snapshot(id) => {
+            if !token.permission_read {
+                return Err(Custom(
+                    Status::Unauthorized,
+                    format!("unauthorized: no read permission on snapshot {id}"),
+                ));
+            }
+            let (snapshot, content) = snapshots::find(token, *id)
+                .map_err(|status| Custom(status, format!("snapshot {id} not found")))?;
+            (format!("snapshot {id}"), snapshot.namespace, content)
+        }
+    };
+
+    let namespace = Namespace::from_path_string(&namespace);
+    parse_all(&content)
+        .map(|atoms| {
+            atoms
+                .into_iter()
+                .map(|atom| relative(&namespace, atom))
+                .collect()
+        })
+        .map_err(|e| {
+            Custom(
+                Status::InternalServerError,
+                format!("Failed to parse {label}: {e}"),
+            )
+        })
+}
+
+/// Compares two namespaces, a namespace and a snapshot, or two snapshots. Atoms are compared in
+/// their canonical form, so spacing and comments do not count as changes, and like in a space,
+/// duplicates count once. Needs `read` permission on both sides.
+#[post("/spaces/diff", data = "<input>")]
+pub async fn diff(
+    token: Token,
+    input: Json<DiffInput>,
+) -> Result<Json<DiffResult>, Custom<String>> {
+    let a = load(&token, &input.a).await?;
+    let b = load(&token, &input.b).await?;
+
+    let only_in_a: Vec<String> = a.difference(&b).map(|atom| atom.to_string()).collect();
+    let only_in_b: Vec<String> = b.difference(&a).map(|atom| atom.to_string()).collect();
+
+    Ok(Json(DiffResult {
+        counts: DiffCounts {
+            a: a.len(),
+            b: b.len(),
+            only_in_a: only_in_a.len(),
+            only_in_b: only_in_b.len(),
+            common: a.intersection(&b).count(),
+        },
+        only_in_a,
+        only_in_b,
+    }))
+}
@@ -0,0 +1,57 @@
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{delete, get, State};
+
+use crate::jobs::{Job, JobRegistry};
+use crate::model::Token;
+
+/// Loads the job with `job_id`, provided it runs in the namespace of `token`
+fn find_job(token: &Token, jobs: &JobRegistry, job_id: &str) -> Result<Job, Status> {
+    match jobs.get(job_id) {
+        Some(job) if job.namespace.starts_with(&token.namespace) => Ok(job),
+        _ => Err(Status::NotFound),
+    }
+}
+
+/// All background jobs in the namespace of the token
+#[get("/jobs")]
+pub fn get_all(token: Token, jobs: &State<JobRegistry>) -> Result<Json<Vec<Job>>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    Ok(Json(
+        jobs.list()
+            .into_iter()
+            .filter(|job| job.namespace.starts_with(&token.namespace))
+            .collect(),
+    ))
+}
+
+/// Status and statistics of a background job
+#[get("/jobs/<job_id>")]
+pub fn get(token: Token, job_id: &str, jobs: &State<JobRegistry>) -> Result<Json<Job>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    find_job(&token, jobs, job_id).map(Json)
+}
+
+/// Cancels a running job. Changes made before it stops are kept.
+#[delete("/jobs/<job_id>")]
+pub fn cancel(token: Token, job_id: &str, jobs: &State<JobRegistry>) -> Status {
+    if !token.permission_write {
+        return Status::Unauthorized;
+    }
+
+    if let Err(status) = find_job(&token, jobs, job_id) {
+        return status;
+    }
+
+    if jobs.cancel(job_id) {
+        Status::Ok
+    } else {
+        Status::Conflict
+    }
+}
@@ -9,9 +9,19 @@ use rocket::{
 };
 use serde::{Deserialize, Serialize};
 
+pub mod diff;
+pub mod jobs;
+pub mod namespaces;
+pub mod pipelines;
+pub mod queries;
+pub mod rules;
+pub mod snapshots;
+pub mod sources;
 pub mod spaces;
 pub mod tokens;
 pub mod translations;
+pub mod triggers;
+pub mod webhooks;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum AuthError {
@@ -0,0 +1,305 @@
+use diesel::{
+    Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper, TextExpressionMethods,
+};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::{post, State};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::db::establish_connection;
+use crate::events::{EventBus, Operation};
+use crate::model::Token;
+use crate::mork_api::{ClearRequest, MorkApiClient, Namespace, TransformDetails, TransformRequest};
+use crate::routes::spaces::{authorize_namespace, namespace_to_path, path_to_namespace, publish};
+use crate::routes::tokens::token_tree;
+use crate::schema::tokens;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CopyInput {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct MoveInput {
+    pub from: String,
+    pub to: String,
+    /// Whether tokens on `from` or below it are moved along
+    #[serde(default)]
+    pub update_tokens: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RenameInput {
+    pub namespace: String,
+    /// New last segment of the namespace
+    pub name: String,
+    #[serde(default)]
+    pub update_tokens: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RelocateResult {
+    pub from: String,
+    pub to: String,
+    /// Number of tokens whose namespace was moved along
+    pub tokens_updated: usize,
+}
+
+/// Rejects copies of the root and copies into their own subtree or out of it into a parent
+fn check_paths(from: &Path, to: &Path) -> Result<(), Custom<String>> {
+    if from.as_os_str().is_empty() || to.as_os_str().is_empty() {
+        return Err(Custom(
+            Status::BadRequest,
+            "invalid_namespace: the root namespace cannot be copied or moved".to_string(),
+        ));
+    }
+    if from.starts_with(to) || to.starts_with(from) {
+        return Err(Custom(
+            Status::BadRequest,
+            format!(
+                "invalid_namespace: {} and {} overlap",
+                path_to_namespace(from),
+                path_to_namespace(to)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Copies the atoms of `from` and of all namespaces below it to `to`. A single transform
+/// re-roots the whole subtree. Only the atoms of `from` itself are tagged with its name, so
+/// when the last segment differs they are tagged again at `to` and the copies with the old tag
+/// are cleared.
+async fn copy_subtree(from: &Path, to: &Path) -> Result<(), String> {
+    let mork_api_client = MorkApiClient::new();
+
+    let request = TransformRequest::new()
+        .namespace(from.to_path_buf())
+        .target_namespace(to.to_path_buf())
+        .subtree(true)
+        .transform_input(
+            TransformDetails::new()
+                .patterns(vec!["$rest".to_string()])
+                .templates(vec!["$rest".to_string()]),
+        );
+    mork_api_client
+        .dispatch(request)
+        .await
+        .map_err(|e| format!("Failed to copy: {e}"))?;
+
+    let old_tag = Namespace::from(from.to_path_buf()).data_tag();
+    let new_tag = Namespace::from(to.to_path_buf()).data_tag();
+    if old_tag == new_tag {
+        return Ok(());
+    }
+
+    let request = TransformRequest::new()
+        .namespace(to.to_path_buf())
+        .subtree(true)
+        .transform_input(
+            TransformDetails::new()
+                .patterns(vec![format!("({old_tag} $x)")])
+                .templates(vec![format!("({new_tag} $x)")]),
+        );
+    mork_api_client
+        .dispatch(request)
+        .await
+        .map_err(|e| format!("Failed to retag copied atoms: {e}"))?;
+
+    let request = ClearRequest::new()
+        .namespace(to.to_path_buf())
+        .expr(format!("({old_tag} $x)"))
+        .subtree(true);
+    mork_api_client
+        .dispatch(request)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to clear atoms with the old tag: {e}"))
+}
+
+/// The tokens on `from` or below it, all of which `token` has to administer, i.e. be or have
+/// created directly or through other tokens, for them to be moved along
+fn tokens_to_move(token: &Token, from: &str) -> Result<Vec<Token>, Custom<String>> {
+    // `_` is allowed in namespaces, but is a wildcard to LIKE
+    let prefix = format!("{}%", from.replace('_', "\\_"));
+    let failed = |_| {
+        Custom(
+            Status::InternalServerError,
+            "Failed to load tokens".to_string(),
+        )
+    };
+
+    let conn = &mut establish_connection();
+    let affected = tokens::table
+        .select(Token::as_select())
+        .filter(tokens::namespace.like(prefix))
+        .load(conn)
+        .map_err(failed)?;
+    let administered: BTreeSet<i32> = token_tree(token, conn)
+        .map_err(failed)?
+        .iter()
+        .map(|token| token.id)
+        .collect();
+
+    let foreign = affected
+        .iter()
+        .filter(|token| !administered.contains(&token.id))
+        .count();
+    if foreign > 0 {
+        return Err(Custom(
+            Status::Unauthorized,
+            format!("unauthorized: {foreign} of the tokens on {from} belong to other users"),
+        ));
+    }
+
+    Ok(affected)
+}
+
+/// Points `affected`, the tokens on `from` or below it, at the same place below `to`, all or
+/// none
+fn update_tokens(affected: &[Token], from: &str, to: &str) -> Result<usize, diesel::result::Error> {
+    establish_connection().transaction(|conn| {
+        for token in affected {
+            let namespace = format!("{to}{}", &token.namespace[from.len()..]);
+            diesel::update(tokens::table.filter(tokens::id.eq(token.id)))
+                .set(tokens::namespace.eq(namespace))
+                .execute(conn)?;
+        }
+
+        Ok(affected.len())
+    })
+}
+
+/// Copies the namespace `from` and the namespaces below it to `to`, keeping what `to` already
+/// holds. Needs `read` permission on `from` and `write` permission on `to`.
+#[post("/spaces/copy", data = "<input>")]
+pub async fn copy(
+    token: Token,
+    input: Json<CopyInput>,
+    events: &State<EventBus>,
+) -> Result<Json<RelocateResult>, Custom<String>> {
+    let from = authorize_namespace(&token, &input.from, false)?;
+    let to = authorize_namespace(&token, &input.to, true)?;
+    check_paths(&from, &to)?;
+
+    let outcome = copy_subtree(&from, &to).await;
+    publish(events, Operation::Transform, &to, &token, &outcome);
+    outcome.map_err(|e| Custom(Status::InternalServerError, e))?;
+
+    Ok(Json(RelocateResult {
+        from: path_to_namespace(&from),
+        to: path_to_namespace(&to),
+        tokens_updated: 0,
+    }))
+}
+
+/// Checks that `token` may move `namespace` away, which needs `read` and `write` permission
+fn authorize_source(token: &Token, namespace: &str) -> Result<PathBuf, Custom<String>> {
+    let path = authorize_namespace(token, namespace, true)?;
+    if !token.permission_read {
+        return Err(Custom(
+            Status::Unauthorized,
+            format!("unauthorized: no read permission on {namespace}"),
+        ));
+    }
+
+    Ok(path)
+}
+
+/// Shared by [`move_to`] and [`rename`]. `from` is only cleared once the copy went through,
+/// but the steps are separate requests to Mork, so a failure can leave both in place.
+async fn relocate(
+    token: &Token,
+    from: PathBuf,
+    to: PathBuf,
+    move_tokens: bool,
+    events: &EventBus,
+) -> Result<Json<RelocateResult>, Custom<String>> {
+    check_paths(&from, &to)?;
+    // checked before anything is moved, so the data does not go without its tokens
+    let affected = if move_tokens {
+        tokens_to_move(token, &path_to_namespace(&from))?
+    } else {
+        Vec::new()
+    };
+
+    let outcome = copy_subtree(&from, &to).await;
+    publish(events, Operation::Transform, &to, token, &outcome);
+    outcome.map_err(|e| Custom(Status::InternalServerError, e))?;
+
+    let request = ClearRequest::new()
+        .namespace(from.clone())
+        .expr("$rest".to_string())
+        .subtree(true);
+    let outcome = MorkApiClient::new().dispatch(request).await;
+    publish(events, Operation::Clear, &from, token, &outcome);
+    outcome.map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            format!(
+                "Copied, but failed to clear {}: {e}",
+                path_to_namespace(&from)
+            ),
+        )
+    })?;
+
+    let from = path_to_namespace(&from);
+    let to = path_to_namespace(&to);
+    let tokens_updated = if move_tokens {
+        update_tokens(&affected, &from, &to).map_err(|_| {
+            Custom(
+                Status::InternalServerError,
+                "Moved, but failed to update tokens".to_string(),
+            )
+        })?
+    } else {
+        0
+    };
+
+    Ok(Json(RelocateResult {
+        from,
+        to,
+        tokens_updated,
+    }))
+}
+
+/// Moves the namespace `from` and the namespaces below it to `to`, optionally along with the
+/// tokens on them. Needs `read` and `write` permission on `from` and `write` permission on `to`.
+#[post("/spaces/move", data = "<input>")]
+pub async fn move_to(
+    token: Token,
+    input: Json<MoveInput>,
+    events: &State<EventBus>,
+) -> Result<Json<RelocateResult>, Custom<String>> {
+    let from = authorize_source(&token, &input.from)?;
+    let to = authorize_namespace(&token, &input.to, true)?;
+
+    relocate(&token, from, to, input.update_tokens, events).await
+}
+
+/// Moves a namespace to a new name within the same parent, see [`move_to`]
+#[post("/spaces/rename", data = "<input>")]
+pub async fn rename(
+    token: Token,
+    input: Json<RenameInput>,
+    events: &State<EventBus>,
+) -> Result<Json<RelocateResult>, Custom<String>> {
+    let from = authorize_source(&token, &input.namespace)?;
+
+    let name = namespace_to_path(&input.name)
+        .filter(|name| name.components().count() == 1)
+        .ok_or_else(|| {
+            Custom(
+                Status::BadRequest,
+                format!("invalid_namespace: {} is not a valid name", input.name),
+            )
+        })?;
+    let to = from.with_file_name(name);
+    let to = authorize_namespace(&token, &path_to_namespace(&to), true)?;
+
+    relocate(&token, from, to, input.update_tokens, events).await
+}
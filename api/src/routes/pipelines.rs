@@ -0,0 +1,483 @@
+use rocket::http::{ContentType, Status};
+use rocket::response::status::{Accepted, Custom};
+use rocket::serde::json::{serde_json, Json};
+use rocket::{post, Data, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::events::{EventBus, Operation};
+use crate::import_policy::ImportPolicy;
+use crate::jobs::{Job, JobHandle, JobRegistry, JobStatus, StepResult, StepStatus};
+use crate::metta::{parse, parse_all, validate_mm2};
+use crate::model::Token;
+use crate::mork_api::{
+    ClearRequest, ExportFormat, ExportRequest, MorkApiClient, TransformDetails, TransformRequest,
+    UploadRequest,
+};
+use crate::routes::spaces::{
+    authorize_namespace, event, import_into, ingest_mm2, publish, publish_import,
+};
+use crate::routes::translations::{
+    env_limit, load, translate_text, TextFormat, TranslationFailure, TranslationLimiter,
+};
+
+fn any_atom() -> String {
+    "$x".to_string()
+}
+
+/// What a pipeline step does, by `op`. Namespaces are given like in request bodies, or as
+/// `$name` for one of the pipeline's `namespaces`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum StepOp {
+    /// Lets Mork fetch `uri`, see `/spaces/import`
+    Import {
+        namespace: String,
+        uri: String,
+        pattern: Option<String>,
+        template: Option<String>,
+    },
+    /// Uploads the MeTTa in `data`, see `/spaces/upload`
+    Upload {
+        namespace: String,
+        data: String,
+        pattern: Option<String>,
+        template: Option<String>,
+    },
+    /// Translates `data`, or the document at `uri`, and loads the result
+    Translate {
+        namespace: String,
+        format: TextFormat,
+        uri: Option<String>,
+        data: Option<String>,
+    },
+    Transform {
+        namespace: String,
+        patterns: Vec<String>,
+        templates: Vec<String>,
+    },
+    Clear {
+        namespace: String,
+        #[serde(default = "any_atom")]
+        expr: String,
+    },
+    /// The exported MeTTa is the output of the step
+    Export {
+        namespace: String,
+        #[serde(default = "any_atom")]
+        pattern: String,
+        #[serde(default = "any_atom")]
+        template: String,
+    },
+}
+
+impl StepOp {
+    fn name(&self) -> &'static str {
+        match self {
+            StepOp::Import { .. } => "import",
+            StepOp::Upload { .. } => "upload",
+            StepOp::Translate { .. } => "translate",
+            StepOp::Transform { .. } => "transform",
+            StepOp::Clear { .. } => "clear",
+            StepOp::Export { .. } => "export",
+        }
+    }
+
+    fn namespace(&self) -> &str {
+        match self {
+            StepOp::Import { namespace, .. }
+            | StepOp::Upload { namespace, .. }
+            | StepOp::Translate { namespace, .. }
+            | StepOp::Transform { namespace, .. }
+            | StepOp::Clear { namespace, .. }
+            | StepOp::Export { namespace, .. } => namespace,
+        }
+    }
+
+    /// Checks the MM2 and inputs of the step, without contacting anything
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            StepOp::Import {
+                pattern, template, ..
+            } => ingest_mm2(pattern.clone(), template.clone())
+                .map(|_| ())
+                .map_err(|Custom(_, e)| e),
+            StepOp::Upload {
+                data,
+                pattern,
+                template,
+                ..
+            } => {
+                ingest_mm2(pattern.clone(), template.clone()).map_err(|Custom(_, e)| e)?;
+                parse_all(data).map(|_| ()).map_err(|e| e.to_string())
+            }
+            StepOp::Translate { uri, data, .. } => match (uri, data) {
+                (Some(_), None) | (None, Some(_)) => Ok(()),
+                _ => Err("either uri or data must be given".to_string()),
+            },
+            StepOp::Transform {
+                patterns,
+                templates,
+                ..
+            } => {
+                if patterns.is_empty() || templates.is_empty() {
+                    return Err("patterns and templates must not be empty".to_string());
+                }
+                let conjunction = format!("(, {})", patterns.join(" "));
+                templates
+                    .iter()
+                    .try_for_each(|template| validate_mm2(&conjunction, template))
+            }
+            StepOp::Clear { expr, .. } => parse(expr).map(|_| ()).map_err(|e| e.to_string()),
+            StepOp::Export {
+                pattern, template, ..
+            } => validate_mm2(pattern, template),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PipelineStep {
+    pub name: String,
+    /// Names of the steps that have to succeed before this one runs
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(flatten)]
+    pub op: StepOp,
+}
+
+/// A pipeline document, sent as JSON or, with a YAML content type, as YAML
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PipelineInput {
+    /// Namespaces the steps refer to as `$name`
+    #[serde(default)]
+    pub namespaces: HashMap<String, String>,
+    pub steps: Vec<PipelineStep>,
+}
+
+/// A validated step, with its dependencies as indices into the plan
+struct PlannedStep {
+    step: PipelineStep,
+    path: PathBuf,
+    depends_on: Vec<usize>,
+}
+
+fn invalid(message: String) -> Custom<String> {
+    Custom(Status::BadRequest, format!("invalid_pipeline: {message}"))
+}
+
+/// Orders the steps so that every step comes after its dependencies, otherwise keeping the
+/// order of the document, and checks everything that can be checked before running them,
+/// including the permissions of `token`
+fn plan(token: &Token, pipeline: PipelineInput) -> Result<Vec<PlannedStep>, Custom<String>> {
+    if pipeline.steps.is_empty() {
+        return Err(invalid("no steps".to_string()));
+    }
+
+    for (i, step) in pipeline.steps.iter().enumerate() {
+        if pipeline.steps[..i].iter().any(|s| s.name == step.name) {
+            return Err(invalid(format!("duplicate step {}", step.name)));
+        }
+        if let Some(unknown) = step
+            .depends_on
+            .iter()
+            .find(|d| !pipeline.steps.iter().any(|s| &s.name == *d))
+        {
+            return Err(invalid(format!(
+                "step {} depends on unknown step {unknown}",
+                step.name
+            )));
+        }
+    }
+
+    let mut remaining = pipeline.steps;
+    let mut ordered: Vec<PipelineStep> = vec![];
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|step| {
+            step.depends_on
+                .iter()
+                .all(|d| ordered.iter().any(|s| &s.name == d))
+        });
+        match ready {
+            Some(i) => ordered.push(remaining.remove(i)),
+            None => {
+                let names: Vec<&str> = remaining.iter().map(|s| s.name.as_str()).collect();
+                return Err(invalid(format!(
+                    "cyclic dependencies between {}",
+                    names.join(", ")
+                )));
+            }
+        }
+    }
+
+    let mut planned: Vec<PlannedStep> = vec![];
+    for step in ordered {
+        let namespace = step.op.namespace();
+        let namespace = match namespace.strip_prefix('$') {
+            Some(binding) => pipeline.namespaces.get(binding).ok_or_else(|| {
+                invalid(format!(
+                    "step {} refers to unknown namespace {namespace}",
+                    step.name
+                ))
+            })?,
+            None => namespace,
+        };
+
+        let path = match step.op {
+            StepOp::Export { .. } => authorize_namespace(token, namespace, false)?,
+            StepOp::Transform { .. } => {
+                authorize_namespace(token, namespace, false)?;
+                authorize_namespace(token, namespace, true)?
+            }
+            _ => authorize_namespace(token, namespace, true)?,
+        };
+
+        step.op
+            .validate()
+            .map_err(|e| invalid(format!("step {}: {e}", step.name)))?;
+
+        let depends_on = step
+            .depends_on
+            .iter()
+            .filter_map(|d| planned.iter().position(|p| &p.step.name == d))
+            .collect();
+        planned.push(PlannedStep {
+            step,
+            path,
+            depends_on,
+        });
+    }
+
+    Ok(planned)
+}
+
+/// Runs one step, returning its output. Writes are published like the same requests to the
+/// spaces routes.
+async fn execute(
+    token: &Token,
+    limiter: &TranslationLimiter,
+    events: &EventBus,
+    op: &StepOp,
+    path: PathBuf,
+) -> Result<Option<String>, String> {
+    let mork_api_client = MorkApiClient::new();
+    let backend_error = |e| format!("Failed to contact backend: {e}");
+
+    match op {
+        StepOp::Import {
+            uri,
+            pattern,
+            template,
+            ..
+        } => {
+            let mm2 = ingest_mm2(pattern.clone(), template.clone()).map_err(|Custom(_, e)| e)?;
+            let outcome = import_into(
+                token,
+                path.clone(),
+                uri.clone(),
+                mm2,
+                &ImportPolicy::from_env(),
+            )
+            .await;
+            publish_import(events, &path, token, &outcome);
+            outcome.map(|_| None).map_err(|Custom(_, e)| e)
+        }
+        StepOp::Upload {
+            data,
+            pattern,
+            template,
+            ..
+        } => {
+            let mm2 = ingest_mm2(pattern.clone(), template.clone()).map_err(|Custom(_, e)| e)?;
+            let request = UploadRequest::new()
+                .namespace(path.clone())
+                .pattern(mm2.patterns[0].clone())
+                .template(mm2.templates[0].clone())
+                .data(data.clone());
+            let outcome = mork_api_client.dispatch(request).await;
+            events
+                .publish(event(Operation::Upload, &path, token, &outcome).size(data.len() as u64));
+            outcome.map(|_| None).map_err(backend_error)
+        }
+        StepOp::Translate {
+            format, uri, data, ..
+        } => {
+            let text = match (uri, data) {
+                (Some(uri), _) => ImportPolicy::from_env()
+                    .fetch(uri)
+                    .await
+                    .map_err(|e| format!("{}: {e}", e.code()))?,
+                (None, data) => data.clone().unwrap_or_default(),
+            };
+            let failed =
+                |Custom(_, Json(e)): TranslationFailure| format!("{}: {}", e.error, e.message);
+            let metta = translate_text(token, limiter, *format, text)
+                .await
+                .map_err(failed)?;
+            let atoms = load(token, path, metta, events).await.map_err(failed)?;
+            Ok(Some(format!("{atoms} atoms loaded")))
+        }
+        StepOp::Transform {
+            patterns,
+            templates,
+            ..
+        } => {
+            let request = TransformRequest::new()
+                .namespace(path.clone())
+                .transform_input(
+                    TransformDetails::new()
+                        .patterns(patterns.clone())
+                        .templates(templates.clone()),
+                );
+            let outcome = mork_api_client.dispatch(request).await;
+            publish(events, Operation::Transform, &path, token, &outcome);
+            outcome.map(|_| None).map_err(backend_error)
+        }
+        StepOp::Clear { expr, .. } => {
+            let request = ClearRequest::new()
+                .namespace(path.clone())
+                .expr(expr.clone());
+            let outcome = mork_api_client.dispatch(request).await;
+            publish(events, Operation::Clear, &path, token, &outcome);
+            outcome.map(|_| None).map_err(backend_error)
+        }
+        StepOp::Export {
+            pattern, template, ..
+        } => {
+            let request = ExportRequest::new()
+                .namespace(path)
+                .pattern(pattern.clone())
+                .template(template.clone())
+                .format(ExportFormat::Metta);
+            mork_api_client
+                .dispatch(request)
+                .await
+                .map(Some)
+                .map_err(backend_error)
+        }
+    }
+}
+
+/// Runs the planned steps in order. A step whose dependencies did not all succeed is skipped,
+/// steps that do not depend on a failed one still run.
+async fn run_steps(
+    token: Token,
+    limiter: TranslationLimiter,
+    events: EventBus,
+    steps: Vec<PlannedStep>,
+    handle: JobHandle,
+) {
+    let names: Vec<String> = steps.iter().map(|p| p.step.name.clone()).collect();
+    let mut succeeded = vec![false; steps.len()];
+
+    for (i, planned) in steps.into_iter().enumerate() {
+        if handle.is_cancelled() {
+            handle.update_step(i, |step| {
+                step.status = StepStatus::Skipped;
+                step.message = Some("job was cancelled".to_string());
+            });
+            continue;
+        }
+
+        if let Some(&dependency) = planned.depends_on.iter().find(|&&d| !succeeded[d]) {
+            let dependency = &names[dependency];
+            handle.update_step(i, |step| {
+                step.status = StepStatus::Skipped;
+                step.message = Some(format!("{dependency} did not succeed"));
+            });
+            continue;
+        }
+
+        handle.update_step(i, |step| step.status = StepStatus::Running);
+        let started = Instant::now();
+        let outcome = execute(&token, &limiter, &events, &planned.step.op, planned.path).await;
+        let duration_ms = Some(started.elapsed().as_millis());
+
+        succeeded[i] = outcome.is_ok();
+        handle.update_step(i, |step| {
+            step.duration_ms = duration_ms;
+            match outcome {
+                Ok(output) => {
+                    step.status = StepStatus::Succeeded;
+                    step.output = output;
+                }
+                Err(e) => {
+                    step.status = StepStatus::Failed;
+                    step.message = Some(e);
+                }
+            }
+        });
+    }
+
+    let failed = succeeded.iter().filter(|s| !**s).count();
+    if handle.is_cancelled() {
+        handle.finish(JobStatus::Cancelled, None);
+    } else if failed == 0 {
+        handle.finish(JobStatus::Succeeded, None);
+    } else {
+        handle.finish(
+            JobStatus::Failed,
+            Some(format!(
+                "{failed} of {} steps did not succeed",
+                succeeded.len()
+            )),
+        );
+    }
+}
+
+/// Starts a background job running a pipeline of import, upload, translate, transform, clear
+/// and export steps over namespaces of the token. The whole document, including permissions,
+/// is checked before anything runs. Follow the job, with the result of every step, with
+/// `/jobs/<id>`.
+#[post("/pipelines", data = "<document>")]
+pub async fn run(
+    token: Token,
+    content_type: Option<&ContentType>,
+    document: Data<'_>,
+    limiter: &State<TranslationLimiter>,
+    jobs: &State<JobRegistry>,
+    events: &State<EventBus>,
+) -> Result<Accepted<Json<Job>>, Custom<String>> {
+    let max_bytes = env_limit("METTA_KG_PIPELINE_MAX_BYTES", 20 * 1024 * 1024);
+    let document = document
+        .open(max_bytes.into())
+        .into_string()
+        .await
+        .map_err(|e| Custom(Status::BadRequest, format!("Failed to read body: {e}")))?;
+    if !document.is_complete() {
+        return Err(Custom(
+            Status::PayloadTooLarge,
+            format!("too_large: pipelines may not exceed {max_bytes} bytes"),
+        ));
+    }
+
+    let yaml = content_type.is_some_and(|ct| matches!(ct.sub().as_str(), "yaml" | "x-yaml"));
+    let pipeline: PipelineInput = if yaml {
+        serde_yaml_ng::from_str(&document).map_err(|e| invalid(e.to_string()))?
+    } else {
+        serde_json::from_str(&document).map_err(|e| invalid(e.to_string()))?
+    };
+
+    let steps = plan(&token, pipeline)?;
+
+    let results = steps
+        .iter()
+        .map(|planned| StepResult {
+            name: planned.step.name.clone(),
+            op: planned.step.op.name().to_string(),
+            status: StepStatus::Pending,
+            message: None,
+            output: None,
+            duration_ms: None,
+        })
+        .collect();
+    let (job, handle) = jobs.start("pipeline", token.namespace.clone(), results);
+
+    let limiter = limiter.inner().clone();
+    let events = events.inner().clone();
+    rocket::tokio::spawn(run_steps(token, limiter, events, steps, handle));
+
+    Ok(Accepted(Json(job)))
+}
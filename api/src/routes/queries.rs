@@ -0,0 +1,345 @@
+use chrono::Utc;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::{serde_json::Value, Json};
+use rocket::{delete, get, post, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::db::establish_connection;
+use crate::events::{EventBus, Operation};
+use crate::metta::{parse, validate_mm2, Expr};
+use crate::model::{SavedQuery, SavedQueryInsert, Token};
+use crate::mork_api::{
+    ExportFormat, ExportRequest, MorkApiClient, TransformDetails, TransformRequest,
+};
+use crate::routes::spaces::{namespace_to_path, path_to_namespace, publish};
+use crate::schema::saved_queries;
+
+/// What running a saved query does with its namespace
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SavedQueryKind {
+    /// Exports the result of a single pattern and template, needs `read` permission
+    Query,
+    /// Writes the results of its templates into the namespace, needs `write` permission
+    Transform,
+}
+
+impl SavedQueryKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SavedQueryKind::Query => "query",
+            SavedQueryKind::Transform => "transform",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedQueryInput {
+    pub namespace: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub kind: SavedQueryKind,
+    pub patterns: Vec<String>,
+    pub templates: Vec<String>,
+    /// Names of the variables, without `$`, that are bound when the query is run rather than
+    /// by its patterns
+    #[serde(default)]
+    pub parameters: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum SavedQueryOutput {
+    /// The exported MeTTa of a query
+    Exported(String),
+    /// Whether a transform went through
+    Transformed(bool),
+}
+
+fn bad_request(code: &str, message: String) -> Custom<String> {
+    Custom(Status::BadRequest, format!("{code}: {message}"))
+}
+
+/// Parses `patterns` and `templates` with the `parameters` bound to `values`. Without
+/// `values`, as when a query is saved, the parameters count as bound by the patterns.
+fn bind(
+    query: &SavedQueryInput,
+    values: Option<&HashMap<String, Expr>>,
+) -> Result<(Vec<String>, Vec<String>), Custom<String>> {
+    let parse_all = |sources: &[String], what: &str| {
+        sources
+            .iter()
+            .map(|source| {
+                let atom = parse(source)
+                    .map_err(|e| bad_request("invalid_query", format!("invalid {what}: {e}")))?;
+                Ok(match values {
+                    Some(values) => atom.substitute(values).to_string(),
+                    None => atom.to_string(),
+                })
+            })
+            .collect::<Result<Vec<String>, Custom<String>>>()
+    };
+    let patterns = parse_all(&query.patterns, "pattern")?;
+    let templates = parse_all(&query.templates, "template")?;
+
+    // parameters count as bound, whether or not a pattern mentions them
+    let bound_by_parameters = query
+        .parameters
+        .iter()
+        .map(|p| format!("${p}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let conjunction = format!("(, {} ({bound_by_parameters}))", patterns.join(" "));
+    for template in &templates {
+        validate_mm2(&conjunction, template).map_err(|e| bad_request("invalid_query", e))?;
+    }
+
+    Ok((patterns, templates))
+}
+
+/// Values for the parameters of `query`: MeTTa atoms given as JSON strings or numbers, e.g.
+/// `{"user": "42", "name": "\"Alice\""}`
+fn parameter_values(
+    query: &SavedQueryInput,
+    values: &HashMap<String, Value>,
+) -> Result<HashMap<String, Expr>, Custom<String>> {
+    if let Some(unknown) = values.keys().find(|k| !query.parameters.contains(k)) {
+        return Err(bad_request(
+            "unknown_parameter",
+            format!("{} has no parameter {unknown}", query.name),
+        ));
+    }
+
+    query
+        .parameters
+        .iter()
+        .map(|name| {
+            let source = match values.get(name) {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Number(n)) => n.to_string(),
+                Some(_) => {
+                    return Err(bad_request(
+                        "invalid_parameter",
+                        format!("{name} must be a string or a number"),
+                    ))
+                }
+                None => {
+                    return Err(bad_request(
+                        "missing_parameter",
+                        format!("no value for {name}"),
+                    ))
+                }
+            };
+            let atom = parse(&source)
+                .map_err(|e| bad_request("invalid_parameter", format!("{name}: {e}")))?;
+            if !atom.variables().is_empty() {
+                return Err(bad_request(
+                    "invalid_parameter",
+                    format!("{name} must not contain variables"),
+                ));
+            }
+            Ok((name.clone(), atom))
+        })
+        .collect()
+}
+
+impl From<&SavedQuery> for SavedQueryInput {
+    fn from(query: &SavedQuery) -> Self {
+        SavedQueryInput {
+            namespace: query.namespace.clone(),
+            name: query.name.clone(),
+            description: query.description.clone(),
+            kind: match query.kind.as_str() {
+                "transform" => SavedQueryKind::Transform,
+                _ => SavedQueryKind::Query,
+            },
+            patterns: query.patterns.clone(),
+            templates: query.templates.clone(),
+            parameters: query.parameters.clone(),
+        }
+    }
+}
+
+/// Loads the saved query with `query_id`, provided it lies in the namespace of `token`
+fn find_query(token: &Token, query_id: i32) -> Result<SavedQuery, Status> {
+    let query = saved_queries::table
+        .select(SavedQuery::as_select())
+        .filter(saved_queries::id.eq(query_id))
+        .get_result(&mut establish_connection())
+        .map_err(|_| Status::NotFound)?;
+
+    if !query.namespace.starts_with(&token.namespace) {
+        return Err(Status::NotFound);
+    }
+
+    Ok(query)
+}
+
+/// Saves a named set of MM2 patterns and templates for a namespace. Names are unique per
+/// namespace.
+#[post("/queries", data = "<input>")]
+pub fn create(
+    token: Token,
+    input: Json<SavedQueryInput>,
+) -> Result<Json<SavedQuery>, Custom<String>> {
+    let path = namespace_to_path(&input.namespace)
+        .ok_or_else(|| Custom(Status::BadRequest, "Invalid namespace".to_string()))?;
+
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_write {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    if input.name.trim().is_empty() {
+        return Err(bad_request("invalid_query", "name is empty".to_string()));
+    }
+    if input.patterns.is_empty() || input.templates.is_empty() {
+        return Err(bad_request(
+            "invalid_query",
+            "patterns and templates must not be empty".to_string(),
+        ));
+    }
+    if input.kind == SavedQueryKind::Query
+        && (input.patterns.len() != 1 || input.templates.len() != 1)
+    {
+        return Err(bad_request(
+            "invalid_query",
+            "a query has a single pattern and template".to_string(),
+        ));
+    }
+    bind(&input, None)?;
+
+    let to_insert = SavedQueryInsert {
+        token: token.id,
+        namespace: path_to_namespace(&path),
+        name: input.name.clone(),
+        description: input.description.clone(),
+        kind: input.kind.as_str().to_string(),
+        patterns: input.patterns.clone(),
+        templates: input.templates.clone(),
+        parameters: input.parameters.clone(),
+        creation_timestamp: Utc::now().naive_utc(),
+    };
+
+    let result = diesel::insert_into(saved_queries::table)
+        .values(&to_insert)
+        .get_result(&mut establish_connection());
+
+    match result {
+        Ok(query) => Ok(Json(query)),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => Err(Custom(
+            Status::Conflict,
+            format!(
+                "duplicate_name: {} already exists in {}",
+                to_insert.name, to_insert.namespace
+            ),
+        )),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            "Failed to store query".to_string(),
+        )),
+    }
+}
+
+/// All saved queries in the namespace of the token
+#[get("/queries")]
+pub fn get_all(token: Token) -> Result<Json<Vec<SavedQuery>>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    let results = saved_queries::table
+        .select(SavedQuery::as_select())
+        .order(saved_queries::id.asc())
+        .load(&mut establish_connection());
+
+    match results {
+        Ok(results) => Ok(Json(
+            results
+                .into_iter()
+                .filter(|query| query.namespace.starts_with(&token.namespace))
+                .collect(),
+        )),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+#[delete("/queries/<query_id>")]
+pub fn delete(token: Token, query_id: i32) -> Status {
+    if !token.permission_write {
+        return Status::Unauthorized;
+    }
+
+    if let Err(status) = find_query(&token, query_id) {
+        return status;
+    }
+
+    let result = diesel::delete(saved_queries::table.filter(saved_queries::id.eq(query_id)))
+        .execute(&mut establish_connection());
+
+    match result {
+        Ok(_) => Status::Ok,
+        Err(_) => Status::NotFound,
+    }
+}
+
+/// Runs a saved query on its namespace, with its parameters bound to the values in the body
+#[post("/queries/<query_id>/run", data = "<values>")]
+pub async fn run(
+    token: Token,
+    query_id: i32,
+    values: Option<Json<HashMap<String, Value>>>,
+    events: &State<EventBus>,
+) -> Result<Json<SavedQueryOutput>, Custom<String>> {
+    let saved = find_query(&token, query_id).map_err(|s| Custom(s, "Not found".to_string()))?;
+    let query = SavedQueryInput::from(&saved);
+
+    let allowed = match query.kind {
+        SavedQueryKind::Query => token.permission_read,
+        SavedQueryKind::Transform => token.permission_read && token.permission_write,
+    };
+    if !allowed {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    let values = parameter_values(&query, &values.map(|v| v.into_inner()).unwrap_or_default())?;
+    let (patterns, templates) = bind(&query, Some(&values))?;
+
+    let path = namespace_to_path(&saved.namespace)
+        .ok_or_else(|| Custom(Status::InternalServerError, "Invalid namespace".to_string()))?;
+    let mork_api_client = MorkApiClient::new();
+    let backend_error = |e: Status| Custom(e, "Failed to contact backend".to_string());
+
+    match query.kind {
+        SavedQueryKind::Query => {
+            let request = ExportRequest::new()
+                .namespace(path)
+                .pattern(patterns[0].clone())
+                .template(templates[0].clone())
+                .format(ExportFormat::Metta);
+            mork_api_client
+                .dispatch(request)
+                .await
+                .map(|data| Json(SavedQueryOutput::Exported(data)))
+                .map_err(backend_error)
+        }
+        SavedQueryKind::Transform => {
+            let request = TransformRequest::new()
+                .namespace(path.clone())
+                .transform_input(
+                    TransformDetails::new()
+                        .patterns(patterns)
+                        .templates(templates),
+                );
+            let outcome = mork_api_client.dispatch(request).await;
+            publish(events, Operation::Transform, &path, &token, &outcome);
+            outcome
+                .map(|_| Json(SavedQueryOutput::Transformed(true)))
+                .map_err(backend_error)
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use rocket::http::Status;
+use rocket::response::status::{Accepted, Custom};
+use rocket::serde::json::Json;
+use rocket::{post, State};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::events::{EventBus, Operation};
+use crate::jobs::{IterationStats, Job, JobHandle, JobRegistry, JobStatus};
+use crate::metta::{parse_all, validate_mm2};
+use crate::model::Token;
+use crate::mork_api::{
+    ExportFormat, ExportRequest, MorkApiClient, TransformDetails, TransformRequest,
+};
+use crate::routes::spaces::{path_to_namespace, publish, Mm2InputMulti};
+use crate::routes::translations::env_limit;
+
+/// An ordered set of rules, applied until the namespace stops growing
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct RuleSetInput {
+    pub rules: Vec<Mm2InputMulti>,
+    /// Capped by `METTA_KG_RULES_MAX_ITERATIONS`
+    pub max_iterations: Option<usize>,
+    /// Capped by `METTA_KG_RULES_MAX_SECONDS`
+    pub max_seconds: Option<u64>,
+}
+
+/// Number of atoms in the `path` space
+async fn count_atoms(mork_api_client: &MorkApiClient, path: &Path) -> Result<usize, String> {
+    let request = ExportRequest::new()
+        .namespace(path.to_path_buf())
+        .pattern("$x".to_string())
+        .template("$x".to_string())
+        .format(ExportFormat::Metta);
+
+    let data = mork_api_client
+        .dispatch(request)
+        .await
+        .map_err(|e| format!("Failed to count atoms: {e}"))?;
+    parse_all(&data)
+        .map(|atoms| atoms.len())
+        .map_err(|e| format!("Failed to count atoms: {e}"))
+}
+
+/// Applies `rules` in order, once per iteration, until an iteration adds no atoms. The limits
+/// are checked between iterations, cancellation between rules. Every transform is published
+/// on behalf of `token`, like a request to `/spaces/transform`.
+async fn fixpoint(
+    token: &Token,
+    events: &EventBus,
+    path: PathBuf,
+    rules: Vec<TransformDetails>,
+    max_iterations: usize,
+    max_duration: Duration,
+    handle: &JobHandle,
+) -> Result<(JobStatus, Option<String>), String> {
+    let mork_api_client = MorkApiClient::new();
+    let started = Instant::now();
+
+    let mut atoms = count_atoms(&mork_api_client, &path).await?;
+    handle.set_initial_atoms(atoms);
+
+    for iteration in 1..=max_iterations {
+        if started.elapsed() >= max_duration {
+            return Ok((
+                JobStatus::LimitReached,
+                Some(format!(
+                    "no fixpoint after {} seconds",
+                    max_duration.as_secs()
+                )),
+            ));
+        }
+
+        let iteration_started = Instant::now();
+        for (i, rule) in rules.iter().enumerate() {
+            if handle.is_cancelled() {
+                return Ok((JobStatus::Cancelled, None));
+            }
+
+            let request = TransformRequest::new()
+                .namespace(path.clone())
+                .transform_input(rule.clone());
+            let outcome = mork_api_client.dispatch(request).await;
+            publish(events, Operation::Transform, &path, token, &outcome);
+            outcome.map_err(|e| format!("Rule {i} failed in iteration {iteration}: {e}"))?;
+        }
+
+        let count = count_atoms(&mork_api_client, &path).await?;
+        handle.record(IterationStats {
+            iteration,
+            atoms: count,
+            added: count.saturating_sub(atoms),
+            duration_ms: iteration_started.elapsed().as_millis(),
+        });
+
+        if count == atoms {
+            return Ok((JobStatus::Converged, None));
+        }
+        atoms = count;
+    }
+
+    Ok((
+        JobStatus::LimitReached,
+        Some(format!("no fixpoint after {max_iterations} iterations")),
+    ))
+}
+
+/// Starts a background job applying an ordered set of rules to the `<path..>` space until a
+/// fixpoint, e.g. for a transitive closure. Each rule is a transform; an iteration applies
+/// them all in order and the job stops once an iteration adds no atoms, or at the iteration
+/// or time limit. Follow the job with `/jobs/<id>`.
+#[post("/spaces/rules/<path..>", data = "<rule_set>")]
+pub async fn run(
+    token: Token,
+    path: PathBuf,
+    rule_set: Json<RuleSetInput>,
+    jobs: &State<JobRegistry>,
+    events: &State<EventBus>,
+) -> Result<Accepted<Json<Job>>, Custom<String>> {
+    let token_namespace = token.namespace.strip_prefix("/").unwrap();
+    if !path.starts_with(token_namespace) || !token.permission_read || !token.permission_write {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    if rule_set.rules.is_empty() {
+        return Err(Custom(
+            Status::BadRequest,
+            "invalid_rules: no rules".to_string(),
+        ));
+    }
+    for (i, rule) in rule_set.rules.iter().enumerate() {
+        if rule.patterns.is_empty() || rule.templates.is_empty() {
+            return Err(Custom(
+                Status::BadRequest,
+                format!("invalid_rules: rule {i} needs patterns and templates"),
+            ));
+        }
+        let conjunction = format!("(, {})", rule.patterns.join(" "));
+        for template in &rule.templates {
+            validate_mm2(&conjunction, template)
+                .map_err(|e| Custom(Status::BadRequest, format!("invalid_rules: rule {i}: {e}")))?;
+        }
+    }
+
+    let max_iterations = env_limit("METTA_KG_RULES_MAX_ITERATIONS", 100) as usize;
+    let max_iterations = rule_set
+        .max_iterations
+        .map_or(max_iterations, |n| n.min(max_iterations));
+    let max_seconds = env_limit("METTA_KG_RULES_MAX_SECONDS", 600);
+    let max_seconds = rule_set
+        .max_seconds
+        .map_or(max_seconds, |n| n.min(max_seconds));
+
+    let rules = rule_set
+        .rules
+        .iter()
+        .map(|rule| {
+            TransformDetails::new()
+                .patterns(rule.patterns.clone())
+                .templates(rule.templates.clone())
+        })
+        .collect();
+
+    let (job, handle) = jobs.start("rules", path_to_namespace(&path), vec![]);
+    let events = events.inner().clone();
+    rocket::tokio::spawn(async move {
+        let outcome = fixpoint(
+            &token,
+            &events,
+            path,
+            rules,
+            max_iterations,
+            Duration::from_secs(max_seconds),
+            &handle,
+        )
+        .await;
+
+        match outcome {
+            Ok((status, message)) => handle.finish(status, message),
+            Err(e) => {
+                eprintln!("Rules job failed: {e}");
+                handle.finish(JobStatus::Failed, Some(e));
+            }
+        }
+    });
+
+    Ok(Accepted(Json(job)))
+}
@@ -0,0 +1,259 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, State};
+use std::path::PathBuf;
+use std::{env, fs, io};
+
+use crate::db::establish_connection;
+use crate::events::{EventBus, NamespaceEvent, Operation};
+use crate::metta::parse_all;
+use crate::model::{Snapshot, SnapshotInsert, Token};
+use crate::mork_api::{ClearRequest, ExportFormat, ExportRequest, MorkApiClient, UploadRequest};
+use crate::routes::spaces::{namespace_to_path, path_to_namespace, publish};
+use crate::routes::translations::env_limit;
+use crate::schema::snapshots;
+
+/// File holding the content of snapshot `snapshot_id`, in `METTA_KG_SNAPSHOT_DIR` (default
+/// `snapshots`). Only the details of a snapshot are kept in the database.
+fn content_file(snapshot_id: i32) -> PathBuf {
+    PathBuf::from(env::var("METTA_KG_SNAPSHOT_DIR").unwrap_or("snapshots".to_string()))
+        .join(format!("{snapshot_id}.metta"))
+}
+
+/// Loads snapshot `snapshot_id` with its content, provided it lies within the namespace of
+/// `token`
+pub(crate) fn find(token: &Token, snapshot_id: i32) -> Result<(Snapshot, String), Status> {
+    let snapshot = snapshots::table
+        .select(Snapshot::as_select())
+        .filter(snapshots::id.eq(snapshot_id))
+        .get_result(&mut establish_connection());
+
+    let snapshot = match snapshot {
+        Ok(snapshot) if snapshot.namespace.starts_with(&token.namespace) => snapshot,
+        _ => return Err(Status::NotFound),
+    };
+
+    match fs::read_to_string(content_file(snapshot_id)) {
+        Ok(content) => Ok((snapshot, content)),
+        Err(e) => {
+            eprintln!("Failed to read content of snapshot {snapshot_id}: {e}");
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Writes `content` to the file of snapshot `snapshot_id`, through a temporary file so a
+/// snapshot is never seen half written
+async fn store_content(snapshot_id: i32, content: &str) -> io::Result<()> {
+    let file = content_file(snapshot_id);
+    if let Some(dir) = file.parent() {
+        rocket::tokio::fs::create_dir_all(dir).await?;
+    }
+    let partial = file.with_extension("metta.partial");
+    rocket::tokio::fs::write(&partial, content).await?;
+    rocket::tokio::fs::rename(&partial, &file).await
+}
+
+/// Stores the current contents of the `<path..>` space and the spaces below it, to be restored
+/// later. Needs `read` permission. Exports above `METTA_KG_SNAPSHOT_MAX_BYTES` (default 100 MiB)
+/// are refused.
+#[post("/spaces/snapshot/<path..>?<description>")]
+pub async fn create(
+    token: Token,
+    path: PathBuf,
+    description: Option<String>,
+) -> Result<Json<Snapshot>, Custom<String>> {
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_read {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    // atoms of the namespaces below keep their wrappers, relative to `path`
+    let request = ExportRequest::new()
+        .namespace(path.clone())
+        .pattern("$x".to_string())
+        .template("$x".to_string())
+        .format(ExportFormat::Metta)
+        .subtree(true);
+
+    let content = MorkApiClient::new().dispatch(request).await.map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            format!("Failed to contact backend: {e}"),
+        )
+    })?;
+
+    let max_bytes = env_limit("METTA_KG_SNAPSHOT_MAX_BYTES", 100 * 1024 * 1024);
+    if content.len() as u64 > max_bytes {
+        return Err(Custom(
+            Status::PayloadTooLarge,
+            format!(
+                "too_large: namespace holds {} bytes, which exceeds the limit",
+                content.len()
+            ),
+        ));
+    }
+
+    // a snapshot that cannot be uploaded again is no use
+    let atoms = parse_all(&content).map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            format!("Failed to parse export: {e}"),
+        )
+    })?;
+
+    let to_insert = SnapshotInsert {
+        token: Some(token.id),
+        namespace: path_to_namespace(&path),
+        description: description.unwrap_or_default(),
+        atoms: atoms.len() as i32,
+        bytes: content.len() as i64,
+        creation_timestamp: Utc::now().naive_utc(),
+    };
+
+    let snapshot = diesel::insert_into(snapshots::table)
+        .values(&to_insert)
+        .returning(Snapshot::as_returning())
+        .get_result(&mut establish_connection())
+        .map_err(|_| {
+            Custom(
+                Status::InternalServerError,
+                "Failed to store snapshot".to_string(),
+            )
+        })?;
+
+    if let Err(e) = store_content(snapshot.id, &content).await {
+        eprintln!("Failed to write content of snapshot {}: {e}", snapshot.id);
+        let _ = diesel::delete(snapshots::table.filter(snapshots::id.eq(snapshot.id)))
+            .execute(&mut establish_connection());
+        return Err(Custom(
+            Status::InternalServerError,
+            "Failed to store snapshot".to_string(),
+        ));
+    }
+
+    Ok(Json(snapshot))
+}
+
+/// Snapshots of namespaces of the token, newest first
+#[get("/snapshots")]
+pub fn get_all(token: Token) -> Result<Json<Vec<Snapshot>>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    let results = snapshots::table
+        .select(Snapshot::as_select())
+        .order(snapshots::id.desc())
+        .load(&mut establish_connection());
+
+    match results {
+        Ok(results) => Ok(Json(
+            results
+                .into_iter()
+                .filter(|snapshot| snapshot.namespace.starts_with(&token.namespace))
+                .collect(),
+        )),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+/// Puts the namespace of the snapshot and the namespaces below it back in the state they were
+/// captured in: everything in them is cleared, then the snapshot is uploaded. Needs `write`
+/// permission on the namespace.
+#[post("/snapshots/<snapshot_id>/restore")]
+pub async fn restore(
+    token: Token,
+    snapshot_id: i32,
+    events: &State<EventBus>,
+) -> Result<Json<Snapshot>, Custom<String>> {
+    let (snapshot, content) =
+        find(&token, snapshot_id).map_err(|status| Custom(status, "Not found".to_string()))?;
+
+    if !token.permission_write {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+    let path = namespace_to_path(&snapshot.namespace).ok_or_else(|| {
+        Custom(
+            Status::InternalServerError,
+            format!("Invalid namespace {}", snapshot.namespace),
+        )
+    })?;
+
+    let mork_api_client = MorkApiClient::new();
+
+    let request = ClearRequest::new()
+        .namespace(path.clone())
+        .expr("$x".to_string())
+        .subtree(true);
+    let outcome = mork_api_client.dispatch(request).await;
+    publish(events, Operation::Clear, &path, &token, &outcome);
+    outcome.map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            format!("Failed to clear {}: {e}", snapshot.namespace),
+        )
+    })?;
+
+    let request = UploadRequest::new()
+        .namespace(path)
+        .pattern("$x".to_string())
+        .template("$x".to_string())
+        .data(content)
+        .subtree(true);
+    let outcome = mork_api_client.dispatch(request).await;
+    events.publish(
+        NamespaceEvent::new(
+            Operation::Upload,
+            snapshot.namespace.clone(),
+            token.id,
+            outcome.is_ok(),
+        )
+        .size(snapshot.bytes as u64),
+    );
+    outcome.map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            format!(
+                "Cleared {} but failed to upload the snapshot: {e}",
+                snapshot.namespace
+            ),
+        )
+    })?;
+
+    Ok(Json(snapshot))
+}
+
+#[delete("/snapshots/<snapshot_id>")]
+pub fn delete(token: Token, snapshot_id: i32) -> Status {
+    if !token.permission_write {
+        return Status::Unauthorized;
+    }
+
+    let snapshot = snapshots::table
+        .select(Snapshot::as_select())
+        .filter(snapshots::id.eq(snapshot_id))
+        .get_result(&mut establish_connection());
+
+    match snapshot {
+        Ok(snapshot) if snapshot.namespace.starts_with(&token.namespace) => (),
+        _ => return Status::NotFound,
+    }
+
+    let result = diesel::delete(snapshots::table.filter(snapshots::id.eq(snapshot_id)))
+        .execute(&mut establish_connection());
+
+    if result.is_err() {
+        return Status::NotFound;
+    }
+
+    match fs::remove_file(content_file(snapshot_id)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            eprintln!("Failed to remove content of snapshot {snapshot_id}: {e}");
+        }
+        _ => (),
+    }
+    Status::Ok
+}
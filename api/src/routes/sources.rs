@@ -0,0 +1,180 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, State};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use url::Url;
+
+use crate::db::establish_connection;
+use crate::events::EventBus;
+use crate::import_policy::ImportPolicy;
+use crate::model::{ImportRun, ImportSource, ImportSourceInsert, Token};
+use crate::routes::spaces::{ingest_mm2, namespace_to_path, path_to_namespace};
+use crate::schedule::Schedule;
+use crate::scheduler::run_source;
+use crate::schema::{import_runs, import_sources};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImportSourceInput {
+    pub uri: String,
+    pub namespace: String,
+    pub pattern: Option<String>,
+    pub template: Option<String>,
+    /// see [`Schedule`]
+    pub schedule: String,
+    /// clear the data level of the namespace before every import
+    #[serde(default)]
+    pub clear_before: bool,
+}
+
+/// Loads the source with `source_id`, provided it lies in the namespace of `token`
+fn find_source(token: &Token, source_id: i32) -> Result<ImportSource, Status> {
+    let source = import_sources::table
+        .select(ImportSource::as_select())
+        .filter(import_sources::id.eq(source_id))
+        .get_result(&mut establish_connection())
+        .map_err(|_| Status::NotFound)?;
+
+    if !source.namespace.starts_with(&token.namespace) {
+        return Err(Status::NotFound);
+    }
+
+    Ok(source)
+}
+
+/// Registers a remote source that is re-imported into its namespace on schedule
+#[post("/sources", data = "<input>")]
+pub fn create(
+    token: Token,
+    input: Json<ImportSourceInput>,
+) -> Result<Json<ImportSource>, Custom<String>> {
+    let path = namespace_to_path(&input.namespace)
+        .ok_or_else(|| Custom(Status::BadRequest, "Invalid namespace".to_string()))?;
+
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_write {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    ingest_mm2(input.pattern.clone(), input.template.clone())?;
+
+    let parsed_schedule = Schedule::from_str(&input.schedule)
+        .map_err(|e| Custom(Status::BadRequest, e.to_string()))?;
+
+    // the full check, including DNS resolution, happens on every run
+    let url = Url::parse(&input.uri)
+        .map_err(|_| Custom(Status::BadRequest, "invalid_uri".to_string()))?;
+    if let Err(e) = ImportPolicy::from_env().check_url(&url) {
+        return Err(Custom(e.status(), format!("{}: {e}", e.code())));
+    }
+
+    let now = Utc::now().naive_utc();
+
+    let to_insert = ImportSourceInsert {
+        token: token.id,
+        namespace: path_to_namespace(&path),
+        uri: input.uri.clone(),
+        pattern: input.pattern.clone(),
+        template: input.template.clone(),
+        schedule: input.schedule.clone(),
+        clear_before: input.clear_before,
+        creation_timestamp: now,
+        next_run: parsed_schedule.next_after(now),
+    };
+
+    let result = diesel::insert_into(import_sources::table)
+        .values(&to_insert)
+        .get_result(&mut establish_connection());
+
+    match result {
+        Ok(source) => Ok(Json(source)),
+        Err(_) => Err(Custom(
+            Status::InternalServerError,
+            "Failed to store source".to_string(),
+        )),
+    }
+}
+
+/// All sources in the namespace of the token
+#[get("/sources")]
+pub fn get_all(token: Token) -> Result<Json<Vec<ImportSource>>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    let results = import_sources::table
+        .select(ImportSource::as_select())
+        .order(import_sources::id.asc())
+        .load(&mut establish_connection());
+
+    match results {
+        Ok(results) => Ok(Json(
+            results
+                .into_iter()
+                .filter(|source| source.namespace.starts_with(&token.namespace))
+                .collect(),
+        )),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+#[delete("/sources/<source_id>")]
+pub fn delete(token: Token, source_id: i32) -> Status {
+    if !token.permission_write {
+        return Status::Unauthorized;
+    }
+
+    if let Err(status) = find_source(&token, source_id) {
+        return status;
+    }
+
+    let result = diesel::delete(import_sources::table.filter(import_sources::id.eq(source_id)))
+        .execute(&mut establish_connection());
+
+    match result {
+        Ok(_) => Status::Ok,
+        Err(_) => Status::NotFound,
+    }
+}
+
+/// Past runs of a source, most recent first
+#[get("/sources/<source_id>/runs")]
+pub fn runs(token: Token, source_id: i32) -> Result<Json<Vec<ImportRun>>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    find_source(&token, source_id)?;
+
+    let results = import_runs::table
+        .select(ImportRun::as_select())
+        .filter(import_runs::source.eq(source_id))
+        .order(import_runs::id.desc())
+        .load(&mut establish_connection());
+
+    match results {
+        Ok(results) => Ok(Json(results)),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+/// Runs a source immediately, outside of its schedule
+#[post("/sources/<source_id>/run")]
+pub async fn run(
+    token: Token,
+    source_id: i32,
+    events: &State<EventBus>,
+) -> Result<Json<ImportRun>, Status> {
+    if !token.permission_write {
+        return Err(Status::Unauthorized);
+    }
+
+    let source = find_source(&token, source_id)?;
+
+    match run_source(&source, events).await {
+        Ok(result) => Ok(Json(result)),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
@@ -1,18 +1,40 @@
 use rocket::http::Status;
+use rocket::serde::json::serde_json::{Map, Value};
 use rocket::serde::json::Json;
 use rocket::tokio::io::AsyncReadExt;
 use serde::{Deserialize, Serialize};
-use url::Url;
 
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use regex::Regex;
+use rocket::fs::TempFile;
 use rocket::response::status::Custom;
-use rocket::{get, post, Data};
-use std::path::PathBuf;
-
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::tokio::sync::Semaphore;
+use rocket::tokio::task::{spawn_blocking, JoinSet};
+use rocket::tokio::time::{interval_at, Duration, Instant};
+use rocket::{get, post, Data, Shutdown, State};
+use std::collections::BTreeSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::establish_connection;
+use crate::events::{EventBus, NamespaceEvent, Operation};
+use crate::export_formats::{convert, OutputFormat};
+use crate::import_policy::ImportPolicy;
+use crate::metta::{parse, parse_all, unescape, validate_mm2, Expr};
 use crate::model::Token;
 use crate::mork_api::{
-    ClearRequest, ExploreRequest, ExportFormat, ExportRequest, ImportRequest, MorkApiClient,
+    ClearRequest, ExploreRequest, ExportFormat, ExportRequest, MorkApiClient, Namespace,
     ReadRequest, Request, TransformDetails, TransformRequest, UploadRequest,
 };
+use crate::routes::translations::{
+    admit_input, env_limit, sparql_to_mm2, TranslationFailure, TranslationLimiter,
+};
+use crate::schema::tokens;
 
 /// The input for a transformation operation.
 /// see mm2 operations for more    // TODO: Add links
@@ -34,6 +56,71 @@ pub struct ExploreInput {
     pub token: String,
 }
 
+/// Resolves the optional `pattern`/`template` query parameters of upload and import into the
+/// mm2 pair that is applied to the incoming data. Defaults to `$x` -> `$x`, storing it unchanged.
+pub(crate) fn ingest_mm2(
+    pattern: Option<String>,
+    template: Option<String>,
+) -> Result<TransformDetails, Custom<String>> {
+    let (pattern, template) = match (pattern, template) {
+        (None, None) => return Ok(TransformDetails::new()),
+        (Some(pattern), Some(template)) => (pattern, template),
+        _ => {
+            return Err(Custom(
+                Status::BadRequest,
+                "pattern and template must be given together".to_string(),
+            ))
+        }
+    };
+
+    if let Err(e) = validate_mm2(&pattern, &template) {
+        return Err(Custom(Status::BadRequest, e));
+    }
+
+    Ok(TransformDetails::new()
+        .patterns(vec![pattern])
+        .templates(vec![template]))
+}
+
+pub(crate) fn event<T, E>(
+    operation: Operation,
+    path: &Path,
+    token: &Token,
+    outcome: &Result<T, E>,
+) -> NamespaceEvent {
+    NamespaceEvent::new(
+        operation,
+        path_to_namespace(path),
+        token.id,
+        outcome.is_ok(),
+    )
+}
+
+/// Tells subscribers such as triggers about a mutating `operation` on `path` by `token`
+pub(crate) fn publish<T, E>(
+    events: &EventBus,
+    operation: Operation,
+    path: &Path,
+    token: &Token,
+    outcome: &Result<T, E>,
+) {
+    events.publish(event(operation, path, token, outcome));
+}
+
+/// Like [`publish`] for imports, whose event carries the number of bytes loaded
+pub(crate) fn publish_import<E>(
+    events: &EventBus,
+    path: &Path,
+    token: &Token,
+    outcome: &Result<u64, E>,
+) {
+    let event = event(Operation::Import, path, token, outcome);
+    events.publish(match outcome {
+        Ok(size) => event.size(*size),
+        Err(_) => event,
+    });
+}
+
 /// Fetches the `<path..>` space content. Use cautously as it will load everything.
 /// It is recommended to use the `/spaces/<path..>?op=explore` instead for large queries
 #[get("/spaces/<path..>", rank = 1)]
@@ -55,6 +142,7 @@ pub async fn transform(
     token: Token,
     path: PathBuf,
     mm2: Json<Mm2InputMulti>,
+    events: &State<EventBus>,
 ) -> Result<Json<bool>, Status> {
     let token_namespace = token.namespace.strip_prefix("/").unwrap();
 
@@ -64,7 +152,7 @@ pub async fn transform(
 
     let mork_api_client = MorkApiClient::new();
     let request = TransformRequest::new()
-        .namespace(path.to_path_buf())
+        .namespace(path.clone())
         .transform_input(
             TransformDetails::new()
                 .patterns(mm2.patterns.clone())
@@ -72,24 +160,215 @@ pub async fn transform(
         );
 
     // TODO: use server sent events instead
-    match mork_api_client.dispatch(request).await {
+    let outcome = mork_api_client.dispatch(request).await;
+    publish(events, Operation::Transform, &path, &token, &outcome);
+
+    match outcome {
         Ok(_) => Ok(Json(true)),
         Err(e) => Err(e),
     }
 }
 
-/// Upload to the `<path..>` space. Exectes mm2 on the imported data.
-#[post("/spaces/upload/<path..>", data = "<data>")]
+/// Default and maximum number of atoms in [`TransformPreview::sample`]
+const PREVIEW_SAMPLE_SIZE: usize = 20;
+const PREVIEW_SAMPLE_MAX: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TransformPreview {
+    /// Number of atoms the templates produce that are not in the namespace yet
+    pub count: usize,
+    /// The first of these atoms
+    pub sample: Vec<String>,
+}
+
+/// Runs a transformation of the `<path..>` space without changing it: the templates are
+/// written to a scratch namespace, which is read back and cleared. Atoms the namespace already
+/// holds are left out, as the transform would not change them. Only needs `read` permission.
+#[post("/spaces/transform-preview/<path..>?<sample>", data = "<mm2>")]
+pub async fn transform_preview(
+    token: Token,
+    path: PathBuf,
+    sample: Option<usize>,
+    mm2: Json<Mm2InputMulti>,
+) -> Result<Json<TransformPreview>, Custom<String>> {
+    let token_namespace = token.namespace.strip_prefix("/").unwrap();
+    if !path.starts_with(token_namespace) || !token.permission_read {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    let mork_api_client = MorkApiClient::new();
+    let invalid = |e| {
+        Custom(
+            Status::InternalServerError,
+            format!("invalid_response: {e}"),
+        )
+    };
+
+    let request = ExportRequest::new()
+        .namespace(path.clone())
+        .pattern("$x".to_string())
+        .template("$x".to_string())
+        .format(ExportFormat::Metta);
+    let existing = mork_api_client
+        .dispatch(request)
+        .await
+        .map_err(|e| Custom(e, "Failed to contact backend".to_string()))?;
+    let existing: BTreeSet<Expr> = parse_all(&existing).map_err(invalid)?.into_iter().collect();
+
+    let details = TransformDetails::new()
+        .patterns(mm2.patterns.clone())
+        .templates(mm2.templates.clone());
+    let data = transform_into_scratch(&mork_api_client, path, details)
+        .await
+        .map_err(|e| Custom(e, "Failed to contact backend".to_string()))?;
+
+    let atoms: Vec<Expr> = parse_all(&data)
+        .map_err(invalid)?
+        .into_iter()
+        .filter(|atom| !existing.contains(atom))
+        .collect();
+    let sample_size = sample
+        .unwrap_or(PREVIEW_SAMPLE_SIZE)
+        .min(PREVIEW_SAMPLE_MAX);
+
+    Ok(Json(TransformPreview {
+        count: atoms.len(),
+        sample: atoms
+            .iter()
+            .take(sample_size)
+            .map(|a| a.to_string())
+            .collect(),
+    }))
+}
+
+/// A pattern matched in its own namespace
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SourcePattern {
+    pub namespace: String,
+    pub pattern: String,
+}
+
+/// A template written to its own namespace
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TargetTemplate {
+    pub namespace: String,
+    pub template: String,
+}
+
+/// The input for a transformation across namespaces
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CrossTransformInput {
+    pub patterns: Vec<SourcePattern>,
+    pub templates: Vec<TargetTemplate>,
+}
+
+/// Checks that `namespace` is valid and that the token may access it, as a source with `read`
+/// permission or as a target with `write` permission
+pub(crate) fn authorize_namespace(
+    token: &Token,
+    namespace: &str,
+    write: bool,
+) -> Result<PathBuf, Custom<String>> {
+    let path = namespace_to_path(namespace).ok_or_else(|| {
+        Custom(
+            Status::BadRequest,
+            format!("invalid_namespace: {namespace}"),
+        )
+    })?;
+
+    let allowed = if write {
+        token.permission_write
+    } else {
+        token.permission_read
+    };
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !allowed {
+        let permission = if write { "write" } else { "read" };
+        return Err(Custom(
+            Status::Unauthorized,
+            format!("unauthorized: no {permission} permission on {namespace}"),
+        ));
+    }
+
+    Ok(path)
+}
+
+/// Performs a transformation where every pattern is matched in its own namespace and every
+/// template written to its own namespace, e.g. to derive a curated namespace from several
+/// raw ones. Needs `read` permission on all sources and `write` permission on all targets.
+#[post("/spaces/cross-transform", data = "<mm2>")]
+pub async fn cross_transform(
+    token: Token,
+    mm2: Json<CrossTransformInput>,
+    events: &State<EventBus>,
+) -> Result<Json<bool>, Custom<String>> {
+    if mm2.patterns.is_empty() || mm2.templates.is_empty() {
+        return Err(Custom(
+            Status::BadRequest,
+            "invalid_transform: patterns and templates must not be empty".to_string(),
+        ));
+    }
+
+    let sources = mm2
+        .patterns
+        .iter()
+        .map(|p| authorize_namespace(&token, &p.namespace, false))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    let targets = mm2
+        .templates
+        .iter()
+        .map(|t| authorize_namespace(&token, &t.namespace, true))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+
+    let patterns: Vec<String> = mm2.patterns.iter().map(|p| p.pattern.clone()).collect();
+    let templates: Vec<String> = mm2.templates.iter().map(|t| t.template.clone()).collect();
+    let conjunction = format!("(, {})", patterns.join(" "));
+    for template in &templates {
+        validate_mm2(&conjunction, template)
+            .map_err(|e| Custom(Status::BadRequest, format!("invalid_transform: {e}")))?;
+    }
+
+    let request = TransformRequest::new()
+        .namespace(sources[0].clone())
+        .pattern_namespaces(sources)
+        .template_namespaces(targets.clone())
+        .transform_input(
+            TransformDetails::new()
+                .patterns(patterns)
+                .templates(templates),
+        );
+
+    let outcome = MorkApiClient::new().dispatch(request).await;
+    let mut changed: Vec<&PathBuf> = vec![];
+    for target in &targets {
+        if !changed.contains(&target) {
+            publish(events, Operation::Transform, target, &token, &outcome);
+            changed.push(target);
+        }
+    }
+
+    match outcome {
+        Ok(_) => Ok(Json(true)),
+        Err(e) => Err(Custom(e, "Failed to contact backend".to_string())),
+    }
+}
+
+/// Upload to the `<path..>` space. Exectes mm2 on the imported data, see [`ingest_mm2`].
+#[post("/spaces/upload/<path..>?<pattern>&<template>", data = "<data>")]
 pub async fn upload(
     token: Token,
     path: PathBuf,
+    pattern: Option<String>,
+    template: Option<String>,
     data: Data<'_>,
+    events: &State<EventBus>,
 ) -> Result<Json<String>, Custom<String>> {
     let token_namespace = token.namespace.strip_prefix("/").unwrap();
     if !path.starts_with(token_namespace) || !token.permission_write {
         return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
     }
 
+    let mm2 = ingest_mm2(pattern, template)?;
+
     let mut body = String::new();
     if let Err(e) = data
         .open(rocket::data::ByteUnit::Mebibyte(20))
@@ -103,17 +382,18 @@ pub async fn upload(
         ));
     }
 
-    let pattern = "$x";
-    let template = "$x";
-
+    let size = body.len() as u64;
     let mork_api_client = MorkApiClient::new();
     let request = UploadRequest::new()
-        .namespace(path)
-        .pattern(pattern.to_string())
-        .template(template.to_string())
+        .namespace(path.clone())
+        .pattern(mm2.patterns[0].clone())
+        .template(mm2.templates[0].clone())
         .data(body);
 
-    match mork_api_client.dispatch(request).await {
+    let outcome = mork_api_client.dispatch(request).await;
+    events.publish(event(Operation::Upload, &path, &token, &outcome).size(size));
+
+    match outcome {
         Ok(text) => Ok(Json(text)),
         Err(e) => Err(Custom(
             Status::InternalServerError,
@@ -122,27 +402,184 @@ pub async fn upload(
     }
 }
 
-/// Imports data from `<uri>` into the `<path..>` space. Exectes mm2 on the imported data.
-#[post("/spaces/import/<path..>?<uri>")]
-pub async fn import(token: Token, path: PathBuf, uri: String) -> Result<Json<bool>, Status> {
+/// Imports `uri` into `path` on behalf of `token`, shared by [`import`] and [`import_batch`].
+/// Returns the number of bytes loaded.
+pub(crate) async fn import_into(
+    token: &Token,
+    path: PathBuf,
+    uri: String,
+    mm2: TransformDetails,
+    policy: &ImportPolicy,
+) -> Result<u64, Custom<String>> {
     if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_write {
-        return Err(Status::Unauthorized);
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
     }
 
-    // validate uri
-    if Url::parse(&uri).is_err() {
-        return Err(Status::BadRequest);
-    }
+    // the API downloads the data itself and uploads it, rather than letting Mork fetch the uri,
+    // so the size and time limits hold for what is actually loaded
+    let data = match policy.fetch(&uri).await {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Rejected import of {uri} by token {}: {e}", token.id);
+            return Err(Custom(e.status(), format!("{}: {e}", e.code())));
+        }
+    };
 
+    let size = data.len() as u64;
     let mork_api_client = MorkApiClient::new();
-    let request = ImportRequest::new().namespace(path).uri(uri);
+    let request = UploadRequest::new()
+        .namespace(path)
+        .pattern(mm2.patterns[0].clone())
+        .template(mm2.templates[0].clone())
+        .data(data);
 
     match mork_api_client.dispatch(request).await {
-        Ok(_) => Ok(Json(true)),
-        Err(e) => Err(e),
+        Ok(_) => Ok(size),
+        Err(e) => Err(Custom(e, "Failed to contact backend".to_string())),
     }
 }
 
+/// Imports data from `<uri>` into the `<path..>` space. Exectes mm2 on the imported data, see
+/// [`ingest_mm2`].
+/// The uri is checked against the [`ImportPolicy`] and downloaded within its limits before the
+/// data is uploaded to Mork; rejections are answered with a body of the form `<code>: <reason>`.
+#[post("/spaces/import/<path..>?<uri>&<pattern>&<template>")]
+pub async fn import(
+    token: Token,
+    path: PathBuf,
+    uri: String,
+    pattern: Option<String>,
+    template: Option<String>,
+    events: &State<EventBus>,
+) -> Result<Json<bool>, Custom<String>> {
+    let mm2 = ingest_mm2(pattern, template)?;
+    let policy = ImportPolicy::from_env();
+
+    let outcome = import_into(&token, path.clone(), uri, mm2, &policy).await;
+    if !matches!(&outcome, Err(Custom(status, _)) if *status == Status::Unauthorized) {
+        publish_import(events, &path, &token, &outcome);
+    }
+
+    outcome.map(|_| Json(true))
+}
+
+/// Converts a namespace given in a request body, such as `/space/subspace/`, into the path form
+/// used by the `<path..>` routes. Rejects segments that are not valid namespace names.
+pub(crate) fn namespace_to_path(namespace: &str) -> Option<PathBuf> {
+    let segment_regex = Regex::new(r"^[a-zA-Z0-9]([a-zA-Z0-9]|\-|_)*$").unwrap();
+
+    namespace
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment_regex.is_match(segment).then_some(segment))
+        .collect::<Option<PathBuf>>()
+}
+
+/// Inverse of [`namespace_to_path`], e.g. `space/subspace` becomes `/space/subspace/`
+pub(crate) fn path_to_namespace(path: &Path) -> String {
+    let mut namespace = String::from("/");
+    for segment in path.iter() {
+        namespace.push_str(&segment.to_string_lossy());
+        namespace.push('/');
+    }
+    namespace
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImportBatchEntry {
+    pub uri: String,
+    pub namespace: String,
+    pub pattern: Option<String>,
+    pub template: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImportBatchResult {
+    pub uri: String,
+    pub namespace: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Imports every entry into its namespace, running at most `METTA_KG_IMPORT_BATCH_CONCURRENCY`
+/// (default 4) imports at the same time. Entries fail independently; the response lists the
+/// outcome of each entry in request order. Batches of more than
+/// `METTA_KG_IMPORT_BATCH_MAX_ENTRIES` (default 100) entries are refused as a whole.
+#[post("/spaces/import-batch", data = "<entries>")]
+pub async fn import_batch(
+    token: Token,
+    entries: Json<Vec<ImportBatchEntry>>,
+    events: &State<EventBus>,
+) -> Result<Json<Vec<ImportBatchResult>>, Custom<String>> {
+    if !token.permission_write {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    let entries = entries.into_inner();
+    let max_entries = env_limit("METTA_KG_IMPORT_BATCH_MAX_ENTRIES", 100);
+    if entries.len() as u64 > max_entries {
+        return Err(Custom(
+            Status::BadRequest,
+            format!(
+                "too_many_entries: batch holds {} entries, at most {max_entries} are allowed",
+                entries.len()
+            ),
+        ));
+    }
+
+    let concurrency = env::var("METTA_KG_IMPORT_BATCH_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(4);
+
+    let policy = ImportPolicy::from_env();
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+
+    for (i, entry) in entries.into_iter().enumerate() {
+        let token = token.clone();
+        let policy = policy.clone();
+        let semaphore = semaphore.clone();
+        let events = events.inner().clone();
+
+        tasks.spawn(async move {
+            let outcome = async {
+                let path = namespace_to_path(&entry.namespace)
+                    .ok_or_else(|| Custom(Status::BadRequest, "Invalid namespace".to_string()))?;
+                let mm2 = ingest_mm2(entry.pattern.clone(), entry.template.clone())?;
+
+                let _permit = semaphore.acquire().await.unwrap();
+                let outcome =
+                    import_into(&token, path.clone(), entry.uri.clone(), mm2, &policy).await;
+                if !matches!(&outcome, Err(Custom(status, _)) if *status == Status::Unauthorized) {
+                    publish_import(&events, &path, &token, &outcome);
+                }
+                outcome
+            }
+            .await;
+
+            let result = ImportBatchResult {
+                uri: entry.uri,
+                namespace: entry.namespace,
+                success: outcome.is_ok(),
+                error: outcome
+                    .err()
+                    .map(|Custom(status, message)| format!("{status}: {message}")),
+            };
+
+            (i, result)
+        });
+    }
+
+    let mut results: Vec<(usize, ImportBatchResult)> = tasks.join_all().await;
+    results.sort_by_key(|(i, _)| *i);
+
+    Ok(Json(
+        results.into_iter().map(|(_, result)| result).collect(),
+    ))
+}
+
 /// Performs an explore operation on the `<path..>` space. Get the result that
 /// matches the `<pattern>` by incrementally traversing the resulting space.
 #[post("/spaces/explore/<path..>", data = "<explore_input>")]
@@ -170,14 +607,20 @@ pub async fn explore(
 
 /// Performs an export operation on the `<path..>` space. Get the result that
 /// matches the `<pattern>` by incrementally traversing the resulting space.
-#[post("/spaces/export/<path..>", data = "<export_input>")]
+///
+/// The result is MeTTa unless another `format` is asked for, see
+/// [`crate::export_formats::OutputFormat`]. For the graph formats, edges with a `label`
+/// predicate name their source node instead of being drawn.
+#[post("/spaces/export/<path..>?<format>&<label>", data = "<export_input>")]
 pub async fn export(
     token: Token,
     path: PathBuf,
+    format: Option<OutputFormat>,
+    label: Vec<String>,
     export_input: Json<Mm2Input>,
-) -> Result<Json<String>, Status> {
+) -> Result<Json<String>, Custom<String>> {
     if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_read {
-        return Err(Status::Unauthorized);
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
     }
 
     let mork_api_client = MorkApiClient::new();
@@ -189,27 +632,292 @@ pub async fn export(
 
     println!("Dispatching export request to Mork: {}", request.path());
 
-    match mork_api_client.dispatch(request).await {
+    let data = match mork_api_client.dispatch(request).await {
         Ok(data) => {
             println!("Received export response from Mork: {data:?}");
-            Ok(Json(data))
+            data
+        }
+        Err(e) => return Err(Custom(e, "Failed to contact backend".to_string())),
+    };
+
+    convert(&data, format.unwrap_or_default(), &label)
+        .map(Json)
+        .map_err(|e| {
+            Custom(
+                Status::UnprocessableEntity,
+                format!("unsupported_atoms: {e}"),
+            )
+        })
+}
+
+/// The input for a query: a pattern whose variables are returned per match
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct QueryInput {
+    pub pattern: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+/// Value of a bound variable: the text of a string literal, or the atom in MeTTa syntax
+fn binding_value(expr: &Expr) -> String {
+    match Namespace::unwrap(expr) {
+        Expr::Str(s) => unescape(s),
+        expr => expr.to_string(),
+    }
+}
+
+/// Matches `<pattern>` against the `<path..>` space and returns one object per match, mapping
+/// the names of the pattern's variables, without `$`, to their values. `offset` matches are
+/// skipped and at most `limit` are returned.
+#[post("/spaces/query/<path..>", data = "<query_input>")]
+pub async fn query(
+    token: Token,
+    path: PathBuf,
+    query_input: Json<QueryInput>,
+) -> Result<Json<Vec<Map<String, Value>>>, Custom<String>> {
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_read {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    let pattern = parse(&query_input.pattern)
+        .map_err(|e| Custom(Status::BadRequest, format!("invalid_pattern: {e}")))?;
+    let variables = pattern.variables();
+
+    // One atom per match holding the variables in order, MORK only exports what templates build
+    let template = std::iter::once("bindings".to_string())
+        .chain(variables.iter().map(|v| format!("${v}")))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut request = ExportRequest::new()
+        .namespace(path)
+        .pattern(query_input.pattern.clone())
+        .template(format!("({template})"))
+        .format(ExportFormat::Metta);
+    if let Some(limit) = query_input.limit {
+        // saturating, so a huge offset asks for everything rather than overflowing
+        request = request.max_write(limit.saturating_add(query_input.offset));
+    }
+
+    let data = MorkApiClient::new()
+        .dispatch(request)
+        .await
+        .map_err(|e| Custom(e, "Failed to contact backend".to_string()))?;
+    let matches = parse_all(&data).map_err(|e| {
+        Custom(
+            Status::InternalServerError,
+            format!("invalid_response: {e}"),
+        )
+    })?;
+
+    let rows = matches
+        .iter()
+        .filter_map(|m| m.children())
+        .skip(query_input.offset)
+        .take(query_input.limit.unwrap_or(usize::MAX))
+        .map(|values| {
+            variables
+                .iter()
+                .zip(values.iter().skip(1))
+                .map(|(variable, value)| (variable.clone(), Value::String(binding_value(value))))
+                .collect()
+        })
+        .collect();
+
+    Ok(Json(rows))
+}
+
+/// A fresh namespace for intermediate results, outside of what [`namespace_to_path`] accepts
+/// so that it cannot collide with a client namespace. Callers clear it when done.
+pub(crate) fn scratch_namespace() -> PathBuf {
+    PathBuf::from("_scratch").join(Uuid::new_v4().simple().to_string())
+}
+
+/// Runs `details` on the `path` space with the templates written to a scratch namespace
+/// instead, and returns everything that ended up there. The scratch namespace is cleared
+/// afterwards, whether or not the transform succeeded.
+async fn transform_into_scratch(
+    mork_api_client: &MorkApiClient,
+    path: PathBuf,
+    details: TransformDetails,
+) -> Result<String, Status> {
+    let scratch = scratch_namespace();
+    let transform = TransformRequest::new()
+        .namespace(path)
+        .target_namespace(scratch.clone())
+        .transform_input(details);
+
+    let result = match mork_api_client.dispatch(transform).await {
+        Ok(_) => {
+            let request = ExportRequest::new()
+                .namespace(scratch.clone())
+                .pattern("$x".to_string())
+                .template("$x".to_string())
+                .format(ExportFormat::Metta);
+            mork_api_client.dispatch(request).await
         }
         Err(e) => Err(e),
+    };
+
+    let clear = ClearRequest::new()
+        .namespace(scratch.clone())
+        .expr("$x".to_string());
+    if let Err(e) = mork_api_client.dispatch(clear).await {
+        eprintln!(
+            "Failed to clear scratch namespace {}: {e}",
+            scratch.display()
+        );
+    }
+
+    result
+}
+
+/// Runs a SPARQL `SELECT` query against the `<path..>` space, see
+/// [`crate::routes::translations::SparqlTranslation`]. Queries with several triple patterns are
+/// joined in a scratch namespace first.
+#[post("/spaces/sparql/<path..>", data = "<query>")]
+pub async fn sparql(
+    token: Token,
+    path: PathBuf,
+    limiter: &State<TranslationLimiter>,
+    query: TempFile<'_>,
+) -> Result<Json<String>, Custom<String>> {
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_read {
+        return Err(Custom(Status::Unauthorized, "Unauthorized".to_string()));
+    }
+
+    let translation = {
+        // read-only tokens may query, but the translation counts like any other
+        let _permit = admit_input(&token, query.len(), limiter).map_err(translation_error)?;
+        sparql_to_mm2(query).await.map_err(translation_error)?
+    };
+
+    let mork_api_client = MorkApiClient::new();
+    let backend_error = |e: Status| {
+        Custom(
+            Status::InternalServerError,
+            format!("Failed to contact backend: {e}"),
+        )
+    };
+
+    if let [pattern] = translation.patterns.as_slice() {
+        let request = ExportRequest::new()
+            .namespace(path)
+            .pattern(pattern.clone())
+            .template(translation.template)
+            .format(ExportFormat::Metta);
+
+        return mork_api_client
+            .dispatch(request)
+            .await
+            .map(Json)
+            .map_err(backend_error);
     }
+
+    let join = TransformDetails::new()
+        .patterns(translation.patterns)
+        .templates(vec![translation.template]);
+    transform_into_scratch(&mork_api_client, path, join)
+        .await
+        .map(Json)
+        .map_err(backend_error)
+}
+
+/// Flattens a translation failure into the `<code>: <message>` bodies of the space routes
+fn translation_error(failure: TranslationFailure) -> Custom<String> {
+    let Custom(status, Json(error)) = failure;
+    Custom(status, format!("{}: {}", error.error, error.message))
 }
 
 #[post("/spaces/clear/<path..>?<expr>")]
-pub async fn clear(token: Token, path: PathBuf, expr: String) -> Result<Json<bool>, Status> {
+pub async fn clear(
+    token: Token,
+    path: PathBuf,
+    expr: String,
+    events: &State<EventBus>,
+) -> Result<Json<bool>, Status> {
     let token_namespace = token.namespace.strip_prefix("/").unwrap();
     if !path.starts_with(token_namespace) || !token.permission_write {
         return Err(Status::Unauthorized);
     }
 
     let mork_api_client = MorkApiClient::new();
-    let request = ClearRequest::new().namespace(path).expr(expr);
+    let request = ClearRequest::new().namespace(path.clone()).expr(expr);
 
-    match mork_api_client.dispatch(request).await {
+    let outcome = mork_api_client.dispatch(request).await;
+    publish(events, Operation::Clear, &path, &token, &outcome);
+
+    match outcome {
         Ok(_) => Ok(Json(true)),
         Err(e) => Err(e),
     }
 }
+
+/// Whether the token with `token_id` may still read `path`, reloaded as it may have been
+/// changed or deleted since the request. Blocks on the database, see [`subscribe`].
+fn may_still_read(token_id: i32, path: &Path) -> bool {
+    let token = tokens::table
+        .select(Token::as_select())
+        .filter(tokens::id.eq(token_id))
+        .get_result(&mut establish_connection());
+
+    match token {
+        Ok(token) => {
+            path.starts_with(token.namespace.strip_prefix("/").unwrap()) && token.permission_read
+        }
+        Err(_) => false,
+    }
+}
+
+/// Streams the successful uploads, imports, transforms and clears on the `<path..>` space and
+/// the spaces below it as server-sent events, named after the operation and holding the
+/// [`NamespaceEvent`] as JSON. The read permission of the token is checked again every
+/// `METTA_KG_SUBSCRIBE_REVALIDATE_SECONDS` (default 30); once it is gone, a `revoked` event
+/// ends the stream. A `lagged` event tells how many events a slow client missed.
+#[get("/spaces/subscribe/<path..>")]
+pub fn subscribe(
+    token: Token,
+    path: PathBuf,
+    events: &State<EventBus>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    let namespace = path_to_namespace(&path);
+    let mut receiver = events.subscribe();
+    let period = Duration::from_secs(env_limit("METTA_KG_SUBSCRIBE_REVALIDATE_SECONDS", 30).max(1));
+    let mut revalidate = interval_at(Instant::now() + period, period);
+
+    Ok(EventStream! {
+        loop {
+            select! {
+                received = receiver.recv() => match received {
+                    Ok(event) if event.success && event.namespace.starts_with(&namespace) => {
+                        yield Event::json(&event).event(event.operation.name());
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        yield Event::data(missed.to_string()).event("lagged");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = revalidate.tick() => {
+                    // the lookup blocks, so it runs off the async runtime
+                    let (token_id, path) = (token.id, path.clone());
+                    let may_read = spawn_blocking(move || may_still_read(token_id, &path))
+                        .await
+                        .unwrap_or(false);
+                    if !may_read {
+                        yield Event::data(format!("no read permission on {namespace}")).event("revoked");
+                        break;
+                    }
+                }
+                _ = &mut shutdown => break,
+            }
+        }
+    })
+}
@@ -1,6 +1,6 @@
 use chrono::Utc;
 use diesel::sql_types::Integer;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
 use regex::Regex;
 use rocket::http::Status;
 use rocket::serde::json::Json;
@@ -9,14 +9,11 @@ use uuid::Uuid;
 
 use crate::{db::establish_connection, model::Token, model::TokenInsert};
 
-#[get("/tokens")]
-pub fn get_all(token: Token) -> Result<Json<Vec<Token>>, Status> {
-    let conn = &mut establish_connection();
-
-    // get all tokens recursively
+/// `token` and the tokens created below it, directly or through other tokens
+pub(crate) fn token_tree(token: &Token, conn: &mut PgConnection) -> QueryResult<Vec<Token>> {
     // TODO: find a better way to do this
     // TODO: verify that this sanitizes inputs (token.id is not user input, but still)
-    let results = diesel::sql_query(
+    diesel::sql_query(
         "WITH RECURSIVE rectree AS (
         SELECT * 
             FROM tokens 
@@ -29,7 +26,15 @@ pub fn get_all(token: Token) -> Result<Json<Vec<Token>>, Status> {
         ) SELECT * FROM rectree;",
     )
     .bind::<Integer, _>(token.id)
-    .get_results::<Token>(conn);
+    .get_results::<Token>(conn)
+}
+
+#[get("/tokens")]
+pub fn get_all(token: Token) -> Result<Json<Vec<Token>>, Status> {
+    let conn = &mut establish_connection();
+
+    // get all tokens recursively
+    let results = token_tree(&token, conn);
 
     /*
     let results = tokens
@@ -1,11 +1,208 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, RunQueryDsl};
 use rocket::form::{FromForm, FromFormField};
 use rocket::fs::TempFile;
 use rocket::http::Status;
-use rocket::post;
-use rocket::serde::json::Json;
+use rocket::response::status::Custom;
+use rocket::serde::json::{serde_json, Json};
+use rocket::{post, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::process::Command;
-use uuid::Uuid;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::db::establish_connection;
+use crate::events::{EventBus, Operation};
+use crate::metta::{parse, parse_all};
+use crate::model::Token;
+use crate::mork_api::{MorkApiClient, UploadRequest};
+use crate::routes::spaces::{event, namespace_to_path, path_to_namespace};
+use crate::schema::translation_usage;
+use crate::translation_runner::{RunError, TranslationDiagnostics, TranslationRunner};
+
+/// Body of every failed translation request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TranslationError {
+    /// Stable, machine readable code
+    pub error: String,
+    pub message: String,
+    /// Details about the translation subprocess, when it ran and failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<TranslationDiagnostics>,
+}
+
+pub(crate) type TranslationFailure = Custom<Json<TranslationError>>;
+
+/// What a translation reads: an uploaded file, or text such as the data of a pipeline step
+pub(crate) enum TranslationInput<'a> {
+    File(Box<TempFile<'a>>),
+    Text(String),
+}
+
+fn failure(status: Status, error: &str, message: impl Into<String>) -> TranslationFailure {
+    Custom(
+        status,
+        Json(TranslationError {
+            error: error.to_string(),
+            message: message.into(),
+            diagnostics: None,
+        }),
+    )
+}
+
+fn diagnosed(
+    status: Status,
+    error: &str,
+    message: impl Into<String>,
+    diagnostics: TranslationDiagnostics,
+) -> TranslationFailure {
+    let mut failure = failure(status, error, message);
+    failure.1.diagnostics = Some(diagnostics);
+    failure
+}
+
+pub(crate) fn env_limit(key: &str, default: u64) -> u64 {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Number of translations currently running per token
+#[derive(Default, Clone)]
+pub struct TranslationLimiter {
+    active: Arc<Mutex<HashMap<i32, usize>>>,
+}
+
+/// Held for the duration of a translation, frees its slot when dropped
+pub struct TranslationPermit {
+    active: Arc<Mutex<HashMap<i32, usize>>>,
+    token_id: i32,
+}
+
+impl TranslationLimiter {
+    /// Takes one of the `max` slots of `token_id`, if one is free
+    pub fn acquire(&self, token_id: i32, max: usize) -> Option<TranslationPermit> {
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(token_id).or_insert(0);
+
+        if *count >= max {
+            return None;
+        }
+
+        *count += 1;
+
+        Some(TranslationPermit {
+            active: self.active.clone(),
+            token_id,
+        })
+    }
+}
+
+impl Drop for TranslationPermit {
+    fn drop(&mut self) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.token_id) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.token_id);
+            }
+        }
+    }
+}
+
+/// Counts one translation against today's quota of `token`, returning the new count
+fn count_usage(token: &Token) -> Result<i32, diesel::result::Error> {
+    let today = Utc::now().date_naive();
+
+    diesel::insert_into(translation_usage::table)
+        .values((
+            translation_usage::token.eq(token.id),
+            translation_usage::day.eq(today),
+            translation_usage::count.eq(1),
+        ))
+        .on_conflict((translation_usage::token, translation_usage::day))
+        .do_update()
+        .set(translation_usage::count.eq(translation_usage::count + 1))
+        .returning(translation_usage::count)
+        .get_result(&mut establish_connection())
+}
+
+/// Takes one of the `METTA_KG_TRANSLATION_CONCURRENCY` translation slots of `token`
+pub(crate) fn reserve(
+    token: &Token,
+    limiter: &TranslationLimiter,
+) -> Result<TranslationPermit, TranslationFailure> {
+    let concurrency = env_limit("METTA_KG_TRANSLATION_CONCURRENCY", 2) as usize;
+
+    limiter.acquire(token.id, concurrency).ok_or_else(|| {
+        failure(
+            Status::TooManyRequests,
+            "too_many_concurrent",
+            format!("At most {concurrency} translations may run at once per token"),
+        )
+    })
+}
+
+/// Checks everything that has to hold before a translation may start: the token needs write
+/// permission, and the input has to pass [`admit_input`]
+pub(crate) fn admit(
+    token: &Token,
+    len: u64,
+    limiter: &TranslationLimiter,
+) -> Result<TranslationPermit, TranslationFailure> {
+    if !token.permission_write {
+        return Err(failure(
+            Status::Unauthorized,
+            "unauthorized",
+            "Translations require a token with write permission",
+        ));
+    }
+
+    admit_input(token, len, limiter)
+}
+
+/// Checks the limits on translating `len` bytes, which apply per token:
+///
+/// - `METTA_KG_TRANSLATION_MAX_BYTES`: size of the input (default 20 MiB)
+/// - `METTA_KG_TRANSLATION_CONCURRENCY`: translations running at once (default 2)
+/// - `METTA_KG_TRANSLATION_DAILY_QUOTA`: translations per UTC day (default 1000)
+pub(crate) fn admit_input(
+    token: &Token,
+    len: u64,
+    limiter: &TranslationLimiter,
+) -> Result<TranslationPermit, TranslationFailure> {
+    let max_bytes = env_limit("METTA_KG_TRANSLATION_MAX_BYTES", 20 * 1024 * 1024);
+    if len > max_bytes {
+        return Err(failure(
+            Status::PayloadTooLarge,
+            "too_large",
+            format!("File exceeds the limit of {max_bytes} bytes"),
+        ));
+    }
+
+    let permit = reserve(token, limiter)?;
+
+    let quota = env_limit("METTA_KG_TRANSLATION_DAILY_QUOTA", 1000);
+    match count_usage(token) {
+        Ok(count) if count as u64 > quota => Err(failure(
+            Status::TooManyRequests,
+            "quota_exceeded",
+            format!("Daily quota of {quota} translations exceeded"),
+        )),
+        Ok(_) => Ok(permit),
+        Err(e) => {
+            eprintln!("Failed to count translation usage: {e}");
+            Err(failure(
+                Status::InternalServerError,
+                "internal",
+                "Failed to count translation usage",
+            ))
+        }
+    }
+}
 
 #[derive(FromFormField, Copy, Clone)]
 pub enum CSVParseDirection {
@@ -15,10 +212,124 @@ pub enum CSVParseDirection {
     CellLabeled = 4,
 }
 
+/// Shape of row based translations of a CSV file with a header, see the translations README
+#[derive(FromFormField, Copy, Clone)]
+pub enum CSVRowVariant {
+    Naive,
+    Struct,
+    Field,
+    Functional,
+}
+
+/// How the values of a CSV column are written
+#[derive(FromFormField, Copy, Clone)]
+pub enum CSVColumnType {
+    /// Quoted string literal, the default
+    String,
+    /// Plain number, rejected if the value is not one
+    Number,
+    /// Plain symbol, rejected if the value contains whitespace or parentheses
+    Symbol,
+}
+
 #[derive(FromForm, Clone)]
 pub struct CSVParserParameters {
     pub direction: CSVParseDirection,
     pub delimiter: String,
+    /// Whether the first row labels the columns
+    #[field(default = false)]
+    pub header: bool,
+    /// Only for `direction=Row` with a header, `Naive` by default
+    pub variant: Option<CSVRowVariant>,
+    pub quote: Option<String>,
+    pub escape: Option<String>,
+    /// Columns to translate by label or 0-based index, e.g. `columns=Name&columns=2`
+    pub columns: Vec<String>,
+    /// Per column value types by label or 0-based index, e.g. `types[Age]=Number`
+    pub types: HashMap<String, CSVColumnType>,
+}
+
+impl CSVParserParameters {
+    /// Validates the parameters and turns them into arguments for `csv_to_metta_run.py`
+    fn arguments(self) -> Result<Vec<String>, TranslationFailure> {
+        let invalid = |message: &str| {
+            Err(failure(
+                Status::BadRequest,
+                "invalid_parameters",
+                message.to_string(),
+            ))
+        };
+
+        for (name, value) in [
+            ("delimiter", Some(&self.delimiter)),
+            ("quote", self.quote.as_ref()),
+            ("escape", self.escape.as_ref()),
+        ] {
+            if value.is_some_and(|v| v.chars().count() != 1) {
+                return invalid(&format!("{name} must be a single character"));
+            }
+        }
+
+        let is_row = matches!(self.direction, CSVParseDirection::Row);
+        let is_cell = matches!(
+            self.direction,
+            CSVParseDirection::CellUnlabeled | CSVParseDirection::CellLabeled
+        );
+
+        if self.variant.is_some() && !(is_row && self.header) {
+            return invalid("variant only applies to row based translations with a header");
+        }
+
+        if self.header && is_cell {
+            return invalid("cell based translations do not take a header");
+        }
+
+        let mut arguments = vec![(self.direction as u8).to_string(), self.delimiter];
+
+        if self.header {
+            arguments.push("--header".to_string());
+        }
+
+        if let Some(variant) = self.variant {
+            let variant = match variant {
+                CSVRowVariant::Naive => "naive",
+                CSVRowVariant::Struct => "struct",
+                CSVRowVariant::Field => "field",
+                CSVRowVariant::Functional => "functional",
+            };
+            arguments.push(format!("--variant={variant}"));
+        }
+
+        if let Some(quote) = self.quote {
+            arguments.push(format!("--quote={quote}"));
+        }
+
+        if let Some(escape) = self.escape {
+            arguments.push(format!("--escape={escape}"));
+        }
+
+        if !self.columns.is_empty() {
+            arguments.push(format!("--columns={}", serde_json::json!(self.columns)));
+        }
+
+        if !self.types.is_empty() {
+            let types: HashMap<String, &str> = self
+                .types
+                .into_iter()
+                .map(|(column, column_type)| {
+                    let column_type = match column_type {
+                        CSVColumnType::String => "string",
+                        CSVColumnType::Number => "number",
+                        CSVColumnType::Symbol => "symbol",
+                    };
+                    (column, column_type)
+                })
+                .collect();
+            arguments.push(format!("--types={}", serde_json::json!(types)));
+        }
+
+        Ok(arguments)
+    }
 }
 
 #[derive(FromForm, Clone)]
@@ -42,104 +353,536 @@ pub struct JSONLDParserParameters {
     pub dummy: String,
 }
 
+#[derive(FromForm, Clone)]
+pub struct TurtleParserParameters {
+    // TODO: figure out how to deal with this normally empty struct
+    #[allow(dead_code)]
+    pub dummy: String,
+}
+
+#[derive(FromForm, Clone)]
+pub struct RDFXMLParserParameters {
+    // TODO: figure out how to deal with this normally empty struct
+    #[allow(dead_code)]
+    pub dummy: String,
+}
+
+/// How JSON arrays map to atoms
+#[derive(FromFormField, Copy, Clone)]
+pub enum JSONArrayMode {
+    /// Items carry their index: `{"a": ["x"]}` becomes `(a 0 "x")`
+    Indexed,
+    /// Items repeat their key: `{"a": ["x"]}` becomes `(a "x")`
+    Repeated,
+}
+
+/// How nested objects map to atoms
+#[derive(FromFormField, Copy, Clone)]
+pub enum JSONNestingMode {
+    /// `{"a": {"b": 1}}` becomes `(a (b 1))`
+    Nested,
+    /// `{"a": {"b": 1}}` becomes `((a b) 1)`
+    Path,
+}
+
+#[derive(FromForm, Clone)]
+pub struct JSONParserParameters {
+    #[field(default = JSONArrayMode::Indexed)]
+    pub arrays: JSONArrayMode,
+    #[field(default = JSONNestingMode::Nested)]
+    pub nesting: JSONNestingMode,
+    /// Dotted key path to the part of the document to translate, e.g. `data.items`
+    pub root: Option<String>,
+    /// Input is JSON Lines, translated as an array of records
+    #[field(default = false)]
+    pub lines: bool,
+}
+
 #[derive(FromForm, Clone)]
 pub struct ParserParameters {
     csv_parameters: Option<CSVParserParameters>,
     nt_parameters: Option<NTParserParameters>,
     jsonld_parameters: Option<JSONLDParserParameters>,
     n3_parameters: Option<N3ParserParameters>,
+    json_parameters: Option<JSONParserParameters>,
+    turtle_parameters: Option<TurtleParserParameters>,
+    rdfxml_parameters: Option<RDFXMLParserParameters>,
 }
 
-pub async fn create(
+pub(crate) async fn create(
     ext: &str,
-    mut file: TempFile<'_>,
+    input: TranslationInput<'_>,
     parse_parameters: ParserParameters,
-) -> Result<String, Status> {
-    let id = Uuid::new_v4();
-
-    let path = format!("temp/translations-{id}");
-
-    let path_with_extension = format!("{path}.{ext}");
-
-    let result = file.persist_to(&path_with_extension).await;
-
-    match result {
-        Ok(_) => (),
-        Err(err) => println!("{err}"),
-    }
-
-    let status = match parse_parameters {
+) -> Result<String, TranslationFailure> {
+    let (script, args) = match parse_parameters {
         ParserParameters {
             csv_parameters: Some(parameters),
             nt_parameters: None,
             jsonld_parameters: None,
             n3_parameters: None,
-        } => {
-            let direction = (parameters.direction as u8).to_string();
-            let delimiter = parameters.delimiter;
-
-            Command::new("./venv/bin/python")
-                .arg("translations/src/csv_to_metta_run.py")
-                .arg(&path)
-                .arg(&direction)
-                .arg(&delimiter)
-                .status()
-        }
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
+        } => (
+            "translations/src/csv_to_metta_run.py",
+            parameters.arguments()?,
+        ),
         ParserParameters {
             csv_parameters: None,
             nt_parameters: Some(_parameters),
             jsonld_parameters: None,
             n3_parameters: None,
-        } => Command::new("./venv/bin/python")
-            .arg("translations/src/nt_to_metta_run.py")
-            .arg(&path)
-            .status(),
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
+        } => ("translations/src/nt_to_metta_run.py", vec![]),
         ParserParameters {
             csv_parameters: None,
             nt_parameters: None,
             jsonld_parameters: Some(_parameters),
             n3_parameters: None,
-        } => Command::new("./venv/bin/python")
-            .arg("translations/src/jsonld_to_metta_run.py")
-            .arg(&path)
-            .status(),
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
+        } => ("translations/src/jsonld_to_metta_run.py", vec![]),
         ParserParameters {
             csv_parameters: None,
             nt_parameters: None,
             jsonld_parameters: None,
             n3_parameters: Some(_parameters),
-        } => Command::new("./venv/bin/python")
-            .arg("translations/src/n3_to_metta_run.py")
-            .arg(&path)
-            .status(),
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
+        } => ("translations/src/n3_to_metta_run.py", vec![]),
+        ParserParameters {
+            csv_parameters: None,
+            nt_parameters: None,
+            jsonld_parameters: None,
+            n3_parameters: None,
+            json_parameters: None,
+            turtle_parameters: Some(_parameters),
+            rdfxml_parameters: None,
+        } => (
+            "translations/src/rdf_to_metta_run.py",
+            vec![ext.to_string(), "turtle".to_string()],
+        ),
+        ParserParameters {
+            csv_parameters: None,
+            nt_parameters: None,
+            jsonld_parameters: None,
+            n3_parameters: None,
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: Some(_parameters),
+        } => (
+            "translations/src/rdf_to_metta_run.py",
+            vec![ext.to_string(), "xml".to_string()],
+        ),
+        ParserParameters {
+            csv_parameters: None,
+            nt_parameters: None,
+            jsonld_parameters: None,
+            n3_parameters: None,
+            json_parameters: Some(parameters),
+            turtle_parameters: None,
+            rdfxml_parameters: None,
+        } => {
+            let arrays = match parameters.arrays {
+                JSONArrayMode::Indexed => "indexed",
+                JSONArrayMode::Repeated => "repeated",
+            };
+            let nesting = match parameters.nesting {
+                JSONNestingMode::Nested => "nested",
+                JSONNestingMode::Path => "path",
+            };
+
+            (
+                "translations/src/json_to_metta_run.py",
+                vec![
+                    arrays.to_string(),
+                    nesting.to_string(),
+                    parameters.lines.to_string(),
+                    parameters.root.unwrap_or_default(),
+                ],
+            )
+        }
         _ => {
             println!("Parse failed");
-            return Err(Status::InternalServerError);
+            return Err(failure(
+                Status::InternalServerError,
+                "internal",
+                "Invalid parser parameters",
+            ));
         }
     };
 
-    // TODO: better error handling
-    match status {
-        Ok(_) => (),
-        Err(_) => {
-            return Err(Status::InternalServerError);
+    run_script(ext, input, script, args).await
+}
+
+/// Formats that translate without parser parameters, such as in pipeline steps. CSV and JSON
+/// take the defaults of their routes: CSV is comma separated, without header and row based.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextFormat {
+    Nt,
+    Jsonld,
+    N3,
+    Ttl,
+    Rdfxml,
+    Csv,
+    Json,
+}
+
+/// Translates `text` on behalf of `token`, under the same limits as the translation routes
+pub(crate) async fn translate_text(
+    token: &Token,
+    limiter: &TranslationLimiter,
+    format: TextFormat,
+    text: String,
+) -> Result<String, TranslationFailure> {
+    let _permit = admit(token, text.len() as u64, limiter)?;
+
+    let none = ParserParameters {
+        csv_parameters: None,
+        nt_parameters: None,
+        jsonld_parameters: None,
+        n3_parameters: None,
+        json_parameters: None,
+        turtle_parameters: None,
+        rdfxml_parameters: None,
+    };
+    let dummy = String::new;
+    let (ext, parse_parameters) = match format {
+        TextFormat::Nt => (
+            "nt",
+            ParserParameters {
+                nt_parameters: Some(NTParserParameters { dummy: dummy() }),
+                ..none
+            },
+        ),
+        TextFormat::Jsonld => (
+            "jsonld",
+            ParserParameters {
+                jsonld_parameters: Some(JSONLDParserParameters { dummy: dummy() }),
+                ..none
+            },
+        ),
+        TextFormat::N3 => (
+            "n3",
+            ParserParameters {
+                n3_parameters: Some(N3ParserParameters { dummy: dummy() }),
+                ..none
+            },
+        ),
+        TextFormat::Ttl => (
+            "ttl",
+            ParserParameters {
+                turtle_parameters: Some(TurtleParserParameters { dummy: dummy() }),
+                ..none
+            },
+        ),
+        TextFormat::Rdfxml => (
+            "rdf",
+            ParserParameters {
+                rdfxml_parameters: Some(RDFXMLParserParameters { dummy: dummy() }),
+                ..none
+            },
+        ),
+        TextFormat::Csv => (
+            "csv",
+            ParserParameters {
+                csv_parameters: Some(CSVParserParameters {
+                    direction: CSVParseDirection::Row,
+                    delimiter: ",".to_string(),
+                    header: false,
+                    variant: None,
+                    quote: None,
+                    escape: None,
+                    columns: vec![],
+                    types: HashMap::new(),
+                }),
+                ..none
+            },
+        ),
+        TextFormat::Json => (
+            "json",
+            ParserParameters {
+                json_parameters: Some(JSONParserParameters {
+                    arrays: JSONArrayMode::Indexed,
+                    nesting: JSONNestingMode::Nested,
+                    root: None,
+                    lines: false,
+                }),
+                ..none
+            },
+        ),
+    };
+
+    create(ext, TranslationInput::Text(text), parse_parameters).await
+}
+
+/// Runs a translation `script` on `input` in a fresh workspace and returns what it wrote. The
+/// script gets the workspace stem as its first argument, followed by `args`.
+async fn run_script(
+    ext: &str,
+    input: TranslationInput<'_>,
+    script: &str,
+    args: Vec<String>,
+) -> Result<String, TranslationFailure> {
+    let runner = TranslationRunner::from_env();
+
+    // Removed together with the input and output when it goes out of scope
+    let workspace = runner.workspace().map_err(|e| {
+        eprintln!("Failed to create translation workspace: {e}");
+        failure(
+            Status::InternalServerError,
+            "internal",
+            "Failed to create translation workspace",
+        )
+    })?;
+
+    let stored = match input {
+        TranslationInput::File(mut file) => file.persist_to(workspace.input(ext)).await,
+        TranslationInput::Text(text) => fs::write(workspace.input(ext), text),
+    };
+    stored.map_err(|e| {
+        eprintln!("Failed to store translation input: {e}");
+        failure(
+            Status::InternalServerError,
+            "internal",
+            "Failed to store translation input",
+        )
+    })?;
+
+    let mut script_args = vec![workspace.stem().to_string_lossy().to_string()];
+    script_args.extend(args);
+
+    match runner.run(script, &script_args).await {
+        Ok(()) => (),
+        Err(RunError::Spawn(e)) => {
+            eprintln!("Failed to start translation {script}: {e}");
+            return Err(failure(
+                Status::InternalServerError,
+                "internal",
+                "Failed to start translation",
+            ));
+        }
+        Err(RunError::TimedOut(diagnostics)) => {
+            return Err(diagnosed(
+                Status::GatewayTimeout,
+                "translation_timeout",
+                format!(
+                    "Translation did not finish within {} seconds",
+                    runner.timeout.as_secs()
+                ),
+                diagnostics,
+            ));
         }
+        Err(RunError::Failed(diagnostics)) => {
+            return Err(diagnosed(
+                Status::UnprocessableEntity,
+                "translation_failed",
+                "Translation exited with an error",
+                diagnostics,
+            ));
+        }
+    }
+
+    fs::read_to_string(workspace.output()).map_err(|_| {
+        failure(
+            Status::InternalServerError,
+            "translation_failed",
+            "Translation produced no output",
+        )
+    })
+}
+
+/// MM2 equivalent of a SPARQL `SELECT` query over triples in the atom convention of the
+/// N-Triples translation, e.g. `((uriref <iri>) (uriref <iri>) $o)`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SparqlTranslation {
+    /// One pattern per triple of the basic graph pattern, all of which must match
+    pub patterns: Vec<String>,
+    /// `(result (<var> $<var>) ...)` over the selected variables
+    pub template: String,
+    pub variables: Vec<String>,
+}
+
+/// Translates the SPARQL query in `file`, checking that the result is valid MM2
+pub(crate) async fn sparql_to_mm2(
+    file: TempFile<'_>,
+) -> Result<SparqlTranslation, TranslationFailure> {
+    let output = run_script(
+        "sparql",
+        TranslationInput::File(Box::new(file)),
+        "translations/src/sparql_to_mm2_run.py",
+        vec![],
+    )
+    .await?;
+
+    let invalid = |message: String| {
+        eprintln!("SPARQL translation produced invalid output: {message}");
+        failure(Status::InternalServerError, "invalid_output", message)
     };
 
-    let contents = fs::read_to_string(format!("{path}-output.metta"));
+    let translation: SparqlTranslation =
+        serde_json::from_str(&output).map_err(|e| invalid(e.to_string()))?;
+
+    if translation.patterns.is_empty() {
+        return Err(invalid("query has no triple patterns".to_string()));
+    }
 
-    match contents {
-        Ok(contents) => Ok(contents),
-        Err(_) => Err(Status::InternalServerError),
+    let mut bound = vec![];
+    for pattern in &translation.patterns {
+        bound.extend(
+            parse(pattern)
+                .map_err(|e| invalid(e.to_string()))?
+                .variables(),
+        );
     }
+
+    let template = parse(&translation.template).map_err(|e| invalid(e.to_string()))?;
+    if let Some(unbound) = template.variables().iter().find(|v| !bound.contains(v)) {
+        return Err(failure(
+            Status::UnprocessableEntity,
+            "unbound_variable",
+            format!("Selected variable ?{unbound} does not occur in the query"),
+        ));
+    }
+
+    Ok(translation)
+}
+
+/// Translates a SPARQL `SELECT` query into the MM2 patterns and template that run it, see
+/// [`SparqlTranslation`]. Only basic graph patterns are supported.
+#[post("/translations/sparql", data = "<file>")]
+pub async fn create_from_sparql(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    file: TempFile<'_>,
+) -> Result<Json<SparqlTranslation>, TranslationFailure> {
+    let _permit = admit(&token, file.len(), limiter)?;
+
+    sparql_to_mm2(file).await.map(Json)
 }
 
-#[post("/translations/csv?<parse_parameters..>", data = "<file>")]
+/// Result of a translation route: the MeTTa itself, or, when a target namespace was given,
+/// where it was loaded. Untagged so that the plain string response stays as it was.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum TranslationOutput {
+    Loaded { namespace: String, atoms: usize },
+    Metta(String),
+}
+
+/// Resolves the optional target namespace of a translation, which `token` must be able to
+/// write to
+fn target(token: &Token, namespace: Option<String>) -> Result<Option<PathBuf>, TranslationFailure> {
+    let Some(namespace) = namespace else {
+        return Ok(None);
+    };
+
+    let path = namespace_to_path(&namespace).ok_or_else(|| {
+        failure(
+            Status::BadRequest,
+            "invalid_namespace",
+            format!("'{namespace}' is not a valid namespace"),
+        )
+    })?;
+
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_write {
+        return Err(failure(
+            Status::Unauthorized,
+            "unauthorized",
+            format!("Token may not write to {namespace}"),
+        ));
+    }
+
+    Ok(Some(path))
+}
+
+/// Uploads translated `metta` into `path` server side on behalf of `token`, returning the number
+/// of atoms loaded. The upload is published like one to `/spaces/upload`.
+pub(crate) async fn load(
+    token: &Token,
+    path: PathBuf,
+    metta: String,
+    events: &EventBus,
+) -> Result<usize, TranslationFailure> {
+    let atoms = parse_all(&metta).map_err(|e| {
+        eprintln!("Translation produced invalid MeTTa: {e}");
+        failure(
+            Status::InternalServerError,
+            "invalid_output",
+            format!("Translation produced invalid MeTTa: {e}"),
+        )
+    })?;
+
+    let size = metta.len() as u64;
+    let request = UploadRequest::new()
+        .namespace(path.clone())
+        .pattern("$x".to_string())
+        .template("$x".to_string())
+        .data(metta);
+
+    let outcome = MorkApiClient::new().dispatch(request).await;
+    events.publish(event(Operation::Upload, &path, token, &outcome).size(size));
+    match outcome {
+        Ok(_) => Ok(atoms.len()),
+        Err(e) => Err(failure(
+            Status::InternalServerError,
+            "backend",
+            format!("Failed to contact backend: {e}"),
+        )),
+    }
+}
+
+/// Shared body of the translation routes
+async fn translate(
+    token: &Token,
+    limiter: &TranslationLimiter,
+    events: &EventBus,
+    namespace: Option<String>,
+    ext: &str,
+    file: TempFile<'_>,
+    parse_parameters: ParserParameters,
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    let target = target(token, namespace)?;
+    let _permit = admit(token, file.len(), limiter)?;
+
+    let metta = create(
+        ext,
+        TranslationInput::File(Box::new(file)),
+        parse_parameters,
+    )
+    .await?;
+
+    match target {
+        None => Ok(Json(TranslationOutput::Metta(metta))),
+        Some(path) => {
+            let atoms = load(token, path.clone(), metta, events).await?;
+            Ok(Json(TranslationOutput::Loaded {
+                namespace: path_to_namespace(&path),
+                atoms,
+            }))
+        }
+    }
+}
+
+#[post("/translations/csv?<namespace>&<parse_parameters..>", data = "<file>")]
 pub async fn create_from_csv(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    events: &State<EventBus>,
     file: TempFile<'_>,
+    namespace: Option<String>,
     parse_parameters: CSVParserParameters,
-) -> Result<Json<String>, Status> {
-    create(
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    translate(
+        &token,
+        limiter,
+        events,
+        namespace,
         "csv",
         file,
         ParserParameters {
@@ -147,18 +890,28 @@ pub async fn create_from_csv(
             nt_parameters: None,
             jsonld_parameters: None,
             n3_parameters: None,
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
         },
     )
     .await
-    .map(Json)
 }
 
-#[post("/translations/nt?<parse_parameters..>", data = "<file>")]
+#[post("/translations/nt?<namespace>&<parse_parameters..>", data = "<file>")]
 pub async fn create_from_nt(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    events: &State<EventBus>,
     file: TempFile<'_>,
+    namespace: Option<String>,
     parse_parameters: NTParserParameters,
-) -> Result<Json<String>, Status> {
-    create(
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    translate(
+        &token,
+        limiter,
+        events,
+        namespace,
         "nt",
         file,
         ParserParameters {
@@ -166,18 +919,31 @@ pub async fn create_from_nt(
             nt_parameters: Some(parse_parameters),
             jsonld_parameters: None,
             n3_parameters: None,
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
         },
     )
     .await
-    .map(Json)
 }
 
-#[post("/translations/jsonld?<parse_parameters..>", data = "<file>")]
+#[post(
+    "/translations/jsonld?<namespace>&<parse_parameters..>",
+    data = "<file>"
+)]
 pub async fn create_from_jsonld(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    events: &State<EventBus>,
     file: TempFile<'_>,
+    namespace: Option<String>,
     parse_parameters: JSONLDParserParameters,
-) -> Result<Json<String>, Status> {
-    create(
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    translate(
+        &token,
+        limiter,
+        events,
+        namespace,
         "jsonld",
         file,
         ParserParameters {
@@ -185,18 +951,28 @@ pub async fn create_from_jsonld(
             nt_parameters: None,
             jsonld_parameters: Some(parse_parameters),
             n3_parameters: None,
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
         },
     )
     .await
-    .map(Json)
 }
 
-#[post("/translations/n3?<parse_parameters..>", data = "<file>")]
+#[post("/translations/n3?<namespace>&<parse_parameters..>", data = "<file>")]
 pub async fn create_from_n3(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    events: &State<EventBus>,
     file: TempFile<'_>,
+    namespace: Option<String>,
     parse_parameters: N3ParserParameters,
-) -> Result<Json<String>, Status> {
-    create(
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    translate(
+        &token,
+        limiter,
+        events,
+        namespace,
         "n3",
         file,
         ParserParameters {
@@ -204,8 +980,100 @@ pub async fn create_from_n3(
             nt_parameters: None,
             jsonld_parameters: None,
             n3_parameters: Some(parse_parameters),
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: None,
+        },
+    )
+    .await
+}
+
+#[post("/translations/json?<namespace>&<parse_parameters..>", data = "<file>")]
+pub async fn create_from_json(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    events: &State<EventBus>,
+    file: TempFile<'_>,
+    namespace: Option<String>,
+    parse_parameters: JSONParserParameters,
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    translate(
+        &token,
+        limiter,
+        events,
+        namespace,
+        "json",
+        file,
+        ParserParameters {
+            csv_parameters: None,
+            nt_parameters: None,
+            jsonld_parameters: None,
+            n3_parameters: None,
+            json_parameters: Some(parse_parameters),
+            turtle_parameters: None,
+            rdfxml_parameters: None,
+        },
+    )
+    .await
+}
+
+#[post("/translations/ttl?<namespace>&<parse_parameters..>", data = "<file>")]
+pub async fn create_from_turtle(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    events: &State<EventBus>,
+    file: TempFile<'_>,
+    namespace: Option<String>,
+    parse_parameters: TurtleParserParameters,
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    translate(
+        &token,
+        limiter,
+        events,
+        namespace,
+        "ttl",
+        file,
+        ParserParameters {
+            csv_parameters: None,
+            nt_parameters: None,
+            jsonld_parameters: None,
+            n3_parameters: None,
+            json_parameters: None,
+            turtle_parameters: Some(parse_parameters),
+            rdfxml_parameters: None,
+        },
+    )
+    .await
+}
+
+#[post(
+    "/translations/rdfxml?<namespace>&<parse_parameters..>",
+    data = "<file>"
+)]
+pub async fn create_from_rdfxml(
+    token: Token,
+    limiter: &State<TranslationLimiter>,
+    events: &State<EventBus>,
+    file: TempFile<'_>,
+    namespace: Option<String>,
+    parse_parameters: RDFXMLParserParameters,
+) -> Result<Json<TranslationOutput>, TranslationFailure> {
+    translate(
+        &token,
+        limiter,
+        events,
+        namespace,
+        "rdf",
+        file,
+        ParserParameters {
+            csv_parameters: None,
+            nt_parameters: None,
+            jsonld_parameters: None,
+            n3_parameters: None,
+            json_parameters: None,
+            turtle_parameters: None,
+            rdfxml_parameters: Some(parse_parameters),
         },
     )
     .await
-    .map(Json)
 }
@@ -0,0 +1,164 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::db::establish_connection;
+use crate::metta::validate_mm2;
+use crate::model::{Token, Trigger, TriggerInsert};
+use crate::routes::spaces::{authorize_namespace, path_to_namespace};
+use crate::schema::triggers;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct TriggerInput {
+    /// Namespace whose uploads, imports and transforms run the trigger
+    pub namespace: String,
+    /// Where the templates are written, which may not be `namespace` itself
+    pub target_namespace: String,
+    #[serde(default)]
+    pub description: String,
+    pub patterns: Vec<String>,
+    pub templates: Vec<String>,
+}
+
+/// The namespaces through which writes to `target` run the triggers on `namespace`, following
+/// the existing triggers as `(namespace, target_namespace)` edges, if they do
+fn find_loop(edges: &[(String, String)], namespace: &str, target: &str) -> Option<Vec<String>> {
+    let mut seen = HashSet::new();
+    let mut paths = vec![vec![target.to_string()]];
+
+    while let Some(path) = paths.pop() {
+        let current = path.last().unwrap();
+        if current == namespace {
+            return Some(path);
+        }
+        if !seen.insert(current.clone()) {
+            continue;
+        }
+        for (_, next) in edges.iter().filter(|(from, _)| from == current) {
+            let mut longer = path.clone();
+            longer.push(next.clone());
+            paths.push(longer);
+        }
+    }
+
+    None
+}
+
+/// Registers a transform that runs after every successful upload, import or transform on
+/// `namespace`, e.g. to keep an index in another namespace up to date. Needs `read`
+/// permission on the namespace and `write` permission on the target. Triggers that would run
+/// themselves again, directly or through other triggers, are refused.
+#[post("/triggers", data = "<input>")]
+pub fn create(token: Token, input: Json<TriggerInput>) -> Result<Json<Trigger>, Custom<String>> {
+    let source = authorize_namespace(&token, &input.namespace, false)?;
+    let target = authorize_namespace(&token, &input.target_namespace, true)?;
+
+    if input.patterns.is_empty() || input.templates.is_empty() {
+        return Err(Custom(
+            Status::BadRequest,
+            "invalid_trigger: patterns and templates must not be empty".to_string(),
+        ));
+    }
+    let conjunction = format!("(, {})", input.patterns.join(" "));
+    for template in &input.templates {
+        validate_mm2(&conjunction, template)
+            .map_err(|e| Custom(Status::BadRequest, format!("invalid_trigger: {e}")))?;
+    }
+
+    let namespace = path_to_namespace(&source);
+    let target_namespace = path_to_namespace(&target);
+
+    let conn = &mut establish_connection();
+    let edges = triggers::table
+        .select((triggers::namespace, triggers::target_namespace))
+        .load::<(String, String)>(conn)
+        .map_err(|_| {
+            Custom(
+                Status::InternalServerError,
+                "Failed to load triggers".to_string(),
+            )
+        })?;
+    if let Some(path) = find_loop(&edges, &namespace, &target_namespace) {
+        return Err(Custom(
+            Status::BadRequest,
+            format!(
+                "invalid_trigger: what it writes would run it again, through {}",
+                path.join(" -> ")
+            ),
+        ));
+    }
+
+    let to_insert = TriggerInsert {
+        token: token.id,
+        namespace,
+        target_namespace,
+        description: input.description.clone(),
+        patterns: input.patterns.clone(),
+        templates: input.templates.clone(),
+        creation_timestamp: Utc::now().naive_utc(),
+    };
+
+    diesel::insert_into(triggers::table)
+        .values(&to_insert)
+        .get_result(conn)
+        .map(Json)
+        .map_err(|_| {
+            Custom(
+                Status::InternalServerError,
+                "Failed to store trigger".to_string(),
+            )
+        })
+}
+
+/// All triggers on namespaces of the token, with the outcome of their last run
+#[get("/triggers")]
+pub fn get_all(token: Token) -> Result<Json<Vec<Trigger>>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    let results = triggers::table
+        .select(Trigger::as_select())
+        .order(triggers::id.asc())
+        .load(&mut establish_connection());
+
+    match results {
+        Ok(results) => Ok(Json(
+            results
+                .into_iter()
+                .filter(|trigger| trigger.namespace.starts_with(&token.namespace))
+                .collect(),
+        )),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+#[delete("/triggers/<trigger_id>")]
+pub fn delete(token: Token, trigger_id: i32) -> Status {
+    if !token.permission_write {
+        return Status::Unauthorized;
+    }
+
+    let trigger = triggers::table
+        .select(Trigger::as_select())
+        .filter(triggers::id.eq(trigger_id))
+        .get_result(&mut establish_connection());
+
+    match trigger {
+        Ok(trigger) if trigger.namespace.starts_with(&token.namespace) => (),
+        _ => return Status::NotFound,
+    }
+
+    let result = diesel::delete(triggers::table.filter(triggers::id.eq(trigger_id)))
+        .execute(&mut establish_connection());
+
+    match result {
+        Ok(_) => Status::Ok,
+        Err(_) => Status::NotFound,
+    }
+}
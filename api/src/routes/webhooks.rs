@@ -0,0 +1,126 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::db::establish_connection;
+use crate::import_policy::ImportPolicy;
+use crate::model::{Token, Webhook, WebhookInsert};
+use crate::routes::spaces::{authorize_namespace, path_to_namespace};
+use crate::schema::webhooks;
+use crate::webhook_runner::{master_secret, webhook_secret};
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct WebhookInput {
+    /// Changes to this namespace or anything below it are delivered
+    pub namespace: String,
+    pub url: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A newly registered webhook, with the secret its deliveries are signed with
+#[derive(Serialize, Deserialize)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// Registers `url` to be posted to after every upload, import, transform and clear on
+/// `namespace` or below it. Needs `read` permission on the namespace. Only the response holds
+/// the secret to check deliveries with; it can be derived again, but is never listed.
+#[post("/webhooks", data = "<input>")]
+pub async fn create(
+    token: Token,
+    input: Json<WebhookInput>,
+) -> Result<Json<CreatedWebhook>, Custom<String>> {
+    let path = authorize_namespace(&token, &input.namespace, false)?;
+
+    let url = Url::parse(&input.url)
+        .map_err(|e| Custom(Status::BadRequest, format!("invalid_webhook: {e}")))?;
+    ImportPolicy::from_env()
+        .check_host(&url)
+        .await
+        .map_err(|e| Custom(e.status(), format!("{}: {e}", e.code())))?;
+
+    if master_secret().is_none() {
+        return Err(Custom(
+            Status::InternalServerError,
+            "Webhooks need METTA_KG_SECRET to be set".to_string(),
+        ));
+    }
+
+    let to_insert = WebhookInsert {
+        token: token.id,
+        namespace: path_to_namespace(&path),
+        url: url.to_string(),
+        description: input.description.clone(),
+        creation_timestamp: Utc::now().naive_utc(),
+    };
+
+    let webhook: Webhook = diesel::insert_into(webhooks::table)
+        .values(&to_insert)
+        .get_result(&mut establish_connection())
+        .map_err(|_| {
+            Custom(
+                Status::InternalServerError,
+                "Failed to store webhook".to_string(),
+            )
+        })?;
+
+    let secret = webhook_secret(webhook.id).unwrap_or_default();
+    Ok(Json(CreatedWebhook { webhook, secret }))
+}
+
+/// All webhooks on namespaces of the token, with the outcome of their last delivery
+#[get("/webhooks")]
+pub fn get_all(token: Token) -> Result<Json<Vec<Webhook>>, Status> {
+    if !token.permission_read {
+        return Err(Status::Unauthorized);
+    }
+
+    let results = webhooks::table
+        .select(Webhook::as_select())
+        .order(webhooks::id.asc())
+        .load(&mut establish_connection());
+
+    match results {
+        Ok(results) => Ok(Json(
+            results
+                .into_iter()
+                .filter(|webhook| webhook.namespace.starts_with(&token.namespace))
+                .collect(),
+        )),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+#[delete("/webhooks/<webhook_id>")]
+pub fn delete(token: Token, webhook_id: i32) -> Status {
+    if !token.permission_write {
+        return Status::Unauthorized;
+    }
+
+    let webhook = webhooks::table
+        .select(Webhook::as_select())
+        .filter(webhooks::id.eq(webhook_id))
+        .get_result(&mut establish_connection());
+
+    match webhook {
+        Ok(webhook) if webhook.namespace.starts_with(&token.namespace) => (),
+        _ => return Status::NotFound,
+    }
+
+    let result = diesel::delete(webhooks::table.filter(webhooks::id.eq(webhook_id)))
+        .execute(&mut establish_connection());
+
+    match result {
+        Ok(_) => Status::Ok,
+        Err(_) => Status::NotFound,
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+use std::fmt;
+use std::str::FromStr;
+
+/// A cron-like schedule with the usual five fields, evaluated in UTC:
+///
+/// ```text
+/// minute hour day-of-month month day-of-week
+/// ```
+///
+/// Fields accept `*` (or `?`), numbers, ranges (`1-5`), steps (`*/15`, `0-30/10`) and comma
+/// separated lists of these. Day-of-week runs from 0 (Sunday) to 6, 7 is accepted for Sunday as well.
+/// The shorthands `@hourly`, `@daily`, `@weekly` and `@monthly` are also understood.
+///
+/// As in cron, when both day-of-month and day-of-week are restricted, a day matching either
+/// of them matches. A field is restricted when it leaves out any day, so `*/1` or `0-6` is not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleError(pub String);
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid schedule: {}", self.0)
+    }
+}
+
+/// Parses one field into a lookup table indexed by value
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, ScheduleError> {
+    let mut result = vec![false; max as usize + 1];
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| ScheduleError(format!("invalid step in '{part}'")))?;
+                if step == 0 {
+                    return Err(ScheduleError(format!("zero step in '{part}'")));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let parse_value = |value: &str| -> Result<u32, ScheduleError> {
+            match value.parse::<u32>() {
+                Ok(v) if (min..=max).contains(&v) => Ok(v),
+                _ => Err(ScheduleError(format!(
+                    "'{value}' is not in range {min}-{max}"
+                ))),
+            }
+        };
+
+        let (start, end) = match range {
+            "*" | "?" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+                // `5/10` means starting at 5, every 10
+                None if step > 1 => (parse_value(range)?, max),
+                None => {
+                    let value = parse_value(range)?;
+                    (value, value)
+                }
+            },
+        };
+
+        if start > end {
+            return Err(ScheduleError(format!("empty range in '{part}'")));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            result[value as usize] = true;
+        }
+    }
+
+    Ok(result)
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expanded = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(ScheduleError(format!(
+                "expected 5 fields, found {}",
+                fields.len()
+            )));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+        days_of_week.truncate(7);
+        let days_of_month = parse_field(fields[2], 1, 31)?;
+
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            day_of_month_restricted: !days_of_month[1..].iter().all(|day| *day),
+            day_of_week_restricted: !days_of_week.iter().all(|day| *day),
+            days_of_month,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+        })
+    }
+}
+
+impl Schedule {
+    fn day_matches(&self, time: &NaiveDateTime) -> bool {
+        if !self.months[time.month() as usize] {
+            return false;
+        }
+
+        let day_of_month = self.days_of_month[time.day() as usize];
+        let day_of_week = self.days_of_week[time.weekday().num_days_from_sunday() as usize];
+
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+
+    /// The first matching minute strictly after `time`, searching at most about five years ahead
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut candidate = time.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = time + Duration::days(5 * 366);
+
+        while candidate <= limit {
+            if !self.day_matches(&candidate) {
+                candidate = candidate.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+
+            if !self.hours[candidate.hour() as usize] {
+                candidate = candidate.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+
+            if !self.minutes[candidate.minute() as usize] {
+                candidate += Duration::minutes(1);
+                continue;
+            }
+
+            return Some(candidate);
+        }
+
+        None
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use rocket::fairing::AdHoc;
+use rocket::response::status::Custom;
+use rocket::tokio::sync::Semaphore;
+use rocket::tokio::task::JoinSet;
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::establish_connection;
+use crate::events::{EventBus, Operation};
+use crate::import_policy::ImportPolicy;
+use crate::model::{ImportRun, ImportRunInsert, ImportSource, Token};
+use crate::mork_api::{ClearRequest, MorkApiClient};
+use crate::routes::spaces::{import_into, ingest_mm2, namespace_to_path, publish, publish_import};
+use crate::schedule::Schedule;
+use crate::schema::{import_runs, import_sources, tokens};
+
+/// Imports `source` once, clearing the data level of its namespace first if requested. Both
+/// are published like the same requests to the spaces routes.
+async fn execute(source: &ImportSource, events: &EventBus) -> Result<(), String> {
+    let token = tokens::table
+        .select(Token::as_select())
+        .filter(tokens::id.eq(source.token))
+        .get_result(&mut establish_connection())
+        .map_err(|_| "Token of this source no longer exists".to_string())?;
+
+    let path = namespace_to_path(&source.namespace).ok_or("Invalid namespace")?;
+    let mm2 = ingest_mm2(source.pattern.clone(), source.template.clone())
+        .map_err(|Custom(_, message)| message)?;
+
+    if !path.starts_with(token.namespace.strip_prefix("/").unwrap()) || !token.permission_write {
+        return Err("Token of this source may not write to its namespace".to_string());
+    }
+
+    if source.clear_before {
+        let request = ClearRequest::new()
+            .namespace(path.clone())
+            .expr("$x".to_string());
+
+        let outcome = MorkApiClient::new().dispatch(request).await;
+        publish(events, Operation::Clear, &path, &token, &outcome);
+        outcome.map_err(|status| format!("Failed to clear namespace: {status}"))?;
+    }
+
+    let outcome = import_into(
+        &token,
+        path.clone(),
+        source.uri.clone(),
+        mm2,
+        &ImportPolicy::from_env(),
+    )
+    .await;
+    publish_import(events, &path, &token, &outcome);
+    outcome
+        .map(|_| ())
+        .map_err(|Custom(status, message)| format!("{status}: {message}"))
+}
+
+/// Runs `source` now and records the run
+pub async fn run_source(
+    source: &ImportSource,
+    events: &EventBus,
+) -> Result<ImportRun, diesel::result::Error> {
+    let run: ImportRun = diesel::insert_into(import_runs::table)
+        .values(&ImportRunInsert {
+            source: source.id,
+            start_timestamp: Utc::now().naive_utc(),
+            end_timestamp: None,
+            success: None,
+            message: None,
+        })
+        .get_result(&mut establish_connection())?;
+
+    let outcome = execute(source, events).await;
+
+    if let Err(e) = &outcome {
+        eprintln!("Scheduled import {} failed: {e}", source.id);
+    }
+
+    diesel::update(import_runs::table.filter(import_runs::id.eq(run.id)))
+        .set((
+            import_runs::end_timestamp.eq(Some(Utc::now().naive_utc())),
+            import_runs::success.eq(Some(outcome.is_ok())),
+            import_runs::message.eq(outcome.err()),
+        ))
+        .get_result(&mut establish_connection())
+}
+
+/// Runs every source whose `next_run` has passed, after moving `next_run` forward. At most
+/// `METTA_KG_SCHEDULER_CONCURRENCY` (default 4) sources run at the same time.
+async fn tick(events: EventBus) {
+    let conn = &mut establish_connection();
+    let now = Utc::now().naive_utc();
+
+    let due = import_sources::table
+        .select(ImportSource::as_select())
+        .filter(import_sources::next_run.le(now))
+        .load(conn);
+
+    let due = match due {
+        Ok(due) => due,
+        Err(e) => {
+            eprintln!("Failed to load scheduled imports: {e}");
+            return;
+        }
+    };
+
+    let concurrency = env::var("METTA_KG_SCHEDULER_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(4);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut runs = JoinSet::new();
+
+    for source in due {
+        let next = Schedule::from_str(&source.schedule)
+            .ok()
+            .and_then(|s| s.next_after(now));
+
+        let updated =
+            diesel::update(import_sources::table.filter(import_sources::id.eq(source.id)))
+                .set(import_sources::next_run.eq(next))
+                .execute(conn);
+
+        if let Err(e) = updated {
+            eprintln!("Failed to reschedule import {}: {e}", source.id);
+            continue;
+        }
+
+        let semaphore = semaphore.clone();
+        let events = events.clone();
+        runs.spawn(async move {
+            let _permit = semaphore.acquire().await.unwrap();
+            if let Err(e) = run_source(&source, &events).await {
+                eprintln!("Failed to record run of import {}: {e}", source.id);
+            }
+        });
+    }
+
+    while let Some(run) = runs.join_next().await {
+        if let Err(e) = run {
+            eprintln!("Scheduled import failed: {e}");
+        }
+    }
+}
+
+/// Checks for due import sources every `METTA_KG_SCHEDULER_INTERVAL` seconds (default 60).
+/// Setting the interval to 0 disables scheduled imports.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Import scheduler", |rocket| {
+        let events = rocket.state::<EventBus>().cloned();
+
+        Box::pin(async move {
+            let interval = env::var("METTA_KG_SCHEDULER_INTERVAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60);
+
+            if interval == 0 {
+                return;
+            }
+            let Some(events) = events else {
+                eprintln!("No event bus, scheduled imports will not run");
+                return;
+            };
+
+            rocket::tokio::spawn(async move {
+                loop {
+                    rocket::tokio::time::sleep(Duration::from_secs(interval)).await;
+
+                    // a panic, e.g. from an unreachable database, should not stop the scheduler
+                    if let Err(e) = rocket::tokio::spawn(tick(events.clone())).await {
+                        eprintln!("Import scheduler tick failed: {e}");
+                    }
+                }
+            });
+        })
+    })
+}
@@ -15,3 +15,111 @@ diesel::table! {
         parent -> Nullable<Int4>,
     }
 }
+
+diesel::table! {
+    import_sources (id) {
+        id -> Int4,
+        token -> Int4,
+        namespace -> Varchar,
+        uri -> Varchar,
+        pattern -> Nullable<Varchar>,
+        template -> Nullable<Varchar>,
+        schedule -> Varchar,
+        clear_before -> Bool,
+        creation_timestamp -> Timestamp,
+        next_run -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    import_runs (id) {
+        id -> Int4,
+        source -> Int4,
+        start_timestamp -> Timestamp,
+        end_timestamp -> Nullable<Timestamp>,
+        success -> Nullable<Bool>,
+        message -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    saved_queries (id) {
+        id -> Int4,
+        token -> Int4,
+        namespace -> Varchar,
+        name -> Varchar,
+        description -> Varchar,
+        kind -> Varchar,
+        patterns -> Array<Text>,
+        templates -> Array<Text>,
+        parameters -> Array<Text>,
+        creation_timestamp -> Timestamp,
+    }
+}
+
+diesel::table! {
+    translation_usage (token, day) {
+        token -> Int4,
+        day -> Date,
+        count -> Int4,
+    }
+}
+
+diesel::table! {
+    triggers (id) {
+        id -> Int4,
+        token -> Int4,
+        namespace -> Varchar,
+        target_namespace -> Varchar,
+        description -> Varchar,
+        patterns -> Array<Text>,
+        templates -> Array<Text>,
+        creation_timestamp -> Timestamp,
+        last_run_timestamp -> Nullable<Timestamp>,
+        last_error -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Int4,
+        token -> Int4,
+        namespace -> Varchar,
+        url -> Varchar,
+        description -> Varchar,
+        creation_timestamp -> Timestamp,
+        last_delivery_timestamp -> Nullable<Timestamp>,
+        last_status -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    snapshots (id) {
+        id -> Int4,
+        token -> Nullable<Int4>,
+        namespace -> Varchar,
+        description -> Varchar,
+        atoms -> Int4,
+        bytes -> Int8,
+        creation_timestamp -> Timestamp,
+    }
+}
+
+diesel::joinable!(import_runs -> import_sources (source));
+diesel::joinable!(import_sources -> tokens (token));
+diesel::joinable!(saved_queries -> tokens (token));
+diesel::joinable!(snapshots -> tokens (token));
+diesel::joinable!(triggers -> tokens (token));
+diesel::joinable!(webhooks -> tokens (token));
+diesel::joinable!(translation_usage -> tokens (token));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    import_runs,
+    import_sources,
+    saved_queries,
+    snapshots,
+    tokens,
+    translation_usage,
+    triggers,
+    webhooks,
+);
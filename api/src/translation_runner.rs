@@ -0,0 +1,234 @@
+use rocket::tokio::io::AsyncReadExt;
+use rocket::tokio::process::Command;
+use rocket::tokio::time::timeout;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use uuid::Uuid;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_MAX_MEMORY_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+/// Only the tail of stderr is kept, tracebacks end with the interesting part
+const MAX_STDERR_LINES: usize = 50;
+/// How long stderr is still read after a timed out script was killed
+const STDERR_GRACE: Duration = Duration::from_secs(1);
+
+/// Applies the memory limit before handing over to the translation script, so the limit
+/// does not depend on anything platform specific on the Rust side.
+const LAUNCHER: &str = "\
+import os, resource, runpy, sys
+limit = int(sys.argv[1])
+if limit > 0:
+    resource.setrlimit(resource.RLIMIT_AS, (limit, limit))
+script = sys.argv[2]
+sys.argv = sys.argv[2:]
+sys.path.insert(0, os.path.dirname(os.path.abspath(script)))
+runpy.run_path(script, run_name='__main__')
+";
+
+/// What is known about a translation subprocess that did not succeed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranslationDiagnostics {
+    /// `None` when the process was killed by a signal or never exited
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// Last line of a Python traceback, e.g. `ValueError: bad input`
+    pub exception: Option<String>,
+    /// Tail of the captured stderr
+    pub stderr: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    /// The interpreter could not be started at all
+    Spawn(io::Error),
+    TimedOut(TranslationDiagnostics),
+    Failed(TranslationDiagnostics),
+}
+
+impl TranslationDiagnostics {
+    fn from_stderr(exit_code: Option<i32>, stderr: &[u8]) -> Self {
+        let stderr = String::from_utf8_lossy(stderr);
+        let lines: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+        let tail = &lines[lines.len().saturating_sub(MAX_STDERR_LINES)..];
+
+        // The exception is the last unindented line of the last traceback
+        let exception = lines
+            .iter()
+            .rposition(|l| l.starts_with("Traceback"))
+            .and_then(|start| {
+                lines[start + 1..]
+                    .iter()
+                    .rev()
+                    .find(|l| !l.starts_with(char::is_whitespace))
+            })
+            .map(|l| l.to_string());
+
+        TranslationDiagnostics {
+            exit_code,
+            timed_out: false,
+            exception,
+            stderr: tail.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+}
+
+/// A directory holding the input and output of one translation, removed with everything in
+/// it when dropped.
+pub struct TranslationWorkspace {
+    dir: PathBuf,
+}
+
+impl TranslationWorkspace {
+    /// Path without extension that the translation scripts expect as their first argument,
+    /// they read `<stem>.<ext>` and write `<stem>-output.metta`
+    pub fn stem(&self) -> PathBuf {
+        self.dir.join("input")
+    }
+
+    pub fn input(&self, ext: &str) -> PathBuf {
+        self.dir.join(format!("input.{ext}"))
+    }
+
+    pub fn output(&self) -> PathBuf {
+        self.dir.join("input-output.metta")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for TranslationWorkspace {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            if e.kind() != io::ErrorKind::NotFound {
+                eprintln!("Failed to remove {}: {e}", self.dir.display());
+            }
+        }
+    }
+}
+
+/// Runs the Python translation scripts with a wall-clock timeout and an address space limit.
+///
+/// The child gets an empty environment, so secrets such as database credentials are not
+/// passed on, and is killed if it outlives the timeout or the request is dropped.
+#[derive(Clone, Debug)]
+pub struct TranslationRunner {
+    pub python: PathBuf,
+    pub work_dir: PathBuf,
+    pub timeout: Duration,
+    /// Limit on the address space of the interpreter in bytes, 0 for no limit
+    pub max_memory_bytes: u64,
+}
+
+impl Default for TranslationRunner {
+    fn default() -> Self {
+        TranslationRunner {
+            python: PathBuf::from("./venv/bin/python"),
+            work_dir: PathBuf::from("temp"),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            max_memory_bytes: DEFAULT_MAX_MEMORY_BYTES,
+        }
+    }
+}
+
+impl TranslationRunner {
+    /// Reads the configuration from the environment, falling back to
+    /// [`TranslationRunner::default`]:
+    ///
+    /// - `METTA_KG_TRANSLATION_PYTHON`: interpreter to run the scripts with
+    /// - `METTA_KG_TRANSLATION_WORK_DIR`: where workspaces are created
+    /// - `METTA_KG_TRANSLATION_TIMEOUT_SECONDS`
+    /// - `METTA_KG_TRANSLATION_MAX_MEMORY_BYTES`
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        TranslationRunner {
+            python: env::var("METTA_KG_TRANSLATION_PYTHON")
+                .map(PathBuf::from)
+                .unwrap_or(default.python),
+            work_dir: env::var("METTA_KG_TRANSLATION_WORK_DIR")
+                .map(PathBuf::from)
+                .unwrap_or(default.work_dir),
+            timeout: env::var("METTA_KG_TRANSLATION_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.timeout),
+            max_memory_bytes: env::var("METTA_KG_TRANSLATION_MAX_MEMORY_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_memory_bytes),
+        }
+    }
+
+    /// Creates a fresh, empty workspace under the work directory
+    pub fn workspace(&self) -> io::Result<TranslationWorkspace> {
+        let dir = self
+            .work_dir
+            .join(format!("translations-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+
+        Ok(TranslationWorkspace { dir })
+    }
+
+    /// Runs `script` with `args`, succeeding only if it exits with status 0 in time
+    pub async fn run(&self, script: &str, args: &[String]) -> Result<(), RunError> {
+        let mut child = Command::new(&self.python)
+            .arg("-c")
+            .arg(LAUNCHER)
+            .arg(self.max_memory_bytes.to_string())
+            .arg(script)
+            .args(args)
+            .env_clear()
+            .env("PATH", env::var("PATH").unwrap_or_default())
+            .env("PYTHONDONTWRITEBYTECODE", "1")
+            .env("PYTHONIOENCODING", "utf-8")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(RunError::Spawn)?;
+
+        // stderr is read as it is written, so what came before a timeout is not lost with it
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let reader = rocket::tokio::spawn(async move {
+            let mut output = vec![];
+            let _ = stderr.read_to_end(&mut output).await;
+            output
+        });
+
+        let status = match timeout(self.timeout, child.wait()).await {
+            Ok(status) => status.map_err(RunError::Spawn)?,
+            Err(_) => {
+                let _ = child.kill().await;
+                // anything the script started may still hold stderr open
+                let stderr = timeout(STDERR_GRACE, reader)
+                    .await
+                    .ok()
+                    .and_then(Result::ok)
+                    .unwrap_or_default();
+                return Err(RunError::TimedOut(TranslationDiagnostics {
+                    timed_out: true,
+                    ..TranslationDiagnostics::from_stderr(None, &stderr)
+                }));
+            }
+        };
+
+        if status.success() {
+            return Ok(());
+        }
+
+        let stderr = reader.await.unwrap_or_default();
+        Err(RunError::Failed(TranslationDiagnostics::from_stderr(
+            status.code(),
+            &stderr,
+        )))
+    }
+}
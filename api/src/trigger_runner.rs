@@ -0,0 +1,112 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use rocket::fairing::AdHoc;
+use rocket::response::status::Custom;
+
+use crate::db::establish_connection;
+use crate::events::{self, EventBus, NamespaceEvent, Operation};
+use crate::model::{Token, Trigger};
+use crate::mork_api::{MorkApiClient, TransformDetails, TransformRequest};
+use crate::routes::spaces::authorize_namespace;
+use crate::routes::translations::env_limit;
+use crate::schema::{tokens, triggers};
+
+/// Runs the transform of `trigger`, provided its token may still read its namespace and write
+/// its target
+async fn run_trigger(trigger: &Trigger) -> Result<(), String> {
+    let token = tokens::table
+        .select(Token::as_select())
+        .filter(tokens::id.eq(trigger.token))
+        .get_result(&mut establish_connection())
+        .map_err(|_| "Token of this trigger no longer exists".to_string())?;
+
+    let source = authorize_namespace(&token, &trigger.namespace, false)
+        .map_err(|Custom(_, message)| message)?;
+    let target = authorize_namespace(&token, &trigger.target_namespace, true)
+        .map_err(|Custom(_, message)| message)?;
+
+    let request = TransformRequest::new()
+        .namespace(source)
+        .target_namespace(target)
+        .transform_input(
+            TransformDetails::new()
+                .patterns(trigger.patterns.clone())
+                .templates(trigger.templates.clone()),
+        );
+
+    MorkApiClient::new()
+        .dispatch(request)
+        .await
+        .map(|_| ())
+        .map_err(|status| format!("Failed to contact backend: {status}"))
+}
+
+/// Runs the triggers on the namespace of a successful upload, import or transform. What they
+/// write is published in turn, so triggers can build on each other; past
+/// `METTA_KG_TRIGGER_MAX_DEPTH` (default 4) triggers in a row, the chain is assumed to be a
+/// loop and stops.
+async fn fire(events: EventBus, event: NamespaceEvent) {
+    if !event.success || event.operation == Operation::Clear {
+        return;
+    }
+
+    let due = triggers::table
+        .select(Trigger::as_select())
+        .filter(triggers::namespace.eq(&event.namespace))
+        .order(triggers::id.asc())
+        .load(&mut establish_connection());
+
+    let due = match due {
+        Ok(due) => due,
+        Err(e) => {
+            eprintln!("Failed to load triggers on {}: {e}", event.namespace);
+            return;
+        }
+    };
+
+    if due.is_empty() {
+        return;
+    }
+
+    let max_depth = env_limit("METTA_KG_TRIGGER_MAX_DEPTH", 4) as usize;
+    if event.depth >= max_depth {
+        eprintln!(
+            "Not running triggers on {} after {} triggers in a row, they probably loop",
+            event.namespace, event.depth
+        );
+        return;
+    }
+
+    for trigger in due {
+        let outcome = run_trigger(&trigger).await;
+
+        if let Err(e) = &outcome {
+            eprintln!("Trigger {} failed: {e}", trigger.id);
+        }
+
+        let recorded = diesel::update(triggers::table.filter(triggers::id.eq(trigger.id)))
+            .set((
+                triggers::last_run_timestamp.eq(Some(Utc::now().naive_utc())),
+                triggers::last_error.eq(outcome.as_ref().err()),
+            ))
+            .execute(&mut establish_connection());
+        if let Err(e) = recorded {
+            eprintln!("Failed to record run of trigger {}: {e}", trigger.id);
+        }
+
+        events.publish(
+            NamespaceEvent::new(
+                Operation::Transform,
+                trigger.target_namespace.clone(),
+                trigger.token,
+                outcome.is_ok(),
+            )
+            .depth(event.depth + 1),
+        );
+    }
+}
+
+/// Runs triggers in the background, as the events they react to come in
+pub fn fairing() -> AdHoc {
+    events::subscriber("Trigger runner", fire)
+}
@@ -0,0 +1,188 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use hmac::{Hmac, Mac};
+use reqwest::{redirect, Client};
+use rocket::fairing::AdHoc;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::env;
+use std::fmt::Write;
+use std::time::Duration;
+use url::Url;
+
+use crate::db::establish_connection;
+use crate::events::{self, EventBus, NamespaceEvent, Operation};
+use crate::import_policy::ImportPolicy;
+use crate::model::Webhook;
+use crate::routes::translations::env_limit;
+use crate::schema::webhooks;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying the signature of a delivery, `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "X-MeTTa-KG-Signature";
+
+/// Body of a webhook delivery
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WebhookPayload {
+    /// Id of the webhook, to find the secret the body is signed with
+    pub webhook: i32,
+    pub operation: Operation,
+    /// Namespace that changed, in the `/space/subspace/` form
+    pub path: String,
+    /// Id of the token the operation ran on behalf of
+    pub token: i32,
+    /// `success` or `failure`
+    pub outcome: String,
+    pub timestamp: NaiveDateTime,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// `METTA_KG_SECRET`, which the secrets of all webhooks are derived from
+pub(crate) fn master_secret() -> Option<String> {
+    env::var("METTA_KG_SECRET").ok().filter(|s| !s.is_empty())
+}
+
+/// Secret that deliveries of webhook `id` are signed with. It is derived from
+/// `METTA_KG_SECRET` rather than stored, so `None` when that is not set.
+pub fn webhook_secret(id: i32) -> Option<String> {
+    let secret = master_secret()?;
+    Some(hex(&hmac(
+        secret.as_bytes(),
+        format!("webhook-{id}").as_bytes(),
+    )))
+}
+
+/// Value of the [`SIGNATURE_HEADER`] for `body`, signed with `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    format!("sha256={}", hex(&hmac(secret.as_bytes(), body)))
+}
+
+/// Posts `body` to the webhook until it answers with a success status, retrying
+/// `METTA_KG_WEBHOOK_MAX_ATTEMPTS` (default 5) times with a backoff that starts at
+/// `METTA_KG_WEBHOOK_BACKOFF_MS` (default 1000) and doubles. Returns the last status, or why
+/// nothing was delivered.
+async fn deliver(webhook: &Webhook, body: String) -> Result<String, String> {
+    let secret = webhook_secret(webhook.id).ok_or("METTA_KG_SECRET is not set")?;
+    let signature = sign(&secret, body.as_bytes());
+
+    // the host may resolve elsewhere since the webhook was registered
+    let url = Url::parse(&webhook.url).map_err(|e| format!("invalid_uri: {e}"))?;
+    ImportPolicy::from_env()
+        .check_host(&url)
+        .await
+        .map_err(|e| format!("{}: {e}", e.code()))?;
+
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .timeout(Duration::from_secs(env_limit(
+            "METTA_KG_WEBHOOK_TIMEOUT_SECONDS",
+            10,
+        )))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let max_attempts = env_limit("METTA_KG_WEBHOOK_MAX_ATTEMPTS", 5).max(1);
+    let mut backoff = Duration::from_millis(env_limit("METTA_KG_WEBHOOK_BACKOFF_MS", 1000));
+    let mut last = String::new();
+
+    for attempt in 1..=max_attempts {
+        if attempt > 1 {
+            rocket::tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+
+        let response = client
+            .post(url.clone())
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => {
+                return Ok(response.status().to_string())
+            }
+            Ok(response) => last = response.status().to_string(),
+            Err(e) => last = format!("fetch_failed: {e}"),
+        }
+    }
+
+    Err(format!("{last} after {max_attempts} attempts"))
+}
+
+/// Notifies the webhooks on the namespace of `event` or a namespace above it
+async fn fire(_: EventBus, event: NamespaceEvent) {
+    let due = webhooks::table
+        .select(Webhook::as_select())
+        .order(webhooks::id.asc())
+        .load(&mut establish_connection());
+
+    let due = match due {
+        Ok(due) => due,
+        Err(e) => {
+            eprintln!("Failed to load webhooks: {e}");
+            return;
+        }
+    };
+
+    for webhook in due
+        .into_iter()
+        .filter(|webhook| event.namespace.starts_with(&webhook.namespace))
+    {
+        let payload = WebhookPayload {
+            webhook: webhook.id,
+            operation: event.operation,
+            path: event.namespace.clone(),
+            token: event.token,
+            outcome: if event.success { "success" } else { "failure" }.to_string(),
+            timestamp: event.timestamp,
+        };
+        let body = match rocket::serde::json::to_string(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                eprintln!("Failed to serialize payload of webhook {}: {e}", webhook.id);
+                continue;
+            }
+        };
+
+        // a slow receiver should not hold up the others
+        rocket::tokio::spawn(async move {
+            let status = match deliver(&webhook, body).await {
+                Ok(status) => status,
+                Err(e) => {
+                    eprintln!("Delivery to webhook {} failed: {e}", webhook.id);
+                    e
+                }
+            };
+
+            let recorded = diesel::update(webhooks::table.filter(webhooks::id.eq(webhook.id)))
+                .set((
+                    webhooks::last_delivery_timestamp.eq(Some(Utc::now().naive_utc())),
+                    webhooks::last_status.eq(Some(status)),
+                ))
+                .execute(&mut establish_connection());
+            if let Err(e) = recorded {
+                eprintln!("Failed to record delivery of webhook {}: {e}", webhook.id);
+            }
+        });
+    }
+}
+
+/// Delivers events to webhooks in the background
+pub fn fairing() -> AdHoc {
+    events::subscriber("Webhook runner", fire)
+}
@@ -22,6 +22,26 @@ pub fn create_test_database_if_not_exists() {
     }
 }
 
+/// Tables that reference `tokens`, in the order they have to be dropped
+const DEPENDENT_TABLES: &[&str] = &[
+    "import_runs",
+    "import_sources",
+    "translation_usage",
+    "saved_queries",
+    "triggers",
+    "webhooks",
+    "snapshots",
+];
+
+pub fn drop_dependent_tables() {
+    let conn = &mut establish_connection();
+    for table in DEPENDENT_TABLES {
+        diesel::sql_query(format!("DROP TABLE IF EXISTS {table}"))
+            .execute(conn)
+            .expect("Failed to drop dependent table");
+    }
+}
+
 pub fn drop_tokens_table() {
     let conn = &mut establish_connection();
     let sql = r#"DROP TABLE IF EXISTS tokens"#;
@@ -35,6 +55,7 @@ pub fn drop_tokens_table() {
 }
 
 pub fn teardown_database() {
+    drop_dependent_tables();
     drop_tokens_table();
 }
 
@@ -77,6 +98,12 @@ pub fn setup(mork_base_url: &str) {
     env::set_var("POSTGRES_PASSWORD", "metta-kg-password");
     env::set_var("POSTGRES_DB", "metta-kg-test");
     env::set_var("POSTGRES_HOST", "localhost");
+    // imports point at the local mock server
+    env::set_var("METTA_KG_IMPORT_ALLOW_PRIVATE", "true");
+    env::set_var(
+        "METTA_KG_SNAPSHOT_DIR",
+        env::temp_dir().join("metta-kg-test-snapshots"),
+    );
 
     let mut connection = establish_connection();
     connection
@@ -85,3 +112,29 @@ pub fn setup(mork_base_url: &str) {
 
     setup_database();
 }
+
+/// Stands in for the Python interpreter of the translation runner, running `command` where the
+/// translation script would have run. It is invoked as `-c <launcher> <memory limit> <script>
+/// <stem> ...`, so the output file is `$5-output.metta` and the script parameters start at `$6`.
+// only used by the translation tests
+#[allow(dead_code)]
+#[cfg(unix)]
+pub fn fake_interpreter(work_dir: &std::path::Path, command: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = work_dir.join("fake-python");
+    std::fs::write(&path, format!("#!/bin/sh\n{command}\n")).expect("fake interpreter");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).expect("permissions");
+    path
+}
+
+/// Serves the data imports download from `/data` and the paths below it on `server`
+// only used by the import tests
+#[allow(dead_code)]
+pub fn mock_remote_data(server: &httpmock::MockServer) {
+    server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path_matches(httpmock::Regex::new("^/data").unwrap());
+        then.status(200).body("(edge a b)\n");
+    });
+}
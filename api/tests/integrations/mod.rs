@@ -1,11 +1,25 @@
 mod common;
 mod test_clear;
+mod test_diff;
 mod test_explore;
 mod test_export;
 mod test_import;
+mod test_import_batch;
+mod test_namespaces;
+mod test_pipelines;
+mod test_queries;
+mod test_query;
 mod test_read;
+mod test_rules;
+mod test_snapshots;
+mod test_sources;
+mod test_sparql;
+mod test_subscribe;
 mod test_transform;
+mod test_translations;
+mod test_triggers;
 mod test_upload;
+mod test_webhooks;
 
 #[tokio::test]
 async fn test_integration_is_working() {
@@ -1,9 +1,12 @@
+use api::import_policy::{is_non_public_ip, ImportPolicy, ImportPolicyError};
 use api::rocket;
 use httpmock::prelude::*;
 use httpmock::Regex;
 use rocket::http::{Header, Status};
 use rocket::local::asynchronous::Client;
 use serial_test::serial;
+use std::env;
+use url::Url;
 
 use crate::integrations::common;
 
@@ -16,14 +19,15 @@ async fn test_import_success() {
     }
     let server = MockServer::start();
     common::setup(&server.base_url());
+    common::mock_remote_data(&server);
 
     let token = common::create_test_token("/test/", true, true);
 
     // Mock import request
     server.mock(|when, then| {
-        when.method(GET)
-            .path_matches(Regex::new(r"/import/.*").unwrap());
-        then.status(200).body("Import successful");
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
     });
 
     let client = Client::tracked(rocket())
@@ -33,7 +37,7 @@ async fn test_import_success() {
     let response = client
         .post(format!(
             "/spaces/import/test/space?uri={}",
-            urlencoding::encode("http://example.com/data")
+            urlencoding::encode(&server.url("/data"))
         ))
         .header(Header::new("authorization", token.code.clone()))
         .dispatch()
@@ -66,7 +70,7 @@ async fn test_non_existent_namespace() {
     let response = client
         .post(format!(
             "/spaces/import/other/space?uri={}",
-            urlencoding::encode("http://example.com/data")
+            urlencoding::encode(&server.url("/data"))
         ))
         .header(Header::new("authorization", token.code.clone()))
         .dispatch()
@@ -86,14 +90,15 @@ async fn test_existing_empty_namespace() {
     }
     let server = MockServer::start();
     common::setup(&server.base_url());
+    common::mock_remote_data(&server);
 
     let token = common::create_test_token("/test/", true, true);
 
     // Mock import request
     server.mock(|when, then| {
-        when.method(GET)
-            .path_matches(Regex::new(r"/import/.*").unwrap());
-        then.status(200).body("Import successful");
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
     });
 
     let client = Client::tracked(rocket())
@@ -103,7 +108,7 @@ async fn test_existing_empty_namespace() {
     let response = client
         .post(format!(
             "/spaces/import/test/space?uri={}",
-            urlencoding::encode("http://example.com/data")
+            urlencoding::encode(&server.url("/data"))
         ))
         .header(Header::new("authorization", token.code.clone()))
         .dispatch()
@@ -125,14 +130,15 @@ async fn test_non_empty_namespace() {
     }
     let server = MockServer::start();
     common::setup(&server.base_url());
+    common::mock_remote_data(&server);
 
     let token = common::create_test_token("/test/", true, true);
 
     // Mock import request
     server.mock(|when, then| {
-        when.method(GET)
-            .path_matches(Regex::new(r"/import/.*").unwrap());
-        then.status(200).body("Import successful");
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
     });
 
     let client = Client::tracked(rocket())
@@ -142,7 +148,7 @@ async fn test_non_empty_namespace() {
     let response = client
         .post(format!(
             "/spaces/import/test/space?uri={}",
-            urlencoding::encode("http://example.com/data")
+            urlencoding::encode(&server.url("/data"))
         ))
         .header(Header::new("authorization", token.code.clone()))
         .dispatch()
@@ -164,14 +170,15 @@ async fn test_different_namespaces() {
     }
     let server = MockServer::start();
     common::setup(&server.base_url());
+    common::mock_remote_data(&server);
 
     let token1 = common::create_test_token("/ns1/", true, true);
     let token2 = common::create_test_token("/ns2/", true, true);
 
     server.mock(|when, then| {
-        when.method(GET)
-            .path_matches(Regex::new(r"/import/.*").unwrap());
-        then.status(200).body("Import successful");
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
     });
 
     let client = Client::tracked(rocket())
@@ -182,7 +189,7 @@ async fn test_different_namespaces() {
     let response1 = client
         .post(format!(
             "/spaces/import/ns1/space?uri={}",
-            urlencoding::encode("http://example.com/data")
+            urlencoding::encode(&server.url("/data"))
         ))
         .header(Header::new("authorization", token1.code.clone()))
         .dispatch()
@@ -193,7 +200,7 @@ async fn test_different_namespaces() {
     let response2 = client
         .post(format!(
             "/spaces/import/ns2/space?uri={}",
-            urlencoding::encode("http://example.com/data")
+            urlencoding::encode(&server.url("/data"))
         ))
         .header(Header::new("authorization", token2.code.clone()))
         .dispatch()
@@ -223,7 +230,7 @@ async fn test_namespace_mismatch() {
     let response = client
         .post(format!(
             "/spaces/import/other/space?uri={}",
-            urlencoding::encode("http://example.com/data")
+            urlencoding::encode(&server.url("/data"))
         ))
         .header(Header::new("authorization", token.code.clone()))
         .dispatch()
@@ -233,3 +240,280 @@ async fn test_namespace_mismatch() {
 
     common::teardown_database();
 }
+
+#[tokio::test]
+#[serial]
+async fn test_import_rejects_file_scheme() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post(format!(
+            "/spaces/import/test/space?uri={}",
+            urlencoding::encode("file:///etc/passwd")
+        ))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Forbidden);
+    let body = response.into_string().await.expect("response body");
+    assert!(body.starts_with("scheme_not_allowed"));
+    import_mock.assert_hits(0);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_import_rejects_private_addresses() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    env::set_var("METTA_KG_IMPORT_ALLOW_PRIVATE", "false");
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for uri in [
+        "http://localhost:8001/status",
+        "http://169.254.169.254/latest/meta-data/",
+        "http://10.0.0.1/data",
+        "http://[::1]/data",
+    ] {
+        let response = client
+            .post(format!(
+                "/spaces/import/test/space?uri={}",
+                urlencoding::encode(uri)
+            ))
+            .header(Header::new("authorization", token.code.clone()))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Forbidden, "{uri}");
+        let body = response.into_string().await.expect("response body");
+        assert!(body.starts_with("private_address"), "{uri}: {body}");
+    }
+    import_mock.assert_hits(0);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+async fn test_import_policy_lists() {
+    let policy = ImportPolicy {
+        allowed_hosts: vec!["*.example.com".to_string()],
+        denied_hosts: vec!["internal.example.com".to_string()],
+        ..Default::default()
+    };
+
+    let check = |uri: &str| policy.check_url(&Url::parse(uri).unwrap());
+
+    assert_eq!(check("https://data.example.com/a.json"), Ok(()));
+    assert!(matches!(
+        check("https://internal.example.com/a.json"),
+        Err(ImportPolicyError::HostNotAllowed(_))
+    ));
+    assert!(matches!(
+        check("https://example.org/a.json"),
+        Err(ImportPolicyError::HostNotAllowed(_))
+    ));
+    assert!(matches!(
+        check("ftp://data.example.com/a.json"),
+        Err(ImportPolicyError::SchemeNotAllowed(_))
+    ));
+
+    assert!(is_non_public_ip("127.0.0.1".parse().unwrap()));
+    assert!(is_non_public_ip("172.16.4.2".parse().unwrap()));
+    assert!(is_non_public_ip("100.64.0.1".parse().unwrap()));
+    assert!(is_non_public_ip("fd00::1".parse().unwrap()));
+    assert!(is_non_public_ip("::ffff:192.168.0.1".parse().unwrap()));
+    assert!(is_non_public_ip("64:ff9b::a9fe:a9fe".parse().unwrap()));
+    assert!(is_non_public_ip("2002:a9fe:a9fe::1".parse().unwrap()));
+    assert!(!is_non_public_ip("93.184.216.34".parse().unwrap()));
+    assert!(!is_non_public_ip("2606:2800:220:1::".parse().unwrap()));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_import_with_mm2() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    common::mock_remote_data(&server);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*edge.*/.*reversed.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post(format!(
+            "/spaces/import/test/space?uri={}&pattern={}&template={}",
+            urlencoding::encode(&server.url("/data")),
+            urlencoding::encode("(edge $a $b)"),
+            urlencoding::encode("(reversed $b $a)")
+        ))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    import_mock.assert();
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_import_forwards_encoded_uri() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    // a second `uri` must stay part of the checked uri rather than be fetched on its own
+    let uri = format!("{}?a=1&uri=http://169.254.169.254/", server.url("/data"));
+    let remote_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/data")
+            .query_param("a", "1")
+            .query_param("uri", "http://169.254.169.254/");
+        then.status(200).body("(edge a b)\n");
+    });
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap())
+            .body("(edge a b)\n");
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post(format!(
+            "/spaces/import/test/space?uri={}",
+            urlencoding::encode(&uri)
+        ))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    remote_mock.assert();
+    import_mock.assert();
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_import_enforces_limits_on_download() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    env::set_var("METTA_KG_IMPORT_MAX_BYTES", "16");
+    env::set_var("METTA_KG_IMPORT_MAX_SECONDS", "1");
+
+    server.mock(|when, then| {
+        when.method(GET).path("/moved");
+        then.status(302)
+            .header("location", "http://169.254.169.254/latest/meta-data/");
+    });
+    // the remote announces no size, the download itself is cut off
+    server.mock(|when, then| {
+        when.method(GET).path("/large");
+        then.status(200).body("(edge a b)\n".repeat(100));
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/slow");
+        then.status(200)
+            .delay(std::time::Duration::from_secs(3))
+            .body("(edge a b)\n");
+    });
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for (path, status, code) in [
+        ("/moved", Status::Forbidden, "redirect_not_allowed"),
+        ("/large", Status::PayloadTooLarge, "too_large"),
+        ("/slow", Status::GatewayTimeout, "timed_out"),
+        ("/missing", Status::BadGateway, "unreachable"),
+    ] {
+        let response = client
+            .post(format!(
+                "/spaces/import/test/space?uri={}",
+                urlencoding::encode(&server.url(path))
+            ))
+            .header(Header::new("authorization", token.code.clone()))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), status, "{path}");
+        let body = response.into_string().await.expect("response body");
+        assert!(body.starts_with(code), "{path}: {body}");
+    }
+    import_mock.assert_hits(0);
+
+    env::remove_var("METTA_KG_IMPORT_MAX_BYTES");
+    env::remove_var("METTA_KG_IMPORT_MAX_SECONDS");
+
+    common::teardown_database();
+}
@@ -0,0 +1,197 @@
+use api::rocket;
+use api::routes::spaces::ImportBatchResult;
+use httpmock::prelude::*;
+use httpmock::Regex;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::json;
+use serial_test::serial;
+use std::env;
+
+use crate::integrations::common;
+
+#[tokio::test]
+#[serial]
+async fn test_import_batch_success() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    common::mock_remote_data(&server);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let entries: Vec<_> = (0..6)
+        .map(|i| {
+            json!({
+                "uri": server.url(format!("/data/{i}")),
+                "namespace": format!("/test/space{i}/"),
+                "pattern": "(edge $a $b)",
+                "template": "(edge $b $a)",
+            })
+        })
+        .collect();
+
+    let response = client
+        .post("/spaces/import-batch")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&entries)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let results: Vec<ImportBatchResult> = response.into_json().await.expect("results");
+    assert_eq!(results.len(), 6);
+    for (i, result) in results.iter().enumerate() {
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.namespace, format!("/test/space{i}/"));
+    }
+    import_mock.assert_hits(6);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_import_batch_partial_failure() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    common::mock_remote_data(&server);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let entries = json!([
+        { "uri": server.url("/data"), "namespace": "/test/space/" },
+        { "uri": server.url("/data"), "namespace": "/other/space/" },
+        { "uri": server.url("/data"), "namespace": "/test/../other/" },
+        { "uri": "file:///etc/passwd", "namespace": "/test/space/" },
+        { "uri": server.url("/data"), "namespace": "/test/space/", "pattern": "$x" },
+    ]);
+
+    let response = client
+        .post("/spaces/import-batch")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&entries)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let results: Vec<ImportBatchResult> = response.into_json().await.expect("results");
+    let successes: Vec<bool> = results.iter().map(|r| r.success).collect();
+    assert_eq!(successes, vec![true, false, false, false, false]);
+    assert!(results[1].error.as_ref().unwrap().contains("Unauthorized"));
+    assert!(results[3]
+        .error
+        .as_ref()
+        .unwrap()
+        .contains("scheme_not_allowed"));
+    import_mock.assert_hits(1);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_import_batch_read_only_token() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, false);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/import-batch")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!([{ "uri": server.url("/data"), "namespace": "/test/space/" }]))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_import_batch_too_many_entries() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    common::mock_remote_data(&server);
+    env::set_var("METTA_KG_IMPORT_BATCH_MAX_ENTRIES", "3");
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let entries: Vec<_> = (0..4)
+        .map(|_| json!({ "uri": server.url("/data"), "namespace": "/test/space/" }))
+        .collect();
+    let response = client
+        .post("/spaces/import-batch")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&entries)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::BadRequest);
+    let body = response.into_string().await.expect("response body");
+    assert!(body.starts_with("too_many_entries"), "{body}");
+    import_mock.assert_hits(0);
+
+    let response = client
+        .post("/spaces/import-batch")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&entries[..3].to_vec())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    import_mock.assert_hits(3);
+
+    env::remove_var("METTA_KG_IMPORT_BATCH_MAX_ENTRIES");
+    common::teardown_database();
+}
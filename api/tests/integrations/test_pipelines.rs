@@ -0,0 +1,378 @@
+use api::jobs::{Job, JobStatus, StepStatus};
+use api::rocket;
+use httpmock::prelude::*;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::json;
+use serial_test::serial;
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+use crate::integrations::common;
+
+/// Polls the job until it is no longer running
+async fn wait_for(client: &Client, token: &str, id: &str) -> Job {
+    for _ in 0..100 {
+        let response = client
+            .get(format!("/jobs/{id}"))
+            .header(Header::new("authorization", token.to_string()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let job: Job = response.into_json().await.expect("job");
+        if job.status != JobStatus::Running {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("job {id} did not finish");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_pipeline() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let clear = server.mock(|when, then| {
+        when.method(GET).path_contains("/clear/");
+        then.status(200).body("Clear successful");
+    });
+    let upload = server.mock(|when, then| {
+        when.method(POST)
+            .path_contains("/upload/")
+            .body("(edge a b)");
+        then.status(200).body("Upload successful");
+    });
+    let transform = server.mock(|when, then| {
+        when.method(POST).path("/transform").body_contains("path");
+        then.status(200).body("Transform successful");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/export/");
+        then.status(200).body("(path a b)\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    // steps run after their dependencies, whatever their order in the document
+    let response = client
+        .post("/pipelines")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "namespaces": {"kg": "/test/kg/"},
+            "steps": [
+                {"name": "paths", "op": "export", "namespace": "$kg",
+                 "pattern": "(path $a $b)", "template": "(path $a $b)",
+                 "depends_on": ["derive"]},
+                {"name": "reset", "op": "clear", "namespace": "$kg"},
+                {"name": "load", "op": "upload", "namespace": "test/kg",
+                 "data": "(edge a b)", "depends_on": ["reset"]},
+                {"name": "derive", "op": "transform", "namespace": "$kg",
+                 "patterns": ["(edge $a $b)"], "templates": ["(path $a $b)"],
+                 "depends_on": ["load"]},
+            ]
+        }))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Accepted);
+    let job: Job = response.into_json().await.expect("job");
+    assert_eq!(job.kind, "pipeline");
+    assert_eq!(
+        job.steps
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["reset", "load", "derive", "paths"]
+    );
+
+    let job = wait_for(&client, &token.code, &job.id).await;
+    assert_eq!(job.status, JobStatus::Succeeded, "{:?}", job.steps);
+    assert!(job
+        .steps
+        .iter()
+        .all(|s| s.status == StepStatus::Succeeded && s.duration_ms.is_some()));
+    assert_eq!(job.steps[3].output.as_deref(), Some("(path a b)\n"));
+
+    clear.assert();
+    upload.assert();
+    transform.assert();
+
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_pipeline_yaml_and_failures() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let work_dir = env::temp_dir().join(format!("metta-kg-translations-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+    env::set_var(
+        "METTA_KG_TRANSLATION_PYTHON",
+        common::fake_interpreter(&work_dir, "printf '(a b c)\\n' > \"$5-output.metta\""),
+    );
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    // translated documents are fetched by the API itself
+    let document = server.mock(|when, then| {
+        when.method(GET).path("/people.nt");
+        then.status(200).body("<a> <b> <c> .\n");
+    });
+    let upload = server.mock(|when, then| {
+        when.method(POST)
+            .path_contains("/upload/")
+            .body("(a b c)\n");
+        then.status(200).body("Upload successful");
+    });
+    let transform = server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let pipeline = format!(
+        r#"
+namespaces:
+  raw: /test/raw/
+steps:
+  - name: people
+    op: translate
+    namespace: $raw
+    format: nt
+    uri: {}/people.nt
+  - name: places
+    op: translate
+    namespace: $raw
+    format: ttl
+    uri: ftp://example.org/places.ttl
+  - name: located
+    op: transform
+    namespace: $raw
+    patterns: ["(lives $p $c)"]
+    templates: ["(located $p $c)"]
+    depends_on: [places]
+"#,
+        server.base_url()
+    );
+
+    let response = client
+        .post("/pipelines")
+        .header(Header::new("authorization", token.code.clone()))
+        .header(ContentType::new("application", "yaml"))
+        .body(pipeline)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Accepted);
+    let job: Job = response.into_json().await.expect("job");
+
+    let job = wait_for(&client, &token.code, &job.id).await;
+    assert_eq!(job.status, JobStatus::Failed);
+    assert_eq!(job.message.as_deref(), Some("2 of 3 steps did not succeed"));
+
+    assert_eq!(job.steps[0].status, StepStatus::Succeeded);
+    assert_eq!(job.steps[0].output.as_deref(), Some("1 atoms loaded"));
+    assert_eq!(job.steps[1].status, StepStatus::Failed);
+    assert!(job.steps[1]
+        .message
+        .as_deref()
+        .unwrap()
+        .starts_with("scheme_not_allowed"));
+    assert_eq!(job.steps[2].status, StepStatus::Skipped);
+    assert_eq!(
+        job.steps[2].message.as_deref(),
+        Some("places did not succeed")
+    );
+
+    document.assert();
+    upload.assert();
+    transform.assert_hits(0);
+
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(&work_dir).ok();
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_pipeline_translates_csv_and_json() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    // the fake translation names the script it was asked to run
+    let work_dir = env::temp_dir().join(format!("metta-kg-translations-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+    env::set_var(
+        "METTA_KG_TRANSLATION_PYTHON",
+        common::fake_interpreter(
+            &work_dir,
+            "printf '(ran %s)\\n' \"$(basename \"$4\" .py)\" > \"$5-output.metta\"",
+        ),
+    );
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let uploads = ["csv", "json"].map(|format| {
+        server.mock(|when, then| {
+            when.method(POST)
+                .path_contains("/upload/")
+                .body(format!("(ran {format}_to_metta_run)\n"));
+            then.status(200).body("Upload successful");
+        })
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/pipelines")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "steps": [
+                {"name": "rows", "op": "translate", "namespace": "/test/raw/", "format": "csv", "data": "a,b\n"},
+                {"name": "doc", "op": "translate", "namespace": "/test/raw/", "format": "json", "data": "{\"a\": 1}"},
+            ],
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Accepted);
+    let job: Job = response.into_json().await.expect("job");
+
+    let job = wait_for(&client, &token.code, &job.id).await;
+    assert_eq!(job.status, JobStatus::Succeeded, "{:?}", job.message);
+    for upload in uploads {
+        upload.assert();
+    }
+
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(&work_dir).ok();
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_invalid_pipelines() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let read_only = common::create_test_token("/test/", true, false);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let clear = |name: &str, depends_on: &[&str]| json!({"name": name, "op": "clear", "namespace": "test", "depends_on": depends_on});
+
+    for (steps, error) in [
+        (json!([]), "no steps"),
+        (
+            json!([clear("a", &[]), clear("a", &[])]),
+            "duplicate step a",
+        ),
+        (
+            json!([clear("a", &["b"])]),
+            "step a depends on unknown step b",
+        ),
+        (
+            json!([clear("a", &["b"]), clear("b", &["a"]), clear("c", &[])]),
+            "cyclic dependencies between a, b",
+        ),
+        (
+            json!([{"name": "a", "op": "clear", "namespace": "$kg"}]),
+            "step a refers to unknown namespace $kg",
+        ),
+        (
+            json!([{"name": "a", "op": "transform", "namespace": "test",
+                    "patterns": ["(edge $a $b)"], "templates": ["(path $a $c)"]}]),
+            "step a: ",
+        ),
+        (
+            json!([{"name": "a", "op": "translate", "namespace": "test", "format": "nt"}]),
+            "step a: either uri or data must be given",
+        ),
+        (json!([{"name": "a", "op": "merge"}]), "unknown variant"),
+        // formats without a translation are named in the error, with those there are
+        (
+            json!([{"name": "a", "op": "translate", "namespace": "test", "format": "xlsx", "data": ""}]),
+            "unknown variant `xlsx`, expected one of",
+        ),
+    ] {
+        let response = client
+            .post("/pipelines")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&json!({ "steps": steps }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest, "{steps}");
+        let body = response.into_string().await.expect("response body");
+        assert!(
+            body.starts_with(&format!("invalid_pipeline: {error}"))
+                || (error.starts_with("unknown variant") && body.contains(error)),
+            "{body}"
+        );
+    }
+
+    // permissions are checked for every step before anything runs
+    for (token, namespace, error) in [
+        (
+            &token,
+            "other",
+            "unauthorized: no write permission on other",
+        ),
+        (
+            &read_only,
+            "test",
+            "unauthorized: no write permission on test",
+        ),
+    ] {
+        let response = client
+            .post("/pipelines")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&json!({"steps": [
+                {"name": "a", "op": "export", "namespace": "test"},
+                {"name": "b", "op": "clear", "namespace": namespace},
+            ]}))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+        let body = response.into_string().await.expect("response body");
+        assert_eq!(body, error);
+    }
+
+    common::teardown_database();
+}
@@ -0,0 +1,188 @@
+use api::rocket;
+use api::routes::spaces::QueryInput;
+use httpmock::prelude::*;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::{json, Value};
+use serial_test::serial;
+
+use crate::integrations::common;
+
+#[tokio::test]
+#[serial]
+async fn test_query_bindings() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    // offset + limit matches are asked for, the offset is skipped by the API
+    let export = server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("bindings")
+            .query_param("max_write", "3");
+        then.status(200).body(concat!(
+            "(bindings alice \"Alice \\\"A\\\"\")\n",
+            "(bindings bob (name \"Bob\"))\n",
+            "(bindings carol (sub (suba727d4f9-836a-4e4c-9480 (name carol))))\n",
+        ));
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/query/test/space")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&QueryInput {
+            pattern: "(person $p $n)".to_string(),
+            limit: Some(2),
+            offset: 1,
+        })
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    export.assert();
+    let rows: Value = response.into_json().await.expect("bindings");
+    assert_eq!(
+        rows,
+        json!([
+            {"p": "bob", "n": "(name \"Bob\")"},
+            {"p": "carol", "n": "(name carol)"},
+        ])
+    );
+
+    let response = client
+        .post("/spaces/query/test/space")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&QueryInput {
+            pattern: "(person $p".to_string(),
+            ..Default::default()
+        })
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::BadRequest);
+    let body = response.into_string().await.expect("response body");
+    assert!(body.starts_with("invalid_pattern: "));
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_large_offset() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let export = server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("bindings")
+            .query_param("max_write", usize::MAX.to_string());
+        then.status(200).body("(bindings alice)\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/query/test/space")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&QueryInput {
+            pattern: "(person $p)".to_string(),
+            limit: Some(10),
+            offset: usize::MAX - 1,
+        })
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    export.assert();
+    let rows: Value = response.into_json().await.expect("bindings");
+    assert_eq!(rows, json!([]));
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_string_values() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(GET).path_contains("bindings");
+        then.status(200)
+            .body("(bindings alice \"Alice \\\"A\\\"\")\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/query/test/space")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&QueryInput {
+            pattern: "(person $p $n)".to_string(),
+            ..Default::default()
+        })
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let rows: Value = response.into_json().await.expect("bindings");
+    assert_eq!(rows, json!([{"p": "alice", "n": "Alice \"A\""}]));
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_unauthorized() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/query/other/space")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&QueryInput {
+            pattern: "$x".to_string(),
+            ..Default::default()
+        })
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    common::teardown_database();
+}
@@ -0,0 +1,249 @@
+use api::jobs::{Job, JobStatus};
+use api::rocket;
+use api::routes::rules::RuleSetInput;
+use api::routes::spaces::Mm2InputMulti;
+use httpmock::prelude::*;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use serial_test::serial;
+use std::time::Duration;
+
+use crate::integrations::common;
+
+fn transitive_closure() -> RuleSetInput {
+    RuleSetInput {
+        rules: vec![
+            Mm2InputMulti {
+                patterns: vec!["(edge $a $b)".to_string()],
+                templates: vec!["(path $a $b)".to_string()],
+            },
+            Mm2InputMulti {
+                patterns: vec!["(path $a $b)".to_string(), "(edge $b $c)".to_string()],
+                templates: vec!["(path $a $c)".to_string()],
+            },
+        ],
+        ..Default::default()
+    }
+}
+
+/// Polls the job until it is no longer running
+async fn wait_for(client: &Client, token: &str, id: &str) -> Job {
+    for _ in 0..100 {
+        let response = client
+            .get(format!("/jobs/{id}"))
+            .header(Header::new("authorization", token.to_string()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let job: Job = response.into_json().await.expect("job");
+        if job.status != JobStatus::Running {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("job {id} did not finish");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rules_converge() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let transform = server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("Transform successful");
+    });
+    let export = server.mock(|when, then| {
+        when.method(GET).path_contains("/export/");
+        then.status(200).body("(edge a b)\n(path a b)\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/rules/test/graph")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&transitive_closure())
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Accepted);
+    let job: Job = response.into_json().await.expect("job");
+    assert_eq!(job.kind, "rules");
+    assert_eq!(job.namespace, "/test/graph/");
+
+    let job = wait_for(&client, &token.code, &job.id).await;
+    assert_eq!(job.status, JobStatus::Converged, "{:?}", job.message);
+    assert_eq!(job.initial_atoms, Some(2));
+    assert_eq!(job.iterations.len(), 1);
+    assert_eq!(job.iterations[0].atoms, 2);
+    assert_eq!(job.iterations[0].added, 0);
+    assert!(job.end_timestamp.is_some());
+
+    // one transform per rule, counted before and after the iteration
+    transform.assert_hits(2);
+    export.assert_hits(2);
+
+    let response = client
+        .get("/jobs")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let jobs: Vec<Job> = response.into_json().await.expect("jobs");
+    assert!(jobs.iter().any(|j| j.id == job.id));
+
+    // finished jobs can no longer be cancelled
+    let response = client
+        .delete(format!("/jobs/{}", job.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Conflict);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rules_limits_and_failures() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let other = common::create_test_token("/other/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("Transform successful");
+    });
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("graph");
+        then.status(200).body("(edge a b)\n");
+    });
+    // the atoms of this namespace cannot be counted
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("broken");
+        then.status(500).body("(edge a");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/rules/test/graph")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&RuleSetInput {
+            max_iterations: Some(0),
+            ..transitive_closure()
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Accepted);
+    let job: Job = response.into_json().await.expect("job");
+
+    let job = wait_for(&client, &token.code, &job.id).await;
+    assert_eq!(job.status, JobStatus::LimitReached);
+    assert_eq!(
+        job.message.as_deref(),
+        Some("no fixpoint after 0 iterations")
+    );
+    assert!(job.iterations.is_empty());
+
+    let response = client
+        .post("/spaces/rules/test/broken")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&transitive_closure())
+        .dispatch()
+        .await;
+    let job: Job = response.into_json().await.expect("job");
+
+    let job = wait_for(&client, &token.code, &job.id).await;
+    assert_eq!(job.status, JobStatus::Failed);
+    assert!(job
+        .message
+        .expect("message")
+        .starts_with("Failed to count atoms"));
+    assert_eq!(job.initial_atoms, None);
+
+    // jobs are only visible within the namespace of the token
+    let response = client
+        .get(format!("/jobs/{}", job.id))
+        .header(Header::new("authorization", other.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_invalid_rules() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let read_only = common::create_test_token("/test/", true, false);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for rules in [
+        RuleSetInput::default(),
+        RuleSetInput {
+            rules: vec![Mm2InputMulti {
+                patterns: vec!["(edge $a $b)".to_string()],
+                templates: vec!["(path $a $c)".to_string()],
+            }],
+            ..Default::default()
+        },
+    ] {
+        let response = client
+            .post("/spaces/rules/test/graph")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&rules)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let body = response.into_string().await.expect("response body");
+        assert!(body.starts_with("invalid_rules: "), "{body}");
+    }
+
+    for (token, path) in [
+        (&token, "/spaces/rules/other"),
+        (&read_only, "/spaces/rules/test"),
+    ] {
+        let response = client
+            .post(path)
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&transitive_closure())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    common::teardown_database();
+}
@@ -0,0 +1,216 @@
+use api::db::establish_connection;
+use api::model::Snapshot;
+use api::mork_api::Namespace;
+use api::rocket;
+use api::schema::tokens;
+use diesel::prelude::*;
+use httpmock::prelude::*;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use serial_test::serial;
+use std::env;
+
+use crate::integrations::common;
+
+const CONTENT: &str = "(edge a b)\n(edge b c)\n";
+
+fn tag(namespace: &str) -> String {
+    Namespace::from_path_string(namespace).data_tag()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_snapshot_and_restore() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    // the namespace and the namespaces below it, with their wrappers below /test/kg/
+    let content = format!(
+        "({} (edge a b))\n(sub ({} (edge b c)))\n",
+        tag("/test/kg/"),
+        tag("/test/kg/sub/")
+    );
+    let subtree = urlencoding::encode("(test (kg $x))").into_owned();
+
+    let export = server.mock(|when, then| {
+        when.method(GET)
+            .path(format!("/export/{subtree}/{}/", urlencoding::encode("$x")));
+        then.status(200).body(&content);
+    });
+    let clear = server.mock(|when, then| {
+        when.method(GET).path(format!("/clear/{subtree}"));
+        then.status(200).body("Clear successful");
+    });
+    let upload = server.mock(|when, then| {
+        when.method(POST)
+            .path(format!("/upload/{}/{subtree}", urlencoding::encode("$x")))
+            .body(&content);
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/snapshot/test/kg?description=before%20cleanup")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let snapshot: Snapshot = response.into_json().await.expect("snapshot");
+    assert_eq!(snapshot.namespace, "/test/kg/");
+    assert_eq!(snapshot.description, "before cleanup");
+    assert_eq!(snapshot.atoms, 2);
+    assert_eq!(snapshot.bytes, content.len() as i64);
+    assert_eq!(snapshot.token, Some(token.id));
+    export.assert();
+
+    // the content lives next to the database, in a file of its own
+    let file = env::temp_dir()
+        .join("metta-kg-test-snapshots")
+        .join(format!("{}.metta", snapshot.id));
+    assert_eq!(
+        std::fs::read_to_string(&file).expect("snapshot file"),
+        content
+    );
+
+    let response = client
+        .get("/snapshots")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let snapshots: Vec<Snapshot> = response.into_json().await.expect("snapshots");
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].id, snapshot.id);
+
+    let response = client
+        .post(format!("/snapshots/{}/restore", snapshot.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    clear.assert();
+    upload.assert();
+
+    // snapshots outlive the token that took them
+    let other = common::create_test_token("/test/", true, true);
+    diesel::delete(tokens::table.filter(tokens::id.eq(token.id)))
+        .execute(&mut establish_connection())
+        .expect("delete token");
+
+    let response = client
+        .get("/snapshots")
+        .header(Header::new("authorization", other.code.clone()))
+        .dispatch()
+        .await;
+    let snapshots: Vec<Snapshot> = response.into_json().await.expect("snapshots");
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].token, None);
+
+    let response = client
+        .delete(format!("/snapshots/{}", snapshot.id))
+        .header(Header::new("authorization", other.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert!(!file.exists());
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_snapshot_failures() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let read_only = common::create_test_token("/test/", true, false);
+    let other = common::create_test_token("/other/", true, true);
+
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("broken");
+        then.status(200).body("(edge a");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/export/");
+        then.status(200).body(CONTENT);
+    });
+    let clear = server.mock(|when, then| {
+        when.method(GET).path_contains("/clear/");
+        then.status(200).body("Clear successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for (token, path, status) in [
+        (&token, "/spaces/snapshot/other", Status::Unauthorized),
+        (
+            &token,
+            "/spaces/snapshot/test/broken",
+            Status::InternalServerError,
+        ),
+    ] {
+        let response = client
+            .post(path)
+            .header(Header::new("authorization", token.code.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), status, "{path}");
+    }
+
+    env::set_var("METTA_KG_SNAPSHOT_MAX_BYTES", "5");
+    let response = client
+        .post("/spaces/snapshot/test/kg")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+    env::remove_var("METTA_KG_SNAPSHOT_MAX_BYTES");
+
+    let response = client
+        .post("/spaces/snapshot/test/kg")
+        .header(Header::new("authorization", read_only.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let snapshot: Snapshot = response.into_json().await.expect("snapshot");
+
+    // restoring writes, and snapshots are only visible within the namespace of the token
+    for (token, status) in [
+        (&read_only, Status::Unauthorized),
+        (&other, Status::NotFound),
+    ] {
+        let response = client
+            .post(format!("/snapshots/{}/restore", snapshot.id))
+            .header(Header::new("authorization", token.code.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), status);
+    }
+    clear.assert_hits(0);
+
+    let response = client
+        .delete(format!("/snapshots/{}", snapshot.id))
+        .header(Header::new("authorization", other.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    common::teardown_database();
+}
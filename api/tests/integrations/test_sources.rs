@@ -0,0 +1,248 @@
+use api::model::{ImportRun, ImportSource};
+use api::rocket;
+use api::schedule::Schedule;
+use chrono::NaiveDate;
+use httpmock::prelude::*;
+use httpmock::Regex;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::json;
+use serial_test::serial;
+use std::str::FromStr;
+
+use crate::integrations::common;
+
+#[tokio::test]
+#[serial]
+async fn test_create_and_run_source() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    common::mock_remote_data(&server);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let clear_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_matches(Regex::new(r"/clear/.*").unwrap());
+        then.status(200).body("Clear successful");
+    });
+    let import_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/sources")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "uri": server.url("/data"),
+            "namespace": "test/mirror",
+            "schedule": "@daily",
+            "clear_before": true,
+        }))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let source: ImportSource = response.into_json().await.expect("source");
+    assert_eq!(source.namespace, "/test/mirror/");
+    assert!(source.next_run.is_some());
+
+    let response = client
+        .get("/sources")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let sources: Vec<ImportSource> = response.into_json().await.expect("sources");
+    assert_eq!(sources.len(), 1);
+
+    let response = client
+        .post(format!("/sources/{}/run", source.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let run: ImportRun = response.into_json().await.expect("run");
+    assert_eq!(run.success, Some(true));
+    assert!(run.end_timestamp.is_some());
+    clear_mock.assert();
+    import_mock.assert();
+
+    let response = client
+        .get(format!("/sources/{}/runs", source.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let runs: Vec<ImportRun> = response.into_json().await.expect("runs");
+    assert_eq!(runs.len(), 1);
+
+    let response = client
+        .delete(format!("/sources/{}", source.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_failed_run_is_recorded() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/sources")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "uri": "http://unresolvable.invalid/data",
+            "namespace": "/test/mirror/",
+            "schedule": "0 3 * * 1-5",
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let source: ImportSource = response.into_json().await.expect("source");
+
+    let response = client
+        .post(format!("/sources/{}/run", source.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let run: ImportRun = response.into_json().await.expect("run");
+    assert_eq!(run.success, Some(false));
+    assert!(run.message.unwrap().contains("unresolvable_host"));
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_create_source_validation() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let other_token = common::create_test_token("/other/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let base = json!({
+        "uri": server.url("/data"),
+        "namespace": "/test/mirror/",
+        "schedule": "*/30 * * * *",
+    });
+
+    for (field, value, status) in [
+        ("namespace", json!("/other/mirror/"), Status::Unauthorized),
+        ("namespace", json!("/test/../other/"), Status::BadRequest),
+        ("schedule", json!("every day"), Status::BadRequest),
+        ("schedule", json!("61 * * * *"), Status::BadRequest),
+        ("uri", json!("file:///etc/passwd"), Status::Forbidden),
+        ("pattern", json!("(edge $a $b)"), Status::BadRequest),
+    ] {
+        let mut input = base.clone();
+        input[field] = value;
+
+        let response = client
+            .post("/sources")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&input)
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), status, "{input}");
+    }
+
+    let response = client
+        .post("/sources")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&base)
+        .dispatch()
+        .await;
+    let source: ImportSource = response.into_json().await.expect("source");
+
+    // sources are only visible within the namespace of a token
+    let response = client
+        .post(format!("/sources/{}/run", source.id))
+        .header(Header::new("authorization", other_token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    common::teardown_database();
+}
+
+#[test]
+fn test_schedule_next_after() {
+    let at = |y, m, d, h, min| {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    };
+    let next = |schedule: &str, time| Schedule::from_str(schedule).unwrap().next_after(time);
+
+    // 2026-10-19 is a Monday
+    let now = at(2026, 10, 19, 10, 7);
+
+    assert_eq!(next("* * * * *", now), Some(at(2026, 10, 19, 10, 8)));
+    assert_eq!(next("*/15 * * * *", now), Some(at(2026, 10, 19, 10, 15)));
+    assert_eq!(next("@hourly", now), Some(at(2026, 10, 19, 11, 0)));
+    assert_eq!(next("@daily", now), Some(at(2026, 10, 20, 0, 0)));
+    assert_eq!(next("30 2 * * 0", now), Some(at(2026, 10, 25, 2, 30)));
+    assert_eq!(next("0 0 1 1 *", now), Some(at(2027, 1, 1, 0, 0)));
+    assert_eq!(next("0 9-17/4 * * 1-5", now), Some(at(2026, 10, 19, 13, 0)));
+    // either day-of-month or day-of-week
+    assert_eq!(next("0 0 1 * 3", now), Some(at(2026, 10, 21, 0, 0)));
+    assert_eq!(next("0 0 30 2 *", now), None);
+    // fields covering every day do not restrict, however they are written
+    for schedule in [
+        "0 0 1 * */1",
+        "0 0 1 * 0-6",
+        "0 0 1 * ?",
+        "0 0 1-31 * 3",
+        "0 0 ? * 3",
+    ] {
+        let expected = if schedule.ends_with('3') {
+            at(2026, 10, 21, 0, 0)
+        } else {
+            at(2026, 11, 1, 0, 0)
+        };
+        assert_eq!(next(schedule, now), Some(expected), "{schedule}");
+    }
+
+    assert!(Schedule::from_str("* * * *").is_err());
+    assert!(Schedule::from_str("*/0 * * * *").is_err());
+    assert!(Schedule::from_str("5-1 * * * *").is_err());
+    assert!(Schedule::from_str("* 24 * * *").is_err());
+}
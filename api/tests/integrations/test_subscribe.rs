@@ -0,0 +1,160 @@
+use api::db::establish_connection;
+use api::events::{NamespaceEvent, Operation};
+use api::rocket;
+use api::schema::tokens;
+use diesel::prelude::*;
+use httpmock::prelude::*;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json;
+use serial_test::serial;
+use std::env;
+use std::time::Duration;
+
+use crate::integrations::common;
+
+/// `(event, data)` of every server-sent event in `body`
+fn parse_events(body: &str) -> Vec<(String, String)> {
+    body.split("\n\n")
+        .filter_map(|message| {
+            let mut event = None;
+            let mut data = None;
+            for line in message.lines() {
+                if let Some(value) = line.strip_prefix("event:") {
+                    event = Some(value.trim().to_string());
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    data = Some(value.trim().to_string());
+                }
+            }
+            Some((event?, data?))
+        })
+        .collect()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_subscribe() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    env::set_var("METTA_KG_SUBSCRIBE_REVALIDATE_SECONDS", "1");
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/upload/");
+        then.status(200).body("Upload successful");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/clear/");
+        then.status(200).body("Clear successful");
+    });
+    common::mock_remote_data(&server);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let subscription = client
+        .get("/spaces/subscribe/test/raw")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(subscription.status(), Status::Ok);
+
+    let import = format!(
+        "/spaces/import/test/raw?uri={}",
+        urlencoding::encode(&server.url("/data"))
+    );
+    for path in [
+        "/spaces/upload/test/raw",
+        "/spaces/upload/test/other",
+        import.as_str(),
+        "/spaces/clear/test/raw/sub?expr=$x",
+    ] {
+        let request = if path.contains("clear") || path.contains("import") {
+            client.post(path)
+        } else {
+            client.post(path).body("(edge a b)")
+        };
+        let response = request
+            .header(Header::new("authorization", token.code.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    // taking the read permission away ends the stream at the next check
+    diesel::update(tokens::table.filter(tokens::id.eq(token.id)))
+        .set(tokens::permission_read.eq(false))
+        .execute(&mut establish_connection())
+        .expect("revoke read permission");
+
+    let body = tokio::time::timeout(Duration::from_secs(10), subscription.into_string())
+        .await
+        .expect("stream ends after revocation")
+        .expect("stream body");
+    let events = parse_events(&body);
+
+    assert_eq!(
+        events
+            .iter()
+            .map(|(event, _)| event.as_str())
+            .collect::<Vec<_>>(),
+        vec!["upload", "import", "clear", "revoked"],
+        "{body}"
+    );
+
+    let upload: NamespaceEvent = serde_json::from_str(&events[0].1).expect("event");
+    assert_eq!(upload.operation, Operation::Upload);
+    assert_eq!(upload.namespace, "/test/raw/");
+    assert_eq!(upload.token, token.id);
+    assert_eq!(upload.size, Some(10));
+
+    let import: NamespaceEvent = serde_json::from_str(&events[1].1).expect("event");
+    assert_eq!(import.operation, Operation::Import);
+    assert_eq!(import.namespace, "/test/raw/");
+    assert_eq!(import.size, Some(11));
+
+    let clear: NamespaceEvent = serde_json::from_str(&events[2].1).expect("event");
+    assert_eq!(clear.namespace, "/test/raw/sub/");
+    assert_eq!(clear.size, None);
+
+    env::remove_var("METTA_KG_SUBSCRIBE_REVALIDATE_SECONDS");
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_subscribe_unauthorized() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let write_only = common::create_test_token("/test/", false, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for (token, path) in [
+        (&token, "/spaces/subscribe/other"),
+        (&write_only, "/spaces/subscribe/test"),
+    ] {
+        let response = client
+            .get(path)
+            .header(Header::new("authorization", token.code.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    common::teardown_database();
+}
@@ -5,7 +5,8 @@ use rocket::local::asynchronous::Client;
 use serial_test::serial;
 
 use crate::integrations::common;
-use api::routes::spaces::Mm2InputMulti;
+use api::routes::spaces::{Mm2InputMulti, TransformPreview};
+use rocket::serde::json::serde_json::json;
 
 #[tokio::test]
 #[serial]
@@ -206,3 +207,177 @@ async fn test_namespace_mismatch() {
 
     common::teardown_database();
 }
+
+#[tokio::test]
+#[serial]
+async fn test_transform_preview() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    // a preview does not write to the namespace
+    let token = common::create_test_token("/test/", true, false);
+
+    // templates go to a scratch namespace, which is read back and cleared
+    let transform_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/transform")
+            .body_contains("_scratch");
+        then.status(200).body("Transform successful");
+    });
+    let export_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("_scratch");
+        then.status(200)
+            .body("(copy a)\n(copy b)\n(copy (c d))\n(copy e)\n");
+    });
+    // atoms the namespace already holds are not counted
+    let target_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("space");
+        then.status(200).body("(copy  e)\n(edge a b)\n");
+    });
+    let clear_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/clear/")
+            .path_contains("_scratch");
+        then.status(200).body("Clear successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let mm2_input = Mm2InputMulti {
+        patterns: vec!["$x".to_string()],
+        templates: vec!["(copy $x)".to_string()],
+    };
+
+    let response = client
+        .post("/spaces/transform-preview/test/space?sample=2")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&mm2_input)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let preview: TransformPreview = response.into_json().await.expect("preview");
+    assert_eq!(
+        preview,
+        TransformPreview {
+            count: 3,
+            sample: vec!["(copy a)".to_string(), "(copy b)".to_string()],
+        }
+    );
+    transform_mock.assert();
+    export_mock.assert();
+    target_mock.assert();
+    clear_mock.assert();
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_cross_transform() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let read_only = common::create_test_token("/test/", true, false);
+
+    // every pattern and template is wrapped in its own namespace
+    let transform_mock = server.mock(|when, then| {
+        when.method(POST).path("/transform").body(concat!(
+            "(transform ",
+            "(, (test (raw1 (raw1a727d4f9-836a-4e4c-9480 (edge $a $b)))) ",
+            "(test (raw2 (raw2a727d4f9-836a-4e4c-9480 (edge $b $c))))) ",
+            "(, (test (curated (curateda727d4f9-836a-4e4c-9480 (path $a $c))))))"
+        ));
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let input = json!({
+        "patterns": [
+            {"namespace": "/test/raw1/", "pattern": "(edge $a $b)"},
+            {"namespace": "/test/raw2/", "pattern": "(edge $b $c)"},
+        ],
+        "templates": [
+            {"namespace": "/test/curated/", "template": "(path $a $c)"},
+        ],
+    });
+
+    let response = client
+        .post("/spaces/cross-transform")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&input)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().await.unwrap(), "true");
+    transform_mock.assert();
+
+    // reading the sources is not enough
+    let response = client
+        .post("/spaces/cross-transform")
+        .header(Header::new("authorization", read_only.code.clone()))
+        .json(&input)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(
+        response.into_string().await.unwrap(),
+        "unauthorized: no write permission on /test/curated/"
+    );
+
+    for (input, status) in [
+        // a source outside of the token namespace
+        (
+            json!({
+                "patterns": [{"namespace": "/other/", "pattern": "$x"}],
+                "templates": [{"namespace": "/test/curated/", "template": "$x"}],
+            }),
+            Status::Unauthorized,
+        ),
+        (
+            json!({
+                "patterns": [{"namespace": "/test/raw1/", "pattern": "(edge $a $b)"}],
+                "templates": [{"namespace": "/test/curated/", "template": "(path $a $c)"}],
+            }),
+            Status::BadRequest,
+        ),
+        (
+            json!({
+                "patterns": [{"namespace": "/test/../", "pattern": "$x"}],
+                "templates": [{"namespace": "/test/curated/", "template": "$x"}],
+            }),
+            Status::BadRequest,
+        ),
+    ] {
+        let response = client
+            .post("/spaces/cross-transform")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&input)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), status, "{input}");
+    }
+
+    transform_mock.assert_hits(1);
+
+    common::teardown_database();
+}
@@ -0,0 +1,581 @@
+use api::rocket;
+use api::routes::translations::{TranslationError, TranslationLimiter, TranslationOutput};
+use api::translation_runner::{RunError, TranslationRunner};
+use httpmock::prelude::*;
+use httpmock::Regex;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use serial_test::serial;
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::integrations::common;
+
+#[tokio::test]
+#[serial]
+async fn test_translation_requires_token() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let read_token = common::create_test_token("/test/", true, false);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/nt?dummy=")
+        .body("_:a <http://xmlns.com/foaf/0.1/name> \"Alice\" .")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/translations/nt?dummy=")
+        .header(Header::new("authorization", read_token.code.clone()))
+        .body("_:a <http://xmlns.com/foaf/0.1/name> \"Alice\" .")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_translation_size_limit() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    env::set_var("METTA_KG_TRANSLATION_MAX_BYTES", "16");
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/csv?direction=Row&delimiter=,")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("a,b,c\n1,2,3\n4,5,6\n7,8,9\n")
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+    let error: TranslationError = response.into_json().await.expect("error body");
+    assert_eq!(error.error, "too_large");
+
+    env::remove_var("METTA_KG_TRANSLATION_MAX_BYTES");
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_translation_quota() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    env::set_var("METTA_KG_TRANSLATION_DAILY_QUOTA", "0");
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/n3?dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(":a :b :c .")
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::TooManyRequests);
+    let error: TranslationError = response.into_json().await.expect("error body");
+    assert_eq!(error.error, "quota_exceeded");
+
+    env::remove_var("METTA_KG_TRANSLATION_DAILY_QUOTA");
+    common::teardown_database();
+}
+
+#[test]
+fn test_translation_limiter() {
+    let limiter = TranslationLimiter::default();
+
+    let first = limiter.acquire(1, 2).expect("free slot");
+    let second = limiter.acquire(1, 2).expect("free slot");
+    assert!(limiter.acquire(1, 2).is_none());
+
+    // slots are per token
+    let other = limiter.acquire(2, 2);
+    assert!(other.is_some());
+
+    drop(first);
+    assert!(limiter.acquire(1, 2).is_some());
+    drop(second);
+}
+
+fn python_available() -> bool {
+    std::process::Command::new("python3")
+        .arg("--version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+fn test_runner(script: &str) -> (TranslationRunner, String) {
+    let work_dir = env::temp_dir().join(format!("metta-kg-runner-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+    let script_path = work_dir.join("script.py");
+    fs::write(&script_path, script).expect("script");
+
+    let runner = TranslationRunner {
+        python: "python3".into(),
+        work_dir,
+        timeout: Duration::from_secs(5),
+        max_memory_bytes: 512 * 1024 * 1024,
+    };
+
+    (runner, script_path.to_string_lossy().to_string())
+}
+
+#[tokio::test]
+async fn test_translation_runner_success_and_cleanup() {
+    if !python_available() {
+        eprintln!("Warning: python3 not available, skipping test");
+        return;
+    }
+    let (runner, script) = test_runner(
+        "import os, sys\n\
+         assert 'DATABASE_URL' not in os.environ\n\
+         open(sys.argv[1] + '-output.metta', 'w').write(open(sys.argv[1] + '.nt').read())\n",
+    );
+    env::set_var("DATABASE_URL", "postgres://secret");
+
+    let workspace = runner.workspace().expect("workspace");
+    fs::write(workspace.input("nt"), "(a b c)").expect("input");
+
+    let result = runner
+        .run(&script, &[workspace.stem().to_string_lossy().to_string()])
+        .await;
+    assert!(result.is_ok(), "{result:?}");
+    assert_eq!(
+        fs::read_to_string(workspace.output()).expect("output"),
+        "(a b c)"
+    );
+
+    let dir = workspace.dir().to_path_buf();
+    drop(workspace);
+    assert!(!dir.exists());
+
+    fs::remove_dir_all(&runner.work_dir).ok();
+}
+
+#[tokio::test]
+async fn test_translation_runner_failure_diagnostics() {
+    if !python_available() {
+        eprintln!("Warning: python3 not available, skipping test");
+        return;
+    }
+    let (runner, script) = test_runner("raise ValueError('bad input on line 3')\n");
+
+    match runner.run(&script, &[]).await {
+        Err(RunError::Failed(diagnostics)) => {
+            assert_eq!(diagnostics.exit_code, Some(1));
+            assert!(!diagnostics.timed_out);
+            assert_eq!(
+                diagnostics.exception.as_deref(),
+                Some("ValueError: bad input on line 3")
+            );
+            assert!(diagnostics
+                .stderr
+                .iter()
+                .any(|l| l.starts_with("Traceback")));
+        }
+        other => panic!("expected failure, got {other:?}"),
+    }
+
+    fs::remove_dir_all(&runner.work_dir).ok();
+}
+
+#[tokio::test]
+async fn test_translation_runner_limits() {
+    if !python_available() {
+        eprintln!("Warning: python3 not available, skipping test");
+        return;
+    }
+    let (mut runner, script) = test_runner(
+        "import sys, time\nprint('still loading', file=sys.stderr, flush=True)\ntime.sleep(30)\n",
+    );
+    runner.timeout = Duration::from_millis(500);
+
+    let started = Instant::now();
+    match runner.run(&script, &[]).await {
+        Err(RunError::TimedOut(diagnostics)) => {
+            assert!(diagnostics.timed_out);
+            assert_eq!(diagnostics.stderr, vec!["still loading".to_string()]);
+        }
+        other => panic!("expected timeout, got {other:?}"),
+    }
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    fs::write(&script, "data = bytearray(1024 * 1024 * 1024)\n").expect("script");
+    match runner.run(&script, &[]).await {
+        Err(RunError::Failed(diagnostics)) => {
+            assert_eq!(
+                diagnostics
+                    .exception
+                    .as_deref()
+                    .map(|e| e.starts_with("MemoryError")),
+                Some(true),
+                "{diagnostics:?}"
+            );
+        }
+        other => panic!("expected memory error, got {other:?}"),
+    }
+
+    fs::remove_dir_all(&runner.work_dir).ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_translation_failure_is_reported() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    if !python_available() {
+        eprintln!("Warning: python3 not available, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let work_dir = env::temp_dir().join(format!("metta-kg-translations-{}", uuid::Uuid::new_v4()));
+    env::set_var("METTA_KG_TRANSLATION_PYTHON", "python3");
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/nt?dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("this is not n-triples")
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let error: TranslationError = response.into_json().await.expect("error body");
+    assert_eq!(error.error, "translation_failed");
+    let diagnostics = error.diagnostics.expect("diagnostics");
+    assert_eq!(diagnostics.exit_code, Some(1));
+    assert!(diagnostics.exception.is_some());
+
+    // the workspace is gone even though the translation failed
+    assert_eq!(fs::read_dir(&work_dir).expect("work dir").count(), 0);
+
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(&work_dir).ok();
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_translation_into_namespace() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let work_dir = env::temp_dir().join(format!("metta-kg-translations-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+    env::set_var(
+        "METTA_KG_TRANSLATION_PYTHON",
+        common::fake_interpreter(
+            &work_dir,
+            "printf '(a b c)\\n(d e \"f\")\\n' > \"$5-output.metta\"",
+        ),
+    );
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    let upload_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap())
+            .body("(a b c)\n(d e \"f\")\n");
+        then.status(200).body("Upload successful");
+    });
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    // without a namespace the MeTTa comes back as a plain string
+    let response = client
+        .post("/translations/nt?dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("_:a <http://xmlns.com/foaf/0.1/name> \"Alice\" .")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let metta: String = response.into_json().await.expect("metta");
+    assert_eq!(metta, "(a b c)\n(d e \"f\")\n");
+    upload_mock.assert_hits(0);
+
+    let response = client
+        .post("/translations/nt?namespace=/test/people/&dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("_:a <http://xmlns.com/foaf/0.1/name> \"Alice\" .")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let output: TranslationOutput = response.into_json().await.expect("output");
+    assert_eq!(
+        output,
+        TranslationOutput::Loaded {
+            namespace: "/test/people/".to_string(),
+            atoms: 2
+        }
+    );
+    upload_mock.assert_hits(1);
+
+    let response = client
+        .post("/translations/nt?namespace=/other/&dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("_:a <http://xmlns.com/foaf/0.1/name> \"Alice\" .")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/translations/nt?namespace=/test/not valid/&dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("_:a <http://xmlns.com/foaf/0.1/name> \"Alice\" .")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+    upload_mock.assert_hits(1);
+
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(&work_dir).ok();
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_translation_json_parameters() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let work_dir = env::temp_dir().join(format!("metta-kg-translations-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+    env::set_var(
+        "METTA_KG_TRANSLATION_PYTHON",
+        common::fake_interpreter(
+            &work_dir,
+            "echo \"(script $4) (args $6 $7 $8 \\\"$9\\\")\" > \"$5-output.metta\"",
+        ),
+    );
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/json")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(r#"{"a": [1, 2]}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let metta: String = response.into_json().await.expect("metta");
+    assert_eq!(
+        metta,
+        "(script translations/src/json_to_metta_run.py) (args indexed nested false \"\")\n"
+    );
+
+    let response = client
+        .post("/translations/json?arrays=Repeated&nesting=Path&lines=true&root=data.items")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("{\"data\": {\"items\": [1]}}\n")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let metta: String = response.into_json().await.expect("metta");
+    assert_eq!(
+        metta,
+        "(script translations/src/json_to_metta_run.py) (args repeated path true \"data.items\")\n"
+    );
+
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(&work_dir).ok();
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_translation_turtle_and_rdfxml() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let work_dir = env::temp_dir().join(format!("metta-kg-translations-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+    // reports the script, the input file it was given and the rdflib format
+    env::set_var(
+        "METTA_KG_TRANSLATION_PYTHON",
+        common::fake_interpreter(
+            &work_dir,
+            "test -f \"$5.$6\" && echo \"(script $4) (input $6) (format $7)\" > \"$5-output.metta\"",
+        ),
+    );
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/ttl?dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("@prefix foaf: <http://xmlns.com/foaf/0.1/> .\n_:a foaf:name \"Alice\" .\n")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let metta: String = response.into_json().await.expect("metta");
+    assert_eq!(
+        metta,
+        "(script translations/src/rdf_to_metta_run.py) (input ttl) (format turtle)\n"
+    );
+
+    let response = client
+        .post("/translations/rdfxml?dummy=")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\"/>")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let metta: String = response.into_json().await.expect("metta");
+    assert_eq!(
+        metta,
+        "(script translations/src/rdf_to_metta_run.py) (input rdf) (format xml)\n"
+    );
+
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(&work_dir).ok();
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_translation_csv_parameters() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let work_dir = env::temp_dir().join(format!("metta-kg-translations-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+    // writes the script parameters one per line
+    env::set_var(
+        "METTA_KG_TRANSLATION_PYTHON",
+        common::fake_interpreter(
+            &work_dir,
+            "out=\"$5-output.metta\"; shift 5; printf '%s\\n' \"$@\" > \"$out\"",
+        ),
+    );
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/csv?direction=Column&delimiter=,")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("a,b\n1,2\n")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let metta: String = response.into_json().await.expect("metta");
+    assert_eq!(metta, "2\n,\n");
+
+    let response = client
+        .post("/translations/csv?direction=Row&delimiter=;&header=true&variant=Struct&quote='&escape=%5C&columns=Name&columns=2&types%5BAge%5D=Number")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("Name;Email;Age\nAlice;a@example.com;30\n")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let metta: String = response.into_json().await.expect("metta");
+    assert_eq!(
+        metta,
+        "1\n;\n--header\n--variant=struct\n--quote='\n--escape=\\\n--columns=[\"Name\",\"2\"]\n--types={\"Age\":\"number\"}\n"
+    );
+
+    for query in [
+        // a variant needs a header
+        "direction=Row&delimiter=,&variant=Field",
+        // so does any row shape but the plain one, cells never take one
+        "direction=CellLabeled&delimiter=,&header=true",
+        "direction=Row&delimiter=,,",
+    ] {
+        let response = client
+            .post(format!("/translations/csv?{query}"))
+            .header(Header::new("authorization", token.code.clone()))
+            .body("a,b\n1,2\n")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest, "{query}");
+        let error: TranslationError = response.into_json().await.expect("error body");
+        assert_eq!(error.error, "invalid_parameters");
+    }
+
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(&work_dir).ok();
+    common::teardown_database();
+}
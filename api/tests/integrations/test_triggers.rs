@@ -0,0 +1,343 @@
+use api::db::establish_connection;
+use api::model::{ImportSource, Trigger, TriggerInsert};
+use api::rocket;
+use api::routes::triggers::TriggerInput;
+use api::schema::triggers;
+use diesel::prelude::*;
+use httpmock::prelude::*;
+use httpmock::Mock;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::json;
+use serial_test::serial;
+use std::env;
+use std::time::Duration;
+
+use crate::integrations::common;
+
+/// Waits until `mock` was hit `hits` times, triggers run in the background
+async fn wait_for_hits(mock: &Mock<'_>, hits: usize) {
+    for _ in 0..100 {
+        if mock.hits() >= hits {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {hits} hits, got {}", mock.hits());
+}
+
+fn index_trigger(namespace: &str, target: &str) -> TriggerInput {
+    TriggerInput {
+        namespace: namespace.to_string(),
+        target_namespace: target.to_string(),
+        description: "indexes edges".to_string(),
+        patterns: vec!["(edge $a $b)".to_string()],
+        templates: vec!["(indexed $a $b)".to_string()],
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_trigger_runs_after_upload() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/upload/");
+        then.status(200).body("Upload successful");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/clear/");
+        then.status(200).body("Clear successful");
+    });
+    let transform = server.mock(|when, then| {
+        when.method(POST)
+            .path("/transform")
+            .body_contains("indexed");
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/triggers")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&index_trigger("test/raw", "test/index"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let trigger: Trigger = response.into_json().await.expect("trigger");
+    assert_eq!(trigger.namespace, "/test/raw/");
+    assert_eq!(trigger.target_namespace, "/test/index/");
+
+    let response = client
+        .post("/spaces/upload/test/raw")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("(edge a b)")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    wait_for_hits(&transform, 1).await;
+
+    // neither other namespaces nor clears run the trigger
+    for path in [
+        "/spaces/upload/test/other",
+        "/spaces/clear/test/raw?expr=$x",
+    ] {
+        let request = if path.contains("clear") {
+            client.post(path)
+        } else {
+            client.post(path).body("(edge a b)")
+        };
+        let response = request
+            .header(Header::new("authorization", token.code.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    transform.assert_hits(1);
+
+    let response = client
+        .get("/triggers")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let triggers: Vec<Trigger> = response.into_json().await.expect("triggers");
+    assert_eq!(triggers.len(), 1);
+    assert!(triggers[0].last_run_timestamp.is_some());
+    assert_eq!(triggers[0].last_error, None);
+
+    let response = client
+        .delete(format!("/triggers/{}", trigger.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_trigger_runs_after_background_writes() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    common::mock_remote_data(&server);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/upload/");
+        then.status(200).body("Upload successful");
+    });
+    let transform = server.mock(|when, then| {
+        when.method(POST)
+            .path("/transform")
+            .body_contains("indexed");
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/triggers")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&index_trigger("test/raw", "test/index"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    // a pipeline step writes like an upload request
+    let response = client
+        .post("/pipelines")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "steps": [{"name": "load", "op": "upload", "namespace": "/test/raw/", "data": "(edge a b)"}],
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Accepted);
+    wait_for_hits(&transform, 1).await;
+
+    // and so does a run of an import source
+    let response = client
+        .post("/sources")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "uri": server.url("/data"),
+            "namespace": "/test/raw/",
+            "schedule": "@daily",
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let source: ImportSource = response.into_json().await.expect("source");
+
+    let response = client
+        .post(format!("/sources/{}/run", source.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    wait_for_hits(&transform, 2).await;
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_trigger_loops_stop() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    env::set_var("METTA_KG_TRIGGER_MAX_DEPTH", "3");
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/upload/");
+        then.status(200).body("Upload successful");
+    });
+    let transform = server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    // each namespace writes into the other. Such loops are refused when triggers are
+    // registered, but could still come about through requests racing each other.
+    let response = client
+        .post("/triggers")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&index_trigger("test/a", "test/b"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let trigger: Trigger = response.into_json().await.expect("trigger");
+
+    diesel::insert_into(triggers::table)
+        .values(&TriggerInsert {
+            token: token.id,
+            namespace: "/test/b/".to_string(),
+            target_namespace: "/test/a/".to_string(),
+            description: trigger.description,
+            patterns: trigger.patterns,
+            templates: trigger.templates,
+            creation_timestamp: trigger.creation_timestamp,
+        })
+        .execute(&mut establish_connection())
+        .expect("insert trigger");
+
+    let response = client
+        .post("/spaces/upload/test/a")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("(edge a b)")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    wait_for_hits(&transform, 3).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    transform.assert_hits(3);
+
+    env::remove_var("METTA_KG_TRIGGER_MAX_DEPTH");
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_invalid_triggers() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let other = common::create_test_token("/other/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/triggers")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&index_trigger("test/raw", "other/index"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/triggers")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&TriggerInput {
+            templates: vec!["(indexed $a $c)".to_string()],
+            ..index_trigger("test/raw", "test/index")
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+    let body = response.into_string().await.expect("response body");
+    assert!(body.starts_with("invalid_trigger: "), "{body}");
+
+    let response = client
+        .post("/triggers")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&index_trigger("test/raw", "test/index"))
+        .dispatch()
+        .await;
+    let trigger: Trigger = response.into_json().await.expect("trigger");
+
+    // triggers that would run themselves again, directly or through others, would loop
+    for (namespace, target, through) in [
+        ("test/raw", "test/raw", "/test/raw/"),
+        ("test/index", "test/raw", "/test/raw/ -> /test/index/"),
+    ] {
+        let response = client
+            .post("/triggers")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&index_trigger(namespace, target))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let body = response.into_string().await.expect("response body");
+        assert_eq!(
+            body,
+            format!("invalid_trigger: what it writes would run it again, through {through}")
+        );
+    }
+
+    // triggers are only visible within the namespace of the token
+    let response = client
+        .delete(format!("/triggers/{}", trigger.id))
+        .header(Header::new("authorization", other.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    common::teardown_database();
+}
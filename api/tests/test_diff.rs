@@ -0,0 +1,166 @@
+use api::model::Snapshot;
+use api::mork_api::Namespace;
+use api::rocket;
+use api::routes::diff::{DiffCounts, DiffResult};
+use httpmock::prelude::*;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::json;
+use serial_test::serial;
+
+#[path = "common.rs"]
+mod common;
+// use crate::common;
+
+fn tag(namespace: &str) -> String {
+    Namespace::from_path_string(namespace).data_tag()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_diff() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    // formatting and duplicates are not differences, and the atoms of the namespaces below
+    // are compared relative to each side
+    let before = tag("/test/before/");
+    let after = tag("/test/after/");
+    let nested = format!("(sub ({} (edge x y)))", tag("/test/before/sub/"));
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("before");
+        then.status(200).body(format!(
+            "({before} (edge a b))\n({before} (edge   b c)) ; kept\n({before} (edge a b))\n{nested}\n"
+        ));
+    });
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("after");
+        then.status(200).body(format!(
+            "({after} (edge b c))\n({after} (edge c d))\n{nested}\n"
+        ));
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/diff")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({"a": {"namespace": "/test/before/"}, "b": {"namespace": "/test/after/"}}))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let diff: DiffResult = response.into_json().await.expect("diff");
+    assert_eq!(
+        diff,
+        DiffResult {
+            only_in_a: vec!["(edge a b)".to_string()],
+            only_in_b: vec!["(edge c d)".to_string()],
+            counts: DiffCounts {
+                a: 3,
+                b: 3,
+                only_in_a: 1,
+                only_in_b: 1,
+                common: 2,
+            },
+        }
+    );
+
+    let response = client
+        .post("/spaces/snapshot/test/before")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let snapshot: Snapshot = response.into_json().await.expect("snapshot");
+
+    let response = client
+        .post("/spaces/diff")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({"a": {"snapshot": snapshot.id}, "b": {"namespace": "/test/after/"}}))
+        .dispatch()
+        .await;
+    let diff: DiffResult = response.into_json().await.expect("diff");
+    assert_eq!(diff.only_in_a, vec!["(edge a b)"]);
+    assert_eq!(diff.only_in_b, vec!["(edge c d)"]);
+
+    let response = client
+        .post("/spaces/diff")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({"a": {"snapshot": snapshot.id}, "b": {"snapshot": snapshot.id}}))
+        .dispatch()
+        .await;
+    let diff: DiffResult = response.into_json().await.expect("diff");
+    assert!(diff.only_in_a.is_empty() && diff.only_in_b.is_empty());
+    assert_eq!(diff.counts.common, 3);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_diff_failures() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_contains("/export/")
+            .path_contains("broken");
+        then.status(200).body("(edge a");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/export/");
+        then.status(200).body("(edge a b)\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for (b, status, error) in [
+        (
+            json!({"namespace": "/other/"}),
+            Status::Unauthorized,
+            "unauthorized: no read permission on /other/",
+        ),
+        (
+            json!({"snapshot": 12345}),
+            Status::NotFound,
+            "snapshot 12345 not found",
+        ),
+        (
+            json!({"namespace": "/test/broken/"}),
+            Status::InternalServerError,
+            "Failed to parse /test/broken/: ",
+        ),
+    ] {
+        let response = client
+            .post("/spaces/diff")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&json!({"a": {"namespace": "/test/"}, "b": b}))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), status);
+        let body = response.into_string().await.expect("response body");
+        assert!(body.starts_with(error), "{body}");
+    }
+
+    common::teardown_database();
+}
@@ -252,3 +252,105 @@ async fn test_namespace_mismatch() {
 
     common::teardown_database();
 }
+
+#[tokio::test]
+#[serial]
+async fn test_export_formats() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_matches(Regex::new(r"/export/.*").unwrap());
+        then.status(200)
+            .body("((uriref http://example.org/a) (uriref http://example.org/p) (bnode \"b0\"))\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let export_input = Mm2Input {
+        pattern: "$x".to_string(),
+        template: "$x".to_string(),
+    };
+
+    let response = client
+        .post("/spaces/export/test/space?format=ntriples")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&export_input)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let body: String = response.into_json().await.expect("response body");
+    assert_eq!(
+        body,
+        "<http://example.org/a> <http://example.org/p> _:b0 .\n"
+    );
+
+    // triples are not a table
+    let response = client
+        .post("/spaces/export/test/space?format=csv")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&export_input)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let body = response.into_string().await.expect("response body");
+    assert!(body.starts_with("unsupported_atoms: not a table row or cell"));
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_export_graph() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(GET)
+            .path_matches(Regex::new(r"/export/.*").unwrap());
+        then.status(200)
+            .body("(cites p2 p1)\n(title p1 \"Knowledge graphs\")\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let export_input = Mm2Input {
+        pattern: "$x".to_string(),
+        template: "$x".to_string(),
+    };
+
+    let response = client
+        .post("/spaces/export/test/space?format=dot&label=title")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&export_input)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let body: String = response.into_json().await.expect("response body");
+    assert_eq!(
+        body,
+        "digraph {\n  \"p2\" [label=\"p2\"];\n  \"p1\" [label=\"Knowledge graphs\"];\n  \"p2\" -> \"p1\" [label=\"cites\"];\n}\n"
+    );
+
+    common::teardown_database();
+}
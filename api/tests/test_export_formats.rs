@@ -0,0 +1,206 @@
+use api::export_formats::{convert, graph, to_csv, to_jsonld, to_ntriples, Graph, OutputFormat};
+use api::metta::parse_all;
+use rocket::serde::json::serde_json::json;
+
+const TRIPLES: &str = r#"
+(
+  (uriref http://example.org/alice)
+  (uriref http://www.w3.org/1999/02/22-rdf-syntax-ns#type)
+  (uriref http://xmlns.com/foaf/0.1/Person)
+)
+(
+  (uriref http://example.org/alice)
+  (uriref http://xmlns.com/foaf/0.1/name)
+  ((literal (http://www.w3.org/2001/XMLSchema#string)) "Alice \"Al\"")
+)
+(
+  (uriref http://example.org/alice)
+  (uriref http://xmlns.com/foaf/0.1/nick)
+  ((literal (http://www.w3.org/1999/02/22-rdf-syntax-ns#langString en)) "Ally")
+)
+(
+  (uriref http://example.org/alice)
+  (uriref http://xmlns.com/foaf/0.1/age)
+  ((literal (http://www.w3.org/2001/XMLSchema#integer)) "30")
+)
+(
+  (uriref http://example.org/alice)
+  (uriref http://xmlns.com/foaf/0.1/knows)
+  (bnode "b0")
+)
+(Namespace ("foaf" "http://xmlns.com/foaf/0.1/"))
+"#;
+
+#[test]
+fn test_ntriples() {
+    let atoms = parse_all(TRIPLES).expect("valid metta");
+
+    assert_eq!(
+        to_ntriples(&atoms).expect("triples"),
+        "<http://example.org/alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://xmlns.com/foaf/0.1/Person> .\n\
+         <http://example.org/alice> <http://xmlns.com/foaf/0.1/name> \"Alice \\\"Al\\\"\" .\n\
+         <http://example.org/alice> <http://xmlns.com/foaf/0.1/nick> \"Ally\"@en .\n\
+         <http://example.org/alice> <http://xmlns.com/foaf/0.1/age> \"30\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n\
+         <http://example.org/alice> <http://xmlns.com/foaf/0.1/knows> _:b0 .\n"
+    );
+
+    let error = to_ntriples(&parse_all("(edge a b)").unwrap()).unwrap_err();
+    assert_eq!(error, "not a triple: (edge a b)");
+}
+
+#[test]
+fn test_jsonld() {
+    let atoms = parse_all(TRIPLES).expect("valid metta");
+
+    assert_eq!(
+        to_jsonld(&atoms).expect("json-ld"),
+        json!({
+            "@context": {"foaf": "http://xmlns.com/foaf/0.1/"},
+            "@graph": [{
+                "@id": "http://example.org/alice",
+                "@type": ["foaf:Person"],
+                "foaf:name": [{"@value": "Alice \"Al\""}],
+                "foaf:nick": [{"@value": "Ally", "@language": "en"}],
+                "foaf:age": [{"@value": "30", "@type": "http://www.w3.org/2001/XMLSchema#integer"}],
+                "foaf:knows": [{"@id": "_:b0"}],
+            }]
+        })
+    );
+    let error = to_jsonld(
+        &parse_all(
+            "((uriref http://example.org/alice) (uriref @id) (uriref http://example.org/bob))",
+        )
+        .unwrap(),
+    )
+    .unwrap_err();
+    assert_eq!(error, "predicate <@id> is the JSON-LD keyword @id");
+}
+
+#[test]
+fn test_csv_row_shapes() {
+    let expected = "Name,Email\r\nAlice,a@example.com\r\n\"Bob, Jr.\",\r\n";
+
+    for metta in [
+        // naive
+        r#"(header ("Name" "Email")) (0 ("Alice" "a@example.com")) (1 ("Bob, Jr." ""))"#,
+        // struct
+        r#"(("Name" "Alice") ("Email" "a@example.com")) (("Name" "Bob, Jr.") ("Email" ""))"#,
+        // field, rows are sorted and missing cells stay empty
+        r#"(1 "Name" "Bob, Jr.") (0 "Name" "Alice") (0 "Email" "a@example.com")"#,
+        // functional
+        r#"(= (value ("Name" 0)) "Alice") (= (value ("Email" 0)) "a@example.com")
+           (= (value ("Name" 1)) "Bob, Jr.")"#,
+    ] {
+        let atoms = parse_all(metta).expect("valid metta");
+        assert_eq!(to_csv(&atoms).expect("csv"), expected, "{metta}");
+    }
+}
+
+#[test]
+fn test_csv_cell_shapes() {
+    let unlabeled = parse_all(r#"(= (value (1 0)) c) (= (value (0 1)) "b") (= (value (0 0)) 1.5)"#)
+        .expect("valid metta");
+    assert_eq!(to_csv(&unlabeled).expect("csv"), "1.5,b\r\nc,\r\n");
+
+    let labeled = parse_all(
+        r#"(= (value ("Home" "Home")) "0") (= (value ("Home" "Bakery")) "2")
+           (= (value ("Bakery" "Home")) "2") (= (value ("Bakery" "Bakery")) "0")"#,
+    )
+    .expect("valid metta");
+    assert_eq!(
+        to_csv(&labeled).expect("csv"),
+        ",Home,Bakery\r\nHome,0,2\r\nBakery,2,0\r\n"
+    );
+
+    let error = to_csv(&parse_all("(edge (a) b)").unwrap()).unwrap_err();
+    assert_eq!(error, "not a table row or cell: (edge (a) b)");
+}
+
+#[test]
+fn test_convert() {
+    assert_eq!(
+        convert("(edge a b)", OutputFormat::Metta, &[]).unwrap(),
+        "(edge a b)"
+    );
+    assert!(convert("(edge a", OutputFormat::Csv, &[]).is_err());
+}
+
+#[test]
+fn test_graph() {
+    let atoms = parse_all(
+        r#"(funds proposal-1 "Round 3") (cites proposal-2 proposal-1) (title proposal-1 "Graph \"KG\"")"#,
+    )
+    .expect("valid metta");
+
+    let g = graph(&atoms, &["title".to_string()]).expect("graph");
+    assert_eq!(
+        g,
+        Graph {
+            nodes: vec![
+                ("proposal-1".to_string(), "Graph \"KG\"".to_string()),
+                ("Round 3".to_string(), "Round 3".to_string()),
+                ("proposal-2".to_string(), "proposal-2".to_string()),
+            ],
+            edges: vec![
+                (
+                    "proposal-1".to_string(),
+                    "Round 3".to_string(),
+                    "funds".to_string()
+                ),
+                (
+                    "proposal-2".to_string(),
+                    "proposal-1".to_string(),
+                    "cites".to_string()
+                ),
+            ],
+        }
+    );
+
+    assert_eq!(
+        g.to_dot(),
+        "digraph {\n  \"proposal-1\" [label=\"Graph \\\"KG\\\"\"];\n  \"Round 3\" [label=\"Round 3\"];\n  \"proposal-2\" [label=\"proposal-2\"];\n  \"proposal-1\" -> \"Round 3\" [label=\"funds\"];\n  \"proposal-2\" -> \"proposal-1\" [label=\"cites\"];\n}\n"
+    );
+    assert_eq!(
+        g.to_cytoscape()["elements"]["edges"][1],
+        json!({"data": {"id": "e1", "source": "proposal-2", "target": "proposal-1", "label": "cites"}})
+    );
+
+    let graphml = g.to_graphml();
+    assert!(graphml.contains(
+        "<node id=\"n0\"><data key=\"id\">proposal-1</data><data key=\"label\">Graph &quot;KG&quot;</data></node>"
+    ));
+    assert!(graphml.contains(
+        "<edge id=\"e1\" source=\"n2\" target=\"n0\"><data key=\"label\">cites</data></edge>"
+    ));
+}
+
+#[test]
+fn test_graph_of_triples() {
+    let atoms = parse_all(TRIPLES).expect("valid metta");
+
+    // the label predicate can be given compacted or in full
+    for label in ["foaf:name", "http://xmlns.com/foaf/0.1/name"] {
+        let g = graph(&atoms, &[label.to_string()]).expect("graph");
+        assert_eq!(
+            g.nodes[0],
+            (
+                "http://example.org/alice".to_string(),
+                "Alice \"Al\"".to_string()
+            )
+        );
+        assert_eq!(
+            g.edges
+                .iter()
+                .map(|(_, target, label)| format!("{label} {target}"))
+                .collect::<Vec<_>>(),
+            vec![
+                "http://www.w3.org/1999/02/22-rdf-syntax-ns#type foaf:Person",
+                "foaf:nick \"Ally\"@en",
+                "foaf:age \"30\"^^<http://www.w3.org/2001/XMLSchema#integer>",
+                "foaf:knows _:b0",
+            ]
+        );
+    }
+
+    assert!(graph(&parse_all("((a) b c)").unwrap(), &[]).is_err());
+}
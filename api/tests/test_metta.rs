@@ -0,0 +1,66 @@
+use api::metta::{parse, parse_all, validate_mm2, Expr, MAX_DEPTH};
+use std::collections::HashMap;
+
+#[test]
+fn test_parse_all() {
+    let atoms = parse_all(
+        r#"
+        ; a comment
+        (edge a b)
+        (name a "Alice \"A\"")  (nested (x $y) ())
+        "#,
+    )
+    .expect("valid metta");
+
+    assert_eq!(atoms.len(), 3);
+    assert_eq!(
+        atoms[0],
+        Expr::Expression(vec![
+            Expr::Symbol("edge".to_string()),
+            Expr::Symbol("a".to_string()),
+            Expr::Symbol("b".to_string()),
+        ])
+    );
+    assert_eq!(atoms[1].to_string(), r#"(name a "Alice \"A\"")"#);
+    assert_eq!(atoms[2].to_string(), "(nested (x $y) ())");
+    assert_eq!(atoms[2].variables(), vec!["y".to_string()]);
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(parse_all("(edge a").is_err());
+    assert!(parse_all("edge a)").is_err());
+    assert!(parse_all("\"open").is_err());
+    assert!(parse("a b").is_err());
+    assert!(parse("").is_err());
+    assert!(parse("$").is_err());
+
+    let deep = format!("{}{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+    assert!(parse(&deep).is_ok());
+    let too_deep = format!("{}{}", "(".repeat(MAX_DEPTH + 1), ")".repeat(MAX_DEPTH + 1));
+    assert_eq!(
+        parse(&too_deep).unwrap_err().message,
+        "expressions nested too deeply"
+    );
+    assert!(parse_all(&"(".repeat(100_000)).is_err());
+}
+
+#[test]
+fn test_validate_mm2() {
+    assert!(validate_mm2("$x", "$x").is_ok());
+    assert!(validate_mm2("(edge $a $b)", "(edge $b $a)").is_ok());
+    assert!(validate_mm2("(edge $a $b)", "(edge $b $c)").is_err());
+    assert!(validate_mm2("(edge $a", "$a").is_err());
+    assert!(validate_mm2("$x", "$x $x").is_err());
+}
+
+#[test]
+fn test_substitute() {
+    let atom = parse("(edge $a ($b \"$a\"))").expect("valid metta");
+    let bindings = HashMap::from([("a".to_string(), parse("(user 42)").unwrap())]);
+
+    assert_eq!(
+        atom.substitute(&bindings).to_string(),
+        "(edge (user 42) ($b \"$a\"))"
+    );
+}
@@ -0,0 +1,295 @@
+use api::db::establish_connection;
+use api::model::Token;
+use api::mork_api::Namespace;
+use api::rocket;
+use api::routes::namespaces::{CopyInput, MoveInput, RelocateResult, RenameInput};
+use api::schema::tokens;
+use diesel::prelude::*;
+use httpmock::prelude::*;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use serial_test::serial;
+
+#[path = "common.rs"]
+mod common;
+// use crate::common;
+
+fn tag(namespace: &str) -> String {
+    Namespace::from_path_string(namespace).data_tag()
+}
+
+fn namespace_of(token: &Token) -> String {
+    tokens::table
+        .select(tokens::namespace)
+        .filter(tokens::id.eq(token.id))
+        .get_result(&mut establish_connection())
+        .expect("token")
+}
+
+#[tokio::test]
+#[serial]
+async fn test_copy_namespace() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    // the whole subtree is re-rooted, then the atoms of the namespace itself are retagged
+    let copy = server.mock(|when, then| {
+        when.method(POST)
+            .path("/transform")
+            .body("(transform (, (test (a $rest))) (, (test (b $rest))))");
+        then.status(200).body("Transform successful");
+    });
+    let retag = server.mock(|when, then| {
+        when.method(POST).path("/transform").body(format!(
+            "(transform (, (test (b ({} $x)))) (, (test (b ({} $x)))))",
+            tag("/test/a/"),
+            tag("/test/b/")
+        ));
+        then.status(200).body("Transform successful");
+    });
+    let clear = server.mock(|when, then| {
+        when.method(GET).path(format!(
+            "/clear/{}",
+            urlencoding::encode(&format!("(test (b ({} $x)))", tag("/test/a/")))
+        ));
+        then.status(200).body("Clear successful");
+    });
+    // with the same last segment, the tags already match
+    let same_name = server.mock(|when, then| {
+        when.method(POST)
+            .path("/transform")
+            .body("(transform (, (test (x (kg $rest)))) (, (test (y (kg $rest)))))");
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/copy")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&CopyInput {
+            from: "test/a".to_string(),
+            to: "/test/b/".to_string(),
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let result: RelocateResult = response.into_json().await.expect("result");
+    assert_eq!(
+        result,
+        RelocateResult {
+            from: "/test/a/".to_string(),
+            to: "/test/b/".to_string(),
+            tokens_updated: 0,
+        }
+    );
+    copy.assert();
+    retag.assert();
+    clear.assert();
+
+    let response = client
+        .post("/spaces/copy")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&CopyInput {
+            from: "/test/x/kg/".to_string(),
+            to: "/test/y/kg/".to_string(),
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    same_name.assert();
+    retag.assert_hits(1);
+    clear.assert_hits(1);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_move_and_rename_namespace() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let below = common::create_test_token("/test/a/sub/", true, false);
+    let similar = common::create_test_token("/test/ab/", true, false);
+    // only tokens created below the moving token go along
+    diesel::update(tokens::table.filter(tokens::id.eq(below.id)))
+        .set(tokens::parent.eq(token.id))
+        .execute(&mut establish_connection())
+        .expect("make below a child token");
+
+    let transform = server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("Transform successful");
+    });
+    let clear_source = server.mock(|when, then| {
+        when.method(GET).path(format!(
+            "/clear/{}",
+            urlencoding::encode("(test (a $rest))")
+        ));
+        then.status(200).body("Clear successful");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/clear/");
+        then.status(200).body("Clear successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/move")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&MoveInput {
+            from: "/test/a/".to_string(),
+            to: "/test/c/".to_string(),
+            update_tokens: true,
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let result: RelocateResult = response.into_json().await.expect("result");
+    assert_eq!(result.tokens_updated, 1);
+    clear_source.assert();
+
+    assert_eq!(namespace_of(&below), "/test/c/sub/");
+    assert_eq!(namespace_of(&similar), "/test/ab/");
+
+    let response = client
+        .post("/spaces/rename")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&RenameInput {
+            namespace: "/test/c/".to_string(),
+            name: "d".to_string(),
+            update_tokens: false,
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let result: RelocateResult = response.into_json().await.expect("result");
+    assert_eq!(result.to, "/test/d/");
+    assert_eq!(result.tokens_updated, 0);
+    assert_eq!(namespace_of(&below), "/test/c/sub/");
+
+    // a token of another user on the namespace stops the move before anything is copied
+    let foreign = common::create_test_token("/test/d/x/", true, false);
+    let transforms = transform.hits();
+    let response = client
+        .post("/spaces/rename")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&RenameInput {
+            namespace: "/test/d/".to_string(),
+            name: "e".to_string(),
+            update_tokens: true,
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(
+        response.into_string().await.expect("body"),
+        "unauthorized: 1 of the tokens on /test/d/ belong to other users"
+    );
+    assert_eq!(transform.hits(), transforms);
+    assert_eq!(namespace_of(&foreign), "/test/d/x/");
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_invalid_namespace_moves() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let read_only = common::create_test_token("/test/", true, false);
+    let root = common::create_test_token("/", true, true);
+
+    let transform = server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for (token, from, to, status, error) in [
+        (
+            &token,
+            "/test/a/",
+            "/test/a/b/",
+            Status::BadRequest,
+            "invalid_namespace: /test/a/ and /test/a/b/ overlap",
+        ),
+        (
+            &root,
+            "/",
+            "/test/",
+            Status::BadRequest,
+            "invalid_namespace: the root namespace cannot be copied or moved",
+        ),
+        (
+            &token,
+            "/test/a/",
+            "/other/",
+            Status::Unauthorized,
+            "unauthorized: no write permission on /other/",
+        ),
+        (
+            &read_only,
+            "/test/a/",
+            "/test/b/",
+            Status::Unauthorized,
+            "unauthorized: no write permission on /test/a/",
+        ),
+    ] {
+        let response = client
+            .post("/spaces/move")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&MoveInput {
+                from: from.to_string(),
+                to: to.to_string(),
+                update_tokens: false,
+            })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), status);
+        let body = response.into_string().await.expect("response body");
+        assert_eq!(body, error);
+    }
+
+    let response = client
+        .post("/spaces/rename")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&RenameInput {
+            namespace: "/test/a/".to_string(),
+            name: "b/c".to_string(),
+            update_tokens: false,
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+
+    transform.assert_hits(0);
+
+    common::teardown_database();
+}
@@ -0,0 +1,233 @@
+use api::model::SavedQuery;
+use api::rocket;
+use api::routes::queries::SavedQueryOutput;
+use httpmock::prelude::*;
+use httpmock::Regex;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::json;
+use serial_test::serial;
+
+#[path = "common.rs"]
+mod common;
+// use crate::common;
+
+#[tokio::test]
+#[serial]
+async fn test_create_and_run_query() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    // the parameter is bound before the pattern is sent
+    let export_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_matches(Regex::new(r"/export/.*proposer_id.*42.*/.*proposal_of").unwrap());
+        then.status(200).body("(proposal_of 42 7)\n");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/queries")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "namespace": "test/proposals",
+            "name": "proposals_per_user",
+            "kind": "query",
+            "patterns": ["((proposal $p) proposer_id $user)"],
+            "templates": ["(proposal_of $user $p)"],
+            "parameters": ["user"],
+        }))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let query: SavedQuery = response.into_json().await.expect("query");
+    assert_eq!(query.namespace, "/test/proposals/");
+    assert_eq!(query.kind, "query");
+
+    // names are unique per namespace
+    let response = client
+        .post("/queries")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "namespace": "/test/proposals/",
+            "name": "proposals_per_user",
+            "kind": "query",
+            "patterns": ["$x"],
+            "templates": ["$x"],
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Conflict);
+
+    let response = client
+        .get("/queries")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let queries: Vec<SavedQuery> = response.into_json().await.expect("queries");
+    assert_eq!(queries.len(), 1);
+
+    let response = client
+        .post(format!("/queries/{}/run", query.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({"user": 42}))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let output: SavedQueryOutput = response.into_json().await.expect("output");
+    assert_eq!(
+        output,
+        SavedQueryOutput::Exported("(proposal_of 42 7)\n".to_string())
+    );
+    export_mock.assert();
+
+    for (values, error) in [
+        (json!({}), "missing_parameter: "),
+        (json!({"user": 42, "round": 1}), "unknown_parameter: "),
+        (json!({"user": "$x"}), "invalid_parameter: "),
+        (json!({"user": "(42"}), "invalid_parameter: "),
+    ] {
+        let response = client
+            .post(format!("/queries/{}/run", query.id))
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&values)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest, "{values}");
+        let body = response.into_string().await.expect("response body");
+        assert!(body.starts_with(error), "{body}");
+    }
+
+    let response = client
+        .delete(format!("/queries/{}", query.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_run_transform() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let read_only = common::create_test_token("/test/", true, false);
+
+    let transform_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/transform")
+            .body_contains("time_accessed 2024-10-01");
+        then.status(200).body("Transform successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/queries")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "namespace": "test",
+            "name": "snapshot_at",
+            "description": "copies the data accessed at $time",
+            "kind": "transform",
+            "patterns": ["(time_accessed $time $x)"],
+            "templates": ["(snapshot $time $x)"],
+            "parameters": ["time"],
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let query: SavedQuery = response.into_json().await.expect("query");
+
+    // transforms write to the namespace
+    let response = client
+        .post(format!("/queries/{}/run", query.id))
+        .header(Header::new("authorization", read_only.code.clone()))
+        .json(&json!({"time": "2024-10-01"}))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post(format!("/queries/{}/run", query.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({"time": "2024-10-01"}))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let output: SavedQueryOutput = response.into_json().await.expect("output");
+    assert_eq!(output, SavedQueryOutput::Transformed(true));
+    transform_mock.assert();
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_invalid_queries() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for query in [
+        // unbound template variable
+        json!({"namespace": "test", "name": "q", "kind": "query",
+               "patterns": ["(edge $a $b)"], "templates": ["(path $a $c)"]}),
+        // queries are exported, so there is only one pattern
+        json!({"namespace": "test", "name": "q", "kind": "query",
+               "patterns": ["(edge $a $b)", "(edge $b $c)"], "templates": ["(path $a $c)"]}),
+        json!({"namespace": "test", "name": "q", "kind": "transform",
+               "patterns": ["(edge $a"], "templates": ["$a"]}),
+    ] {
+        let response = client
+            .post("/queries")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&query)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest, "{query}");
+        let body = response.into_string().await.expect("response body");
+        assert!(body.starts_with("invalid_query: "), "{body}");
+    }
+
+    let response = client
+        .post("/queries")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({"namespace": "other", "name": "q", "kind": "query",
+                      "patterns": ["$x"], "templates": ["$x"]}))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    common::teardown_database();
+}
@@ -0,0 +1,251 @@
+use api::rocket;
+use api::routes::translations::SparqlTranslation;
+use httpmock::prelude::*;
+use httpmock::Regex;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use serial_test::serial;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[path = "common.rs"]
+mod common;
+// use crate::common;
+
+const QUERY: &str = "SELECT ?name WHERE { ?person <http://xmlns.com/foaf/0.1/name> ?name }";
+
+/// Makes the translation runner answer every query with `translation`, returning the work
+/// directory to remove afterwards
+#[cfg(unix)]
+fn translate_to(translation: &str) -> PathBuf {
+    let work_dir = env::temp_dir().join(format!("metta-kg-sparql-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).expect("work dir");
+
+    let command = format!("cat > \"$5-output.metta\" <<'EOF'\n{translation}\nEOF");
+    env::set_var(
+        "METTA_KG_TRANSLATION_PYTHON",
+        common::fake_interpreter(&work_dir, &command),
+    );
+    env::set_var("METTA_KG_TRANSLATION_WORK_DIR", &work_dir);
+
+    work_dir
+}
+
+fn reset_translation(work_dir: PathBuf) {
+    env::remove_var("METTA_KG_TRANSLATION_PYTHON");
+    env::remove_var("METTA_KG_TRANSLATION_WORK_DIR");
+    fs::remove_dir_all(work_dir).ok();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_translate_sparql() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    let work_dir = translate_to(
+        r#"{"patterns": ["($person (uriref http://xmlns.com/foaf/0.1/name) $name)"], "template": "(result (name $name))", "variables": ["name"]}"#,
+    );
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/sparql")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(QUERY)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let translation: SparqlTranslation = response.into_json().await.expect("translation");
+    assert_eq!(
+        translation.patterns,
+        vec!["($person (uriref http://xmlns.com/foaf/0.1/name) $name)".to_string()]
+    );
+    assert_eq!(translation.template, "(result (name $name))");
+    assert_eq!(translation.variables, vec!["name".to_string()]);
+
+    reset_translation(work_dir);
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_translate_sparql_unbound_variable() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    let work_dir = translate_to(
+        r#"{"patterns": ["($person (uriref http://xmlns.com/foaf/0.1/name) $name)"], "template": "(result (mbox $mbox))", "variables": ["mbox"]}"#,
+    );
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/translations/sparql")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("SELECT ?mbox WHERE { ?person <http://xmlns.com/foaf/0.1/name> ?name }")
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    reset_translation(work_dir);
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_sparql_single_pattern() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    let work_dir = translate_to(
+        r#"{"patterns": ["($person (uriref http://xmlns.com/foaf/0.1/name) $name)"], "template": "(result (name $name))", "variables": ["name"]}"#,
+    );
+
+    let export_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_matches(Regex::new(r"^/export/.*uriref.*/.*result.*").unwrap());
+        then.status(200).body("(result (name \"Lily\"))");
+    });
+    let transform_mock = server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("ok");
+    });
+
+    // a read-only token is enough to query
+    let token = common::create_test_token("/test/", true, false);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/sparql/test/people")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(QUERY)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let result: String = response.into_json().await.expect("result");
+    assert_eq!(result, "(result (name \"Lily\"))");
+    export_mock.assert_hits(1);
+    transform_mock.assert_hits(0);
+
+    let response = client
+        .post("/spaces/sparql/other")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(QUERY)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    // queries count against the translation limits of the token
+    env::set_var("METTA_KG_TRANSLATION_MAX_BYTES", "16");
+    let response = client
+        .post("/spaces/sparql/test/people")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(QUERY)
+        .dispatch()
+        .await;
+    env::remove_var("METTA_KG_TRANSLATION_MAX_BYTES");
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+
+    env::set_var("METTA_KG_TRANSLATION_DAILY_QUOTA", "1");
+    let response = client
+        .post("/spaces/sparql/test/people")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(QUERY)
+        .dispatch()
+        .await;
+    env::remove_var("METTA_KG_TRANSLATION_DAILY_QUOTA");
+    assert_eq!(response.status(), Status::TooManyRequests);
+    let body = response.into_string().await.expect("response body");
+    assert!(body.starts_with("quota_exceeded"), "{body}");
+    export_mock.assert_hits(1);
+
+    reset_translation(work_dir);
+    common::teardown_database();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn test_sparql_joined_patterns() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    let work_dir = translate_to(
+        r#"{"patterns": ["($person (uriref http://xmlns.com/foaf/0.1/name) $name)", "($person (uriref http://xmlns.com/foaf/0.1/mbox) $mbox)"], "template": "(result (name $name) (mbox $mbox))", "variables": ["name", "mbox"]}"#,
+    );
+
+    // both patterns read from the space, the template writes to a scratch namespace
+    let transform_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/transform")
+            .body_contains("(test (people (peoplea727d4f9-836a-4e4c-9480 ($person (uriref http://xmlns.com/foaf/0.1/name) $name))))")
+            .body_contains("(test (people (peoplea727d4f9-836a-4e4c-9480 ($person (uriref http://xmlns.com/foaf/0.1/mbox) $mbox))))")
+            .body_contains("(_scratch (");
+        then.status(200).body("ok");
+    });
+    let export_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_matches(Regex::new(r"^/export/.*_scratch.*").unwrap());
+        then.status(200)
+            .body("(result (name \"Lily\") (mbox \"l@example.com\"))");
+    });
+    let clear_mock = server.mock(|when, then| {
+        when.method(GET)
+            .path_matches(Regex::new(r"^/clear/.*_scratch.*").unwrap());
+        then.status(200).body("ok");
+    });
+
+    let token = common::create_test_token("/test/", true, false);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/spaces/sparql/test/people")
+        .header(Header::new("authorization", token.code.clone()))
+        .body(QUERY)
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let result: String = response.into_json().await.expect("result");
+    assert_eq!(result, "(result (name \"Lily\") (mbox \"l@example.com\"))");
+    transform_mock.assert_hits(1);
+    export_mock.assert_hits(1);
+    clear_mock.assert_hits(1);
+
+    reset_translation(work_dir);
+    common::teardown_database();
+}
@@ -221,3 +221,91 @@ async fn test_namespace_mismatch() {
 
     common::teardown_database();
 }
+
+#[tokio::test]
+#[serial]
+async fn test_upload_with_mm2() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let upload_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*edge.*/.*reversed.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post(format!(
+            "/spaces/upload/test/space?pattern={}&template={}",
+            urlencoding::encode("(edge $a $b)"),
+            urlencoding::encode("(reversed $b $a)")
+        ))
+        .header(Header::new("authorization", token.code.clone()))
+        .body("(edge a b)")
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    upload_mock.assert();
+
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_upload_with_invalid_mm2() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+
+    let upload_mock = server.mock(|when, then| {
+        when.method(POST)
+            .path_matches(Regex::new(r"/upload/.*").unwrap());
+        then.status(200).body("Upload successful");
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    for query in [
+        format!("pattern={}", urlencoding::encode("(edge $a $b)")),
+        format!(
+            "pattern={}&template={}",
+            urlencoding::encode("(edge $a $b"),
+            urlencoding::encode("$a")
+        ),
+        format!(
+            "pattern={}&template={}",
+            urlencoding::encode("(edge $a $b)"),
+            urlencoding::encode("(path $a $c)")
+        ),
+    ] {
+        let response = client
+            .post(format!("/spaces/upload/test/space?{query}"))
+            .header(Header::new("authorization", token.code.clone()))
+            .body("(edge a b)")
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::BadRequest, "{query}");
+    }
+    upload_mock.assert_hits(0);
+
+    common::teardown_database();
+}
@@ -0,0 +1,430 @@
+use api::model::{ImportSource, Webhook};
+use api::rocket;
+use api::routes::webhooks::{CreatedWebhook, WebhookInput};
+use api::webhook_runner::WebhookPayload;
+use hmac::{Hmac, Mac};
+use httpmock::prelude::*;
+use httpmock::Mock;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::serde_json::{self, json};
+use serial_test::serial;
+use sha2::Sha256;
+use std::env;
+use std::time::Duration;
+
+#[path = "common.rs"]
+mod common;
+// use crate::common;
+
+const SECRET: &str = "test-secret";
+
+fn hmac_hex(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac key");
+    mac.update(message);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Whether a delivery carries the signature its receiver would compute
+fn signed(request: &HttpMockRequest) -> bool {
+    let body = request.body.clone().unwrap_or_default();
+    let Ok(payload) = serde_json::from_slice::<WebhookPayload>(&body) else {
+        return false;
+    };
+    let secret = hmac_hex(
+        SECRET.as_bytes(),
+        format!("webhook-{}", payload.webhook).as_bytes(),
+    );
+    let expected = format!("sha256={}", hmac_hex(secret.as_bytes(), &body));
+
+    request.headers.iter().flatten().any(|(name, value)| {
+        name.eq_ignore_ascii_case("x-metta-kg-signature") && *value == expected
+    })
+}
+
+/// Waits until `mock` was hit `hits` times, deliveries run in the background
+async fn wait_for_hits(mock: &Mock<'_>, hits: usize) {
+    for _ in 0..100 {
+        if mock.hits() >= hits {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {hits} hits, got {}", mock.hits());
+}
+
+/// Waits until the delivery to the only webhook of `token` is recorded
+async fn wait_for_status(client: &Client, token: &str) -> Webhook {
+    for _ in 0..100 {
+        let response = client
+            .get("/webhooks")
+            .header(Header::new("authorization", token.to_string()))
+            .dispatch()
+            .await;
+        let webhooks: Vec<Webhook> = response.into_json().await.expect("webhooks");
+        if webhooks[0].last_status.is_some() {
+            return webhooks[0].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no delivery recorded");
+}
+
+fn webhook(namespace: &str, url: String) -> WebhookInput {
+    WebhookInput {
+        namespace: namespace.to_string(),
+        url,
+        description: "notifies the indexer".to_string(),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_webhook_delivery() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    env::set_var("METTA_KG_SECRET", SECRET);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/upload/");
+        then.status(200).body("Upload successful");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/clear/");
+        then.status(200).body("Clear successful");
+    });
+    let receiver = server.mock(|when, then| {
+        when.method(POST)
+            .path("/hook")
+            .body_contains(r#""path":"/test/raw/""#)
+            .body_contains(r#""outcome":"success""#)
+            .matches(signed);
+        then.status(204);
+    });
+    let other_receiver = server.mock(|when, then| {
+        when.method(POST).path("/other-hook");
+        then.status(204);
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&webhook("test", server.url("/hook")))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let created: CreatedWebhook = response.into_json().await.expect("webhook");
+    assert_eq!(created.webhook.namespace, "/test/");
+    assert_eq!(
+        created.secret,
+        hmac_hex(
+            SECRET.as_bytes(),
+            format!("webhook-{}", created.webhook.id).as_bytes()
+        )
+    );
+
+    let response = client
+        .post("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&webhook("test/other", server.url("/other-hook")))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    // changes below the namespace of a webhook are delivered to it
+    let response = client
+        .post("/spaces/upload/test/raw")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("(edge a b)")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    wait_for_hits(&receiver, 1).await;
+
+    let response = client
+        .post("/spaces/clear/test/raw?expr=$x")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    wait_for_hits(&receiver, 2).await;
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    receiver.assert_hits(2);
+    other_receiver.assert_hits(0);
+
+    let response = client
+        .get("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    let webhooks: Vec<Webhook> = response.into_json().await.expect("webhooks");
+    assert_eq!(webhooks.len(), 2);
+    assert_eq!(webhooks[0].last_status.as_deref(), Some("204 No Content"));
+    assert!(webhooks[0].last_delivery_timestamp.is_some());
+    assert_eq!(webhooks[1].last_status, None);
+
+    env::remove_var("METTA_KG_SECRET");
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_webhook_delivery_from_background_writes() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    common::mock_remote_data(&server);
+    env::set_var("METTA_KG_SECRET", SECRET);
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path("/transform");
+        then.status(200).body("Transform successful");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path_contains("/clear/");
+        then.status(200).body("Clear successful");
+    });
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/upload/");
+        then.status(200).body("Upload successful");
+    });
+    let [transformed, cleared, imported] = ["transform", "clear", "import"].map(|operation| {
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/hook")
+                .body_contains(format!(r#""operation":"{operation}""#))
+                .body_contains(r#""path":"/test/raw/""#)
+                .matches(signed);
+            then.status(204);
+        })
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&webhook("test", server.url("/hook")))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .post("/pipelines")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "steps": [{
+                "name": "index",
+                "op": "transform",
+                "namespace": "/test/raw/",
+                "patterns": ["(edge $a $b)"],
+                "templates": ["(indexed $a $b)"],
+            }],
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Accepted);
+    wait_for_hits(&transformed, 1).await;
+
+    let response = client
+        .post("/sources")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&json!({
+            "uri": server.url("/data"),
+            "namespace": "/test/raw/",
+            "schedule": "@daily",
+            "clear_before": true,
+        }))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let source: ImportSource = response.into_json().await.expect("source");
+
+    let response = client
+        .post(format!("/sources/{}/run", source.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    wait_for_hits(&cleared, 1).await;
+    wait_for_hits(&imported, 1).await;
+
+    env::remove_var("METTA_KG_SECRET");
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_webhook_retries() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+    env::set_var("METTA_KG_SECRET", SECRET);
+    env::set_var("METTA_KG_WEBHOOK_MAX_ATTEMPTS", "3");
+    env::set_var("METTA_KG_WEBHOOK_BACKOFF_MS", "10");
+
+    let token = common::create_test_token("/test/", true, true);
+
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/upload/");
+        then.status(200).body("Upload successful");
+    });
+    let receiver = server.mock(|when, then| {
+        when.method(POST).path("/hook");
+        then.status(500);
+    });
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    let response = client
+        .post("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&webhook("test", server.url("/hook")))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .post("/spaces/upload/test")
+        .header(Header::new("authorization", token.code.clone()))
+        .body("(edge a b)")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let webhook = wait_for_status(&client, &token.code).await;
+    assert_eq!(
+        webhook.last_status.as_deref(),
+        Some("500 Internal Server Error after 3 attempts")
+    );
+    receiver.assert_hits(3);
+
+    env::remove_var("METTA_KG_SECRET");
+    env::remove_var("METTA_KG_WEBHOOK_MAX_ATTEMPTS");
+    env::remove_var("METTA_KG_WEBHOOK_BACKOFF_MS");
+    common::teardown_database();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_invalid_webhooks() {
+    if !common::is_database_running() {
+        eprintln!("Warning: Database not running, skipping test");
+        return;
+    }
+    let server = MockServer::start();
+    common::setup(&server.base_url());
+
+    let token = common::create_test_token("/test/", true, true);
+    let other = common::create_test_token("/other/", true, true);
+
+    let client = Client::tracked(rocket())
+        .await
+        .expect("valid rocket instance");
+
+    // deliveries cannot be signed without a secret
+    let response = client
+        .post("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&webhook("test", server.url("/hook")))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::InternalServerError);
+
+    env::set_var("METTA_KG_SECRET", SECRET);
+
+    for (namespace, url, status, error) in [
+        (
+            "other",
+            server.url("/hook"),
+            Status::Unauthorized,
+            "unauthorized: no read permission on other",
+        ),
+        (
+            "test",
+            "not a url".to_string(),
+            Status::BadRequest,
+            "invalid_webhook: ",
+        ),
+        (
+            "test",
+            "ftp://example.org/hook".to_string(),
+            Status::Forbidden,
+            "scheme_not_allowed: ",
+        ),
+    ] {
+        let response = client
+            .post("/webhooks")
+            .header(Header::new("authorization", token.code.clone()))
+            .json(&webhook(namespace, url))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), status);
+        let body = response.into_string().await.expect("response body");
+        assert!(body.starts_with(error), "{body}");
+    }
+
+    // the receiver must not be on the private network, unless allowed
+    env::set_var("METTA_KG_IMPORT_ALLOW_PRIVATE", "false");
+    let response = client
+        .post("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&webhook("test", server.url("/hook")))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    env::set_var("METTA_KG_IMPORT_ALLOW_PRIVATE", "true");
+
+    let response = client
+        .post("/webhooks")
+        .header(Header::new("authorization", token.code.clone()))
+        .json(&webhook("test", server.url("/hook")))
+        .dispatch()
+        .await;
+    let created: CreatedWebhook = response.into_json().await.expect("webhook");
+
+    // webhooks are only visible within the namespace of the token
+    let response = client
+        .delete(format!("/webhooks/{}", created.webhook.id))
+        .header(Header::new("authorization", other.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client
+        .delete(format!("/webhooks/{}", created.webhook.id))
+        .header(Header::new("authorization", token.code.clone()))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    env::remove_var("METTA_KG_SECRET");
+    common::teardown_database();
+}
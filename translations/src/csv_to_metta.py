@@ -1,13 +1,14 @@
 import csv
+import re
 import hyperon
 import numpy as np
 
 from io import StringIO
 
 
-def csv_to_matrix(filename: str, delimiter: str=",", quotechar: str='"', lineterminator:str ="\r\n") -> list[list[str]]:
+def csv_to_matrix(filename: str, delimiter: str=",", quotechar: str='"', lineterminator:str ="\r\n", escapechar: str = None) -> list[list[str]]:
     with open(filename, mode="r") as f:
-        r = csv.reader(f, delimiter=delimiter, quotechar=quotechar, lineterminator=lineterminator, strict=True)
+        r = csv.reader(f, delimiter=delimiter, quotechar=quotechar, lineterminator=lineterminator, escapechar=escapechar, strict=True)
         lines = [row for row in r if len(row) > 0]
     line_len = len(lines[0])
     for e, l in enumerate(lines):
@@ -52,8 +53,7 @@ def parse_metta(mettastr: str, kb: hyperon.SpaceRef = None) -> hyperon.MeTTa:
 
 # row based without header
 def matrix_to_row_based_metta(csvlist: list[list[str]]) -> str:
-    return '\n'.join(['(' + str(e) + ' (' + ' '.join(['"' + elem + '"' for elem in row]) + ')' + ')' for e, row in
-                      enumerate(csvlist)])
+    return csv_matrix_to_metta(csvlist)
 
 
 def matrix_from_row_based_metta(m: hyperon.MeTTa) -> list[list[str]]:
@@ -66,8 +66,7 @@ def matrix_from_row_based_metta(m: hyperon.MeTTa) -> list[list[str]]:
 
 # row based with header
 def matrix_to_header_row_based(csvlist: list[list[str]]) -> str:
-    return '(' + 'header ' + '(' + ' '.join([f'"{t}"' for t in csvlist[0]]) + '))\n' + matrix_to_row_based_metta(
-        csvlist[1:])
+    return csv_matrix_to_metta(csvlist, header=True)
 
 
 def matrix_from_header_row_based(m: hyperon.MeTTa) -> list[list[str]]:
@@ -82,7 +81,7 @@ def matrix_from_header_row_based(m: hyperon.MeTTa) -> list[list[str]]:
 
 # column based
 def matrix_to_column_based_metta(csvmatrix: list[list[str]]) -> str:
-    return matrix_to_row_based_metta([list(a) for a in np.transpose(csvmatrix)])
+    return csv_matrix_to_metta(csvmatrix, direction="column")
 
 
 def matrix_from_column_based_metta(metta: hyperon.MeTTa) -> list[list[str, str]]:
@@ -158,7 +157,7 @@ def dict_from_function_metta(metta: hyperon.MeTTa) -> list[dict[str, str]]:
 
 # cell based unlabeled
 def matrix_to_cell_metta_unlabeled(matrix: list[list[str]]) -> str:
-    return '\n'.join([f'(= (value ({rownr} {colnr})) "{value}")' for rownr, row in enumerate(matrix) for colnr, value in enumerate(row)])
+    return csv_matrix_to_metta(matrix, direction="cell-unlabeled")
 
 
 def matrix_from_cell_metta_unlabeled(metta: hyperon.MeTTa) -> list[list[str]]:
@@ -172,16 +171,8 @@ def matrix_from_cell_metta_unlabeled(metta: hyperon.MeTTa) -> list[list[str]]:
 
 # cell based labeled
 def matrix_to_cell_metta_labeled(matrix: list[list[str]]) -> str:
-    # assume labels are in row 0 and column 0
-    # TODO top-left corner of the matrix is not used
-    collabels = matrix[0][1:]
-    rowlabels = [r[0] for r in matrix[1:]]
-    # print("rowlabels", rowlabels)
-
-
-    return '\n'.join([f'(= (value ("{rowlabel}" "{collabel}")) "{value}")'
-                      for rowlabel, row in zip(rowlabels, matrix[1:])
-                      for collabel, value in zip(collabels, row[1:])])
+    # assume labels are in row 0 and column 0, the top-left corner is not used
+    return csv_matrix_to_metta(matrix, direction="cell-labeled")
 
 def matrix_from_cell_metta_labeled(metta: hyperon.MeTTa) -> list[list[str]]:
     rowlabels = list(set([a.get_object().value for a in metta.run(f'!(match &self (= (value ($rowlabel $collabel)) $v) $rowlabel)')[0]]))
@@ -192,3 +183,91 @@ def matrix_from_cell_metta_labeled(metta: hyperon.MeTTa) -> list[list[str]]:
                                        for rowlabel in rowlabels]
 
 
+# configurable translation, producing the shapes above from one function, which the
+# matrix_to_* functions above are shorthands for
+number_regex = re.compile(r"-?\d+(\.\d+)?([eE][+-]?\d+)?")
+
+
+def cell_to_metta(value: str, column_type: str = "string") -> str:
+    match column_type:
+        case "string":
+            return '"' + value.replace('\\', '\\\\').replace('"', '\\"') + '"'
+        case "number":
+            if not number_regex.fullmatch(value.strip()):
+                raise ValueError(f"'{value}' is not a number")
+            return value.strip()
+        case "symbol":
+            if not value or value.startswith("$") or any(c.isspace() or c in '()";' for c in value):
+                raise ValueError(f"'{value}' is not a valid symbol")
+            return value
+    raise ValueError(f"invalid column type {column_type}")
+
+
+def resolve_column(key: str, labels: list[str] | None, n_columns: int) -> int:
+    """Column index of key, which is a label from the first row or a 0-based index"""
+    if labels is not None and key in labels:
+        return labels.index(key)
+    if key.isdigit() and int(key) < n_columns:
+        return int(key)
+    raise ValueError(f"unknown column {key}")
+
+
+def csv_matrix_to_metta(matrix: list[list[str]], direction: str = "row", header: bool = False,
+                        variant: str = "naive", columns: list[str] = None,
+                        types: dict[str, str] = None) -> str:
+    """
+    direction: "row", "column", "cell-unlabeled" or "cell-labeled"
+    header: whether the first row holds column labels, always the case for "cell-labeled"
+    variant: shape of row based translations with a header, "naive", "struct", "field" or
+             "functional"
+    columns: subset of the columns to translate, by label or 0-based index
+    types: "string" (default), "number" or "symbol" per column, by label or 0-based index
+    """
+    n_columns = len(matrix[0])
+    labels = matrix[0] if header or direction == "cell-labeled" else None
+
+    column_types = ["string"] * n_columns
+    for key, column_type in (types or {}).items():
+        column_types[resolve_column(key, labels, n_columns)] = column_type
+
+    selected = [resolve_column(c, labels, n_columns) for c in columns] if columns else range(n_columns)
+    matrix = [[row[i] for i in selected] for row in matrix]
+    column_types = [column_types[i] for i in selected]
+
+    if direction == "cell-labeled":
+        # labels in the first row and column, the top-left corner is not used
+        collabels = [cell_to_metta(label) for label in matrix[0][1:]]
+        return '\n'.join([f'(= (value ({cell_to_metta(row[0])} {collabel})) {cell_to_metta(value, column_type)})'
+                          for row in matrix[1:]
+                          for collabel, value, column_type in zip(collabels, row[1:], column_types[1:])])
+
+    keys = [cell_to_metta(label) for label in matrix[0]] if header else None
+    body = matrix[1:] if header else matrix
+    cells = [[cell_to_metta(value, column_type) for value, column_type in zip(row, column_types)] for row in body]
+
+    match direction:
+        case "row" if not header:
+            return '\n'.join([f'({i} ({" ".join(row)}))' for i, row in enumerate(cells)])
+        case "row":
+            match variant:
+                case "naive":
+                    return '\n'.join([f'(header ({" ".join(keys)}))'] +
+                                     [f'({i} ({" ".join(row)}))' for i, row in enumerate(cells)])
+                case "struct":
+                    return '\n'.join(['(' + ' '.join([f'({key} {value})' for key, value in zip(keys, row)]) + ')'
+                                      for row in cells])
+                case "field":
+                    return '\n'.join([f'({i} {key} {value})' for i, row in enumerate(cells)
+                                      for key, value in zip(keys, row)])
+                case "functional":
+                    return '\n'.join([f'(= (value ({key} {i})) {value})' for i, row in enumerate(cells)
+                                      for key, value in zip(keys, row)])
+            raise ValueError(f"invalid variant {variant}")
+        case "column":
+            transposed = [list(column) for column in zip(*cells)] if cells else [[] for _ in column_types]
+            names = keys if header else [str(j) for j in range(len(transposed))]
+            return '\n'.join([f'({name} ({" ".join(column)}))' for name, column in zip(names, transposed)])
+        case "cell-unlabeled" if not header:
+            return '\n'.join([f'(= (value ({i} {j})) {value})' for i, row in enumerate(cells)
+                              for j, value in enumerate(row)])
+    raise ValueError(f"invalid combination of direction {direction} and header {header}")
@@ -1,23 +1,31 @@
-import sys
-from csv_to_metta import csv_to_matrix, matrix_to_row_based_metta, matrix_to_column_based_metta, matrix_to_cell_metta_unlabeled, matrix_to_cell_metta_labeled
+import argparse
+import json
+from csv_to_metta import csv_to_matrix, csv_matrix_to_metta
+
+directions = {'1': "row", '2': "column", '3': "cell-unlabeled", '4': "cell-labeled"}
 
 if __name__ == '__main__':
-    filename = sys.argv[1]
-    direction = sys.argv[2]
-    delimiter = sys.argv[3]
+    parser = argparse.ArgumentParser()
+    parser.add_argument("filename")
+    parser.add_argument("direction", choices=directions.keys())
+    parser.add_argument("delimiter")
+    parser.add_argument("--header", action="store_true")
+    parser.add_argument("--variant", default="naive", choices=["naive", "struct", "field", "functional"])
+    parser.add_argument("--quote", default='"')
+    parser.add_argument("--escape", default=None)
+    parser.add_argument("--columns", default=None, help="JSON list of column labels or indices")
+    parser.add_argument("--types", default=None, help="JSON object from column label or index to type")
+    args = parser.parse_args()
 
-    matrix = csv_to_matrix(f"{filename}.csv", delimiter=delimiter)
+    matrix = csv_to_matrix(f"{args.filename}.csv", delimiter=args.delimiter, quotechar=args.quote,
+                           escapechar=args.escape)
 
-    if direction == '1':
-        metta = matrix_to_row_based_metta(matrix)
-    elif direction == '2':
-        metta = matrix_to_column_based_metta(matrix)
-    elif direction == '3':
-        metta = matrix_to_cell_metta_unlabeled(matrix)
-    elif direction == '4':
-        metta = matrix_to_cell_metta_labeled(matrix)
-    else:
-        raise Exception("Invalid parse direction")
+    metta = csv_matrix_to_metta(matrix,
+                                direction=directions[args.direction],
+                                header=args.header,
+                                variant=args.variant,
+                                columns=json.loads(args.columns) if args.columns else None,
+                                types=json.loads(args.types) if args.types else None)
 
-    with open(f"{filename}-output.metta", "w+") as f:
+    with open(f"{args.filename}-output.metta", "w+") as f:
         f.write(metta)
@@ -78,6 +78,99 @@ def dict_list_to_metta(f: IO[str], ds: list[dict]) -> None:
             f.write(f"(json {i} {s})\n")
 
 
+def json_str_to_metta(s: str) -> str:
+    escaped = (s.replace("\\", "\\\\").replace('"', '\\"')
+               .replace("\n", "\\n").replace("\r", "\\r").replace("\t", "\\t"))
+    return f'"{escaped}"'
+
+
+def json_key_to_metta(key) -> str:
+    key = str(key)
+    if key == "" or key.startswith("$") or any(c.isspace() or c in '()";' for c in key):
+        return json_str_to_metta(key)
+    return key
+
+
+def json_leaf_to_metta(value) -> str:
+    if isinstance(value, str):
+        return json_str_to_metta(value)
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if value is None:
+        return "null"
+    return str(value)
+
+
+def json_groups(value, arrays: str = "indexed"):
+    """
+    Yields (groups, leaf) for every scalar in value. A group is an object key followed by the
+    indices of the arrays directly below it, e.g. {"a": [[1]]} yields ([["a", 0, 0]], 1).
+    With arrays="repeated" indices are left out, so array items repeat their key.
+    """
+    if isinstance(value, dict):
+        for key, v in value.items():
+            for groups, leaf in json_groups(v, arrays):
+                if groups and isinstance(groups[0][0], int):
+                    # the indices of an array value share the group of its key
+                    yield [[key] + groups[0]] + groups[1:], leaf
+                else:
+                    yield [[key]] + groups, leaf
+    elif isinstance(value, (list, tuple)):
+        for i, v in enumerate(value):
+            for groups, leaf in json_groups(v, arrays):
+                if arrays == "repeated":
+                    yield groups, leaf
+                elif groups and isinstance(groups[0][0], int):
+                    # nested array: extend the group of the enclosing array
+                    yield [[i] + groups[0]] + groups[1:], leaf
+                else:
+                    yield [[i]] + groups, leaf
+    else:
+        yield [], value
+
+
+def select_root(value, root: str):
+    """Follows a dotted key path such as "data.items.0" into value"""
+    for key in filter(None, root.split(".")):
+        if isinstance(value, list):
+            if not key.isdigit() or int(key) >= len(value):
+                raise KeyError(f"root path '{root}': no index {key}")
+            value = value[int(key)]
+        elif isinstance(value, dict):
+            if key not in value:
+                raise KeyError(f"root path '{root}': no key {key}")
+            value = value[key]
+        else:
+            raise KeyError(f"root path '{root}': cannot descend into {key}")
+    return value
+
+
+def json_value_to_metta(f: IO[str], value, arrays: str = "indexed", nesting: str = "nested") -> None:
+    """
+    Writes one atom per scalar in value.
+
+    nesting="nested": {"a": {"b": 1}} becomes (a (b 1))
+    nesting="path":   {"a": {"b": 1}} becomes ((a b) 1)
+    """
+    if arrays not in ("indexed", "repeated"):
+        raise ValueError(f"invalid array mode: {arrays}")
+    if nesting not in ("nested", "path"):
+        raise ValueError(f"invalid nesting mode: {nesting}")
+
+    for groups, leaf in json_groups(value, arrays):
+        s = json_leaf_to_metta(leaf)
+        groups = [list(map(json_key_to_metta, group)) for group in groups if group]
+
+        if not groups:
+            f.write(s + "\n")
+        elif nesting == "path":
+            f.write(f"(({' '.join(sum(groups, []))}) {s})\n")
+        else:
+            for group in reversed(groups):
+                s = f"({' '.join(group)} {s})"
+            f.write(s + "\n")
+
+
 def expr_to_path(e: hyperon.Atom) -> list:
     if isinstance(e, hyperon.ExpressionAtom):
         return sum(map(expr_to_path, e.get_children()), [])
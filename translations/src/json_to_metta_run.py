@@ -0,0 +1,22 @@
+import sys
+from json import load, loads
+from json_to_metta import json_value_to_metta, select_root
+
+if __name__ == '__main__':
+    filename = sys.argv[1]
+    arrays = sys.argv[2]
+    nesting = sys.argv[3]
+    lines = sys.argv[4] == 'true'
+    root = sys.argv[5] if len(sys.argv) > 5 else ''
+
+    with open(f"{filename}.json", "r") as f:
+        if lines:
+            # JSON Lines are treated as an array of records
+            data = [loads(line) for line in f if line.strip()]
+        else:
+            data = load(f)
+
+    data = select_root(data, root)
+
+    with open(f"{filename}-output.metta", "w+") as f:
+        json_value_to_metta(f, data, arrays=arrays, nesting=nesting)
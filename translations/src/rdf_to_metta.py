@@ -0,0 +1,52 @@
+import rdflib
+from rdflib.term import BNode, Literal, URIRef
+
+
+# Translates RDF serializations that rdflib parses into a plain graph (Turtle, RDF/XML) with the
+# same triple-to-atom mapping as nt_to_metta.py:
+#
+# ((<type s> s) (<type p> p) (<type o> o))
+#
+# Prefix declarations are kept as (Namespace ("<prefix>" "<iri>")) atoms, as in n3_to_metta.py,
+# so that IRIs can be compacted again on export.
+
+string_dt = "http://www.w3.org/2001/XMLSchema#string"
+langstring_dt = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString"
+
+
+def parse_rdf(filename: str, format: str) -> rdflib.Graph:
+    # only bind the prefixes the document declares, not rdflib's defaults
+    g = rdflib.Graph(bind_namespaces="none")
+    g.parse(filename, format=format)
+    return g
+
+
+def quote(s: str) -> str:
+    return '"' + s.replace('\\', '\\\\').replace('"', '\\"') + '"'
+
+
+def term_to_atom(t) -> str:
+    match t:
+        case Literal():
+            # the lexical form, as written in the document
+            value = quote(str(t))
+            if t.language:
+                return f'((literal ({langstring_dt} {t.language})) {value})'
+            elif t.datatype:
+                return f'((literal ({t.datatype})) {value})'
+            else:
+                return f'((literal ({string_dt})) {value})'
+        case BNode():
+            return f'(bnode "{t}")'
+        case URIRef():
+            return f'(uriref {t})'
+    raise NotImplementedError(f"unsupported term {t!r}")
+
+
+def graph_to_mettastr(g: rdflib.Graph, translate_namespaces: bool = True) -> str:
+    atoms = ['(' + ' '.join(term_to_atom(t) for t in triple) + ')' for triple in g]
+
+    if translate_namespaces:
+        atoms += [f'(Namespace ("{prefix}" "{iri}"))' for prefix, iri in g.namespaces()]
+
+    return '\n'.join(atoms)
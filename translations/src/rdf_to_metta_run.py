@@ -0,0 +1,13 @@
+import sys
+from rdf_to_metta import parse_rdf, graph_to_mettastr
+
+if __name__ == '__main__':
+    filename = sys.argv[1]
+    extension = sys.argv[2]
+    rdf_format = sys.argv[3]
+
+    g = parse_rdf(f"{filename}.{extension}", rdf_format)
+    metta = graph_to_mettastr(g)
+
+    with open(f"{filename}-output.metta", "w+") as f:
+        f.write(metta)
@@ -0,0 +1,68 @@
+from rdflib.plugins.sparql import parser, algebra
+from rdflib.term import BNode, Literal, URIRef, Variable
+
+
+# Translates SPARQL SELECT queries into MM2 patterns over triples in the atom convention of
+# nt_to_metta.py:
+#
+# SELECT ?name WHERE { ?person <http://xmlns.com/foaf/0.1/name> ?name }
+# pattern:  ($person (uriref http://xmlns.com/foaf/0.1/name) $name)
+# template: (result (name $name))
+#
+# Only basic graph patterns are supported, optionally with DISTINCT (MM2 results are sets).
+
+string_dt = "http://www.w3.org/2001/XMLSchema#string"
+langstring_dt = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString"
+
+
+def quote(s: str) -> str:
+    return '"' + s.replace('\\', '\\\\').replace('"', '\\"') + '"'
+
+
+def term_to_mm2(t) -> str:
+    match t:
+        case Variable():
+            return f"${t}"
+        case BNode():
+            # blank nodes in a query behave like variables that can not be selected
+            return f"$_{t}"
+        case Literal():
+            # the lexical form, as written in the query
+            value = quote(str(t))
+            if t.language:
+                return f'((literal ({langstring_dt} {t.language})) {value})'
+            elif t.datatype:
+                return f'((literal ({t.datatype})) {value})'
+            else:
+                return f'((literal ({string_dt})) {value})'
+        case URIRef():
+            return f"(uriref {t})"
+    raise NotImplementedError(f"unsupported term {t!r}")
+
+
+def basic_graph_pattern(node) -> list:
+    """Unwraps the projection down to the triples of a basic graph pattern"""
+    while node.name in ("Project", "Distinct", "Reduced"):
+        node = node["p"]
+
+    if node.name != "BGP":
+        raise NotImplementedError(f"SPARQL {node.name} is not supported, only basic graph patterns")
+
+    return node["triples"]
+
+
+def query_to_mm2(query: str) -> dict:
+    q = algebra.translateQuery(parser.parseQuery(query))
+    root = q.algebra
+
+    if root.name != "SelectQuery":
+        raise NotImplementedError(f"SPARQL {root.name} is not supported, only SELECT")
+
+    triples = basic_graph_pattern(root["p"])
+    variables = [str(v) for v in root["PV"]]
+
+    return {
+        "patterns": [f"({' '.join(term_to_mm2(t) for t in triple)})" for triple in triples],
+        "template": f"(result {' '.join(f'({v} ${v})' for v in variables)})",
+        "variables": variables,
+    }
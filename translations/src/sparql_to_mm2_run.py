@@ -0,0 +1,12 @@
+import json
+import sys
+from sparql_to_mm2 import query_to_mm2
+
+if __name__ == '__main__':
+    filename = sys.argv[1]
+
+    with open(f"{filename}.sparql", "r") as f:
+        mm2 = query_to_mm2(f.read())
+
+    with open(f"{filename}-output.metta", "w+") as f:
+        json.dump(mm2, f)
@@ -147,6 +147,43 @@ class CSVToMetta(unittest.TestCase):
         pass
 
 
+class ConfigurableCSVToMeTTa(unittest.TestCase):
+    def setUp(self):
+        self.matrix = [['Customer Id', 'First Name', 'Age'],
+                       ['DD37', 'Sheryl', '31'],
+                       ['1Ef7', 'Preston', '40']]
+
+    def test_cell_labeled(self):
+        self.assertEqual(matrix_to_cell_metta_labeled(self.matrix),
+                         '(= (value ("DD37" "First Name")) "Sheryl")\n'
+                         '(= (value ("DD37" "Age")) "31")\n'
+                         '(= (value ("1Ef7" "First Name")) "Preston")\n'
+                         '(= (value ("1Ef7" "Age")) "40")')
+
+    def test_variants(self):
+        self.assertEqual(csv_matrix_to_metta(self.matrix, header=True, variant="struct"),
+                         '(("Customer Id" "DD37") ("First Name" "Sheryl") ("Age" "31"))\n'
+                         '(("Customer Id" "1Ef7") ("First Name" "Preston") ("Age" "40"))')
+        self.assertEqual(csv_matrix_to_metta(self.matrix, header=True, variant="field", columns=["Age"]),
+                         '(0 "Age" "31")\n'
+                         '(1 "Age" "40")')
+        self.assertEqual(csv_matrix_to_metta(self.matrix, header=True, variant="functional", columns=["1"]),
+                         '(= (value ("First Name" 0)) "Sheryl")\n'
+                         '(= (value ("First Name" 1)) "Preston")')
+
+    def test_types(self):
+        self.assertEqual(csv_matrix_to_metta(self.matrix, direction="column", header=True,
+                                             types={"Age": "number", "0": "symbol"}),
+                         '("Customer Id" (DD37 1Ef7))\n'
+                         '("First Name" ("Sheryl" "Preston"))\n'
+                         '("Age" (31 40))')
+        self.assertRaises(ValueError, csv_matrix_to_metta, self.matrix, header=True, types={"First Name": "number"})
+        self.assertRaises(ValueError, csv_matrix_to_metta, self.matrix, header=True, columns=["Missing"])
+
+    def test_escaping(self):
+        self.assertEqual(cell_to_metta('say "hi"'), '"say \\"hi\\""')
+
+
 class ParseMeTTa(unittest.TestCase):
     def test_parse_metta(self):
         kb = hyperon.SpaceRef(hyperon.GroundingSpace())
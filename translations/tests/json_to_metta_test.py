@@ -111,6 +111,46 @@ class JSONToMeTTa(unittest.TestCase):
                                          "(image \"frodobaggins.jpg\")\n")
 
 
+class JSONValueToMeTTa(unittest.TestCase):
+    def setUp(self):
+        self.data = {"outer": {"foo": {"a": 1}}, "sameAs": ["x", "y"], "m": [[1, 2]]}
+
+    def test_indexed_nested(self):
+        out = StringIO()
+        json_value_to_metta(out, self.data)
+        self.assertEqual(out.getvalue(), '(outer (foo (a 1)))\n'
+                                         '(sameAs 0 "x")\n'
+                                         '(sameAs 1 "y")\n'
+                                         '(m 0 0 1)\n'
+                                         '(m 0 1 2)\n')
+
+    def test_repeated_path(self):
+        out = StringIO()
+        json_value_to_metta(out, self.data, arrays="repeated", nesting="path")
+        self.assertEqual(out.getvalue(), '((outer foo a) 1)\n'
+                                         '((sameAs) "x")\n'
+                                         '((sameAs) "y")\n'
+                                         '((m) 1)\n'
+                                         '((m) 2)\n')
+
+    def test_records(self):
+        out = StringIO()
+        json_value_to_metta(out, [{"a": 1}, {"a": [{"b": 2}]}])
+        self.assertEqual(out.getvalue(), '(0 (a 1))\n'
+                                         '(1 (a 0 (b 2)))\n')
+
+    def test_scalars_and_keys(self):
+        out = StringIO()
+        json_value_to_metta(out, {"k ey": 'a"b\nc', "t": True, "n": None})
+        self.assertEqual(out.getvalue(), '("k ey" "a\\"b\\nc")\n'
+                                         '(t true)\n'
+                                         '(n null)\n')
+
+    def test_select_root(self):
+        data = {"data": {"items": [1, {"x": 2}]}}
+        self.assertEqual(select_root(data, "data.items.1"), {"x": 2})
+        self.assertEqual(select_root(data, ""), data)
+        self.assertRaises(KeyError, select_root, data, "data.missing")
 
 
 if __name__ == '__main__':
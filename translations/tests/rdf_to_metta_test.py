@@ -0,0 +1,58 @@
+import re
+import unittest
+
+from translations.src.rdf_to_metta import *
+
+
+# blank node ids are generated by the parser
+def without_bnode_ids(metta: str) -> set[str]:
+    return set(re.sub(r'\(bnode "[^"]*"\)', '(bnode _)', metta).split('\n'))
+
+
+expected_triples = {
+    '((uriref http://www.w3.org/2001/sw/RDFCore/ntriples/) (uriref http://www.w3.org/1999/02/22-rdf-syntax-ns#type) (uriref http://xmlns.com/foaf/0.1/Document))',
+    '((uriref http://www.w3.org/2001/sw/RDFCore/ntriples/) (uriref http://purl.org/dc/terms/title) ((literal (http://www.w3.org/1999/02/22-rdf-syntax-ns#langString en-US)) "N-Triples"))',
+    '((uriref http://www.w3.org/2001/sw/RDFCore/ntriples/) (uriref http://xmlns.com/foaf/0.1/maker) (bnode _))',
+    '((bnode _) (uriref http://www.w3.org/1999/02/22-rdf-syntax-ns#type) (uriref http://xmlns.com/foaf/0.1/Person))',
+    '((bnode _) (uriref http://xmlns.com/foaf/0.1/name) ((literal (http://www.w3.org/2001/XMLSchema#string)) "Art Barstow"))',
+}
+
+
+class ReadRDF(unittest.TestCase):
+    def test_turtle(self):
+        g = parse_rdf("test_files/ttl_files/wiki_example.ttl", "turtle")
+        atoms = without_bnode_ids(graph_to_mettastr(g))
+
+        self.assertTrue(expected_triples.issubset(atoms))
+        self.assertIn('(Namespace ("foaf" "http://xmlns.com/foaf/0.1/"))', atoms)
+        self.assertIn('(Namespace ("dc" "http://purl.org/dc/terms/"))', atoms)
+
+    def test_rdfxml(self):
+        g = parse_rdf("test_files/rdfxml_files/wiki_example.rdf", "xml")
+        atoms = without_bnode_ids(graph_to_mettastr(g))
+
+        self.assertTrue(expected_triples.issubset(atoms))
+        self.assertIn('(Namespace ("foaf" "http://xmlns.com/foaf/0.1/"))', atoms)
+
+    def test_without_namespaces(self):
+        g = parse_rdf("test_files/ttl_files/wiki_example.ttl", "turtle")
+        atoms = without_bnode_ids(graph_to_mettastr(g, translate_namespaces=False))
+
+        self.assertEqual(atoms, expected_triples)
+
+    def test_literal_lexical_forms(self):
+        g = rdflib.Graph(bind_namespaces="none")
+        g.parse(format="turtle", data=r"""
+            <http://example.org/a> <http://example.org/said> "say \"hi\" \\ bye" .
+            <http://example.org/a> <http://example.org/at> "2020-01-01T00:00:00"^^<http://www.w3.org/2001/XMLSchema#dateTime> .
+        """)
+        atoms = set(graph_to_mettastr(g, translate_namespaces=False).split('\n'))
+
+        self.assertEqual(atoms, {
+            r'((uriref http://example.org/a) (uriref http://example.org/said) ((literal (http://www.w3.org/2001/XMLSchema#string)) "say \"hi\" \\ bye"))',
+            '((uriref http://example.org/a) (uriref http://example.org/at) ((literal (http://www.w3.org/2001/XMLSchema#dateTime)) "2020-01-01T00:00:00"))',
+        })
+
+
+if __name__ == '__main__':
+    unittest.main()
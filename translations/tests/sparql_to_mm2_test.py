@@ -0,0 +1,53 @@
+import unittest
+
+from translations.src.sparql_to_mm2 import *
+
+
+class SPARQLToMM2(unittest.TestCase):
+    def test_single_triple(self):
+        mm2 = query_to_mm2("SELECT ?name WHERE { ?person <http://xmlns.com/foaf/0.1/name> ?name }")
+
+        self.assertEqual(mm2, {
+            "patterns": ["($person (uriref http://xmlns.com/foaf/0.1/name) $name)"],
+            "template": "(result (name $name))",
+            "variables": ["name"],
+        })
+
+    def test_prefixes_and_literals(self):
+        mm2 = query_to_mm2("""
+            PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+            SELECT DISTINCT ?person ?mbox WHERE {
+                ?person foaf:name "Lily" .
+                ?person foaf:mbox ?mbox .
+            }""")
+
+        # rdflib reorders triples by selectivity
+        self.assertCountEqual(mm2["patterns"], [
+            '($person (uriref http://xmlns.com/foaf/0.1/name) ((literal (http://www.w3.org/2001/XMLSchema#string)) "Lily"))',
+            "($person (uriref http://xmlns.com/foaf/0.1/mbox) $mbox)",
+        ])
+        self.assertEqual(mm2["template"], "(result (person $person) (mbox $mbox))")
+
+    def test_literal_lexical_forms(self):
+        mm2 = query_to_mm2(r'SELECT ?s WHERE { ?s <http://example.org/said> "say \"hi\" \\ bye" }')
+        self.assertEqual(mm2["patterns"], [
+            r'($s (uriref http://example.org/said) ((literal (http://www.w3.org/2001/XMLSchema#string)) "say \"hi\" \\ bye"))',
+        ])
+
+        mm2 = query_to_mm2("""
+            SELECT ?s WHERE {
+                ?s <http://example.org/at> "2020-01-01T00:00:00"^^<http://www.w3.org/2001/XMLSchema#dateTime>
+            }""")
+        self.assertEqual(mm2["patterns"], [
+            '($s (uriref http://example.org/at) ((literal (http://www.w3.org/2001/XMLSchema#dateTime)) "2020-01-01T00:00:00"))',
+        ])
+
+    def test_unsupported(self):
+        self.assertRaises(NotImplementedError, query_to_mm2,
+                          "SELECT ?s WHERE { ?s ?p ?o FILTER (?o > 3) }")
+        self.assertRaises(NotImplementedError, query_to_mm2,
+                          "ASK { ?s ?p ?o }")
+
+
+if __name__ == '__main__':
+    unittest.main()